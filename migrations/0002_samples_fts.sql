@@ -0,0 +1,27 @@
+-- Full-text index over sample name and description. External content table so
+-- the text lives only in `samples`; the triggers below keep the index in sync.
+CREATE VIRTUAL TABLE IF NOT EXISTS samples_fts USING fts5(
+    name,
+    description,
+    content='samples',
+    content_rowid='id'
+);
+
+INSERT INTO samples_fts(samples_fts) VALUES ('rebuild');
+
+CREATE TRIGGER IF NOT EXISTS samples_fts_ai AFTER INSERT ON samples BEGIN
+    INSERT INTO samples_fts(rowid, name, description)
+    VALUES (new.id, new.name, new.description);
+END;
+
+CREATE TRIGGER IF NOT EXISTS samples_fts_ad AFTER DELETE ON samples BEGIN
+    INSERT INTO samples_fts(samples_fts, rowid, name, description)
+    VALUES ('delete', old.id, old.name, old.description);
+END;
+
+CREATE TRIGGER IF NOT EXISTS samples_fts_au AFTER UPDATE OF name, description ON samples BEGIN
+    INSERT INTO samples_fts(samples_fts, rowid, name, description)
+    VALUES ('delete', old.id, old.name, old.description);
+    INSERT INTO samples_fts(rowid, name, description)
+    VALUES (new.id, new.name, new.description);
+END;
@@ -0,0 +1,18 @@
+-- Every version of a sample, starting with the one it was created as.
+CREATE TABLE IF NOT EXISTS sample_revisions (
+    id INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+    sample_id INTEGER NOT NULL,
+    revision INTEGER NOT NULL,
+    name TEXT NOT NULL,
+    description TEXT,
+    status TEXT NOT NULL,
+    actor_id INTEGER NOT NULL,
+    restored_from INTEGER,
+    created_at TEXT NOT NULL DEFAULT (datetime('now')),
+    UNIQUE(sample_id, revision),
+    FOREIGN KEY(sample_id) REFERENCES samples(id) ON DELETE CASCADE,
+    FOREIGN KEY(actor_id) REFERENCES users(id) ON DELETE RESTRICT
+);
+
+INSERT INTO sample_revisions (sample_id, revision, name, description, status, actor_id, created_at)
+SELECT id, 1, name, description, status, created_by, updated_at FROM samples;
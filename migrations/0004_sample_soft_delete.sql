@@ -0,0 +1,4 @@
+-- Deleting a sample moves it to the trash; rows are purged after the retention period.
+ALTER TABLE samples ADD COLUMN deleted_at TEXT;
+
+CREATE INDEX IF NOT EXISTS idx_samples_deleted_at ON samples(deleted_at);
@@ -0,0 +1,25 @@
+CREATE TABLE IF NOT EXISTS tags (
+    id INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+    name TEXT NOT NULL UNIQUE COLLATE NOCASE
+);
+
+CREATE TABLE IF NOT EXISTS sample_tags (
+    sample_id INTEGER NOT NULL,
+    tag_id INTEGER NOT NULL,
+    PRIMARY KEY (sample_id, tag_id),
+    FOREIGN KEY(sample_id) REFERENCES samples(id) ON DELETE CASCADE,
+    FOREIGN KEY(tag_id) REFERENCES tags(id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_sample_tags_tag_id ON sample_tags(tag_id);
+
+-- Revisions keep the sample's tags as a JSON array. Older revisions predate
+-- tags and stay NULL, except each sample's latest, which records that it has
+-- none yet.
+ALTER TABLE sample_revisions ADD COLUMN tags TEXT;
+
+UPDATE sample_revisions
+SET tags = '[]'
+WHERE revision = (
+    SELECT MAX(r.revision) FROM sample_revisions r WHERE r.sample_id = sample_revisions.sample_id
+);
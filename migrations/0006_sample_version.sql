@@ -0,0 +1,2 @@
+-- Bumped on every write; exposed as the ETag for optimistic concurrency.
+ALTER TABLE samples ADD COLUMN version INTEGER NOT NULL DEFAULT 1;
@@ -0,0 +1,15 @@
+CREATE TABLE IF NOT EXISTS sample_attachments (
+    id INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+    sample_id INTEGER NOT NULL,
+    file_name TEXT NOT NULL,
+    content_type TEXT NOT NULL,
+    size INTEGER NOT NULL,
+    sha256 TEXT NOT NULL,
+    uploaded_by INTEGER NOT NULL,
+    created_at TEXT NOT NULL DEFAULT (datetime('now')),
+    FOREIGN KEY(sample_id) REFERENCES samples(id) ON DELETE CASCADE,
+    FOREIGN KEY(uploaded_by) REFERENCES users(id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_sample_attachments_sample_id ON sample_attachments(sample_id);
+CREATE INDEX IF NOT EXISTS idx_sample_attachments_sha256 ON sample_attachments(sha256);
@@ -0,0 +1,15 @@
+CREATE TABLE IF NOT EXISTS sample_comments (
+    id INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+    sample_id INTEGER NOT NULL,
+    parent_id INTEGER,
+    author_id INTEGER NOT NULL,
+    body TEXT NOT NULL,
+    created_at TEXT NOT NULL DEFAULT (datetime('now')),
+    updated_at TEXT,
+    deleted_at TEXT,
+    FOREIGN KEY(sample_id) REFERENCES samples(id) ON DELETE CASCADE,
+    FOREIGN KEY(parent_id) REFERENCES sample_comments(id) ON DELETE CASCADE,
+    FOREIGN KEY(author_id) REFERENCES users(id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_sample_comments_sample_id ON sample_comments(sample_id);
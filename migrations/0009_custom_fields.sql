@@ -0,0 +1,26 @@
+ALTER TABLE users ADD COLUMN is_admin INTEGER NOT NULL DEFAULT 0;
+
+CREATE TABLE IF NOT EXISTS custom_fields (
+    id INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+    key TEXT NOT NULL UNIQUE,
+    label TEXT NOT NULL,
+    field_type TEXT NOT NULL CHECK (field_type IN ('text','number','integer','boolean','date','enum')),
+    required INTEGER NOT NULL DEFAULT 0,
+    options TEXT NOT NULL DEFAULT '[]',
+    pattern TEXT,
+    position INTEGER NOT NULL DEFAULT 0,
+    created_at TEXT NOT NULL DEFAULT (datetime('now'))
+);
+
+ALTER TABLE samples ADD COLUMN custom_fields TEXT NOT NULL DEFAULT '{}';
+
+-- Revisions keep the sample's custom field values as a JSON object. Older
+-- revisions predate custom fields and stay NULL, except each sample's latest,
+-- which records that it has none yet.
+ALTER TABLE sample_revisions ADD COLUMN custom_fields TEXT;
+
+UPDATE sample_revisions
+SET custom_fields = '{}'
+WHERE revision = (
+    SELECT MAX(r.revision) FROM sample_revisions r WHERE r.sample_id = sample_revisions.sample_id
+);
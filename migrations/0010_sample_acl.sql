@@ -0,0 +1,17 @@
+CREATE TABLE IF NOT EXISTS sample_acl (
+    sample_id INTEGER NOT NULL,
+    user_id INTEGER NOT NULL,
+    role TEXT NOT NULL CHECK (role IN ('viewer','editor','owner')),
+    granted_by INTEGER NOT NULL,
+    created_at TEXT NOT NULL DEFAULT (datetime('now')),
+    PRIMARY KEY (sample_id, user_id),
+    FOREIGN KEY(sample_id) REFERENCES samples(id) ON DELETE CASCADE,
+    FOREIGN KEY(user_id) REFERENCES users(id) ON DELETE CASCADE,
+    FOREIGN KEY(granted_by) REFERENCES users(id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_sample_acl_user_id ON sample_acl(user_id);
+
+-- every existing sample is owned by whoever created it
+INSERT OR IGNORE INTO sample_acl (sample_id, user_id, role, granted_by)
+SELECT id, created_by, 'owner', created_by FROM samples;
@@ -0,0 +1,16 @@
+-- Status changes due at a set time. The scheduler claims a pending action by
+-- moving it to running before applying it, so it is never picked up twice.
+CREATE TABLE IF NOT EXISTS scheduled_actions (
+    id INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+    sample_id INTEGER NOT NULL REFERENCES samples(id) ON DELETE CASCADE,
+    to_status TEXT NOT NULL CHECK (to_status IN ('draft','active','archived')),
+    run_at TEXT NOT NULL,
+    state TEXT NOT NULL DEFAULT 'pending' CHECK (state IN ('pending','running','done','failed','cancelled')),
+    error TEXT,
+    created_by INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    created_at TEXT NOT NULL DEFAULT (datetime('now')),
+    finished_at TEXT
+);
+
+CREATE INDEX IF NOT EXISTS idx_scheduled_actions_due ON scheduled_actions(state, run_at);
+CREATE INDEX IF NOT EXISTS idx_scheduled_actions_sample ON scheduled_actions(sample_id);
@@ -0,0 +1,14 @@
+-- Results of sample creations made with an idempotency key, so a retried
+-- request or redelivered command returns the first result instead of
+-- creating a duplicate. Keys are per user and expire after
+-- IDEMPOTENCY_TTL_HOURS.
+CREATE TABLE IF NOT EXISTS idempotency_keys (
+    user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    key TEXT NOT NULL,
+    request_hash TEXT NOT NULL,
+    response_json TEXT NOT NULL,
+    created_at TEXT NOT NULL DEFAULT (datetime('now')),
+    PRIMARY KEY (user_id, key)
+);
+
+CREATE INDEX IF NOT EXISTS idx_idempotency_keys_created ON idempotency_keys(created_at);
@@ -0,0 +1,16 @@
+-- Named filter, sort and column sets for the samples list. `filters` holds a
+-- `SampleQuery` as JSON and `columns` a JSON array of column names. Each user
+-- may pin one view as the default for a bare /samples.
+CREATE TABLE IF NOT EXISTS saved_views (
+    id INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+    user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    name TEXT NOT NULL,
+    filters TEXT NOT NULL DEFAULT '{}',
+    columns TEXT NOT NULL DEFAULT '[]',
+    is_default INTEGER NOT NULL DEFAULT 0,
+    created_at TEXT NOT NULL DEFAULT (datetime('now')),
+    updated_at TEXT NOT NULL DEFAULT (datetime('now')),
+    UNIQUE(user_id, name)
+);
+
+CREATE UNIQUE INDEX IF NOT EXISTS idx_saved_views_default ON saved_views(user_id) WHERE is_default = 1;
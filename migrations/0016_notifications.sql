@@ -0,0 +1,33 @@
+CREATE TABLE IF NOT EXISTS sample_watches (
+    sample_id INTEGER NOT NULL,
+    user_id INTEGER NOT NULL,
+    created_at TEXT NOT NULL DEFAULT (datetime('now')),
+    PRIMARY KEY (sample_id, user_id),
+    FOREIGN KEY(sample_id) REFERENCES samples(id) ON DELETE CASCADE,
+    FOREIGN KEY(user_id) REFERENCES users(id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_sample_watches_user_id ON sample_watches(user_id);
+
+-- creators follow their own samples
+INSERT OR IGNORE INTO sample_watches (sample_id, user_id)
+SELECT id, created_by FROM samples;
+
+-- The message is written when the notification is, so it still reads right
+-- after the sample is renamed or purged.
+CREATE TABLE IF NOT EXISTS notifications (
+    id INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+    user_id INTEGER NOT NULL,
+    sample_id INTEGER,
+    actor_id INTEGER,
+    kind TEXT NOT NULL CHECK (kind IN ('copied','updated','deleted','restored')),
+    message TEXT NOT NULL,
+    read_at TEXT,
+    created_at TEXT NOT NULL DEFAULT (datetime('now')),
+    FOREIGN KEY(user_id) REFERENCES users(id) ON DELETE CASCADE,
+    FOREIGN KEY(sample_id) REFERENCES samples(id) ON DELETE SET NULL,
+    FOREIGN KEY(actor_id) REFERENCES users(id) ON DELETE SET NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_notifications_user ON notifications(user_id, id);
+CREATE INDEX IF NOT EXISTS idx_notifications_unread ON notifications(user_id) WHERE read_at IS NULL;
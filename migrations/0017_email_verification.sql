@@ -0,0 +1,17 @@
+ALTER TABLE users ADD COLUMN email_verified_at TEXT;
+
+-- accounts from before registration existed were made by hand
+UPDATE users SET email_verified_at = created_at;
+
+-- One-time tokens mailed to users. Only a hash of the token is kept.
+CREATE TABLE IF NOT EXISTS user_tokens (
+    token_hash TEXT NOT NULL PRIMARY KEY,
+    user_id INTEGER NOT NULL,
+    purpose TEXT NOT NULL,
+    expires_at TEXT NOT NULL,
+    created_at TEXT NOT NULL DEFAULT (datetime('now')),
+    FOREIGN KEY(user_id) REFERENCES users(id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_user_tokens_user ON user_tokens(user_id, purpose);
+CREATE INDEX IF NOT EXISTS idx_user_tokens_expires ON user_tokens(expires_at);
@@ -0,0 +1,3 @@
+-- Sessions remember the epoch they were created in. Bumping it logs the user
+-- out everywhere, as after a password reset.
+ALTER TABLE users ADD COLUMN session_epoch INTEGER NOT NULL DEFAULT 0;
@@ -0,0 +1,7 @@
+-- Switches admins can flip at runtime. Values are stored as text; a missing
+-- key means the setting's default.
+CREATE TABLE IF NOT EXISTS app_settings (
+    key TEXT NOT NULL PRIMARY KEY,
+    value TEXT NOT NULL,
+    updated_at TEXT NOT NULL DEFAULT (datetime('now'))
+);
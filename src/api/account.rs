@@ -0,0 +1,92 @@
+use axum::{extract::State, routing::post, Json, Router};
+use http::StatusCode;
+use serde_json::{json, Value};
+use tower_sessions::Session;
+
+use crate::{
+    api::error::ApiError,
+    models::{
+        state::WebState,
+        user::{
+            ForgotPasswordInput, MagicLinkInput, RegisterInput, ResetPasswordInput,
+            VerifyEmailInput,
+        },
+    },
+    services,
+    web::auth::start_session,
+};
+
+pub fn router() -> Router<WebState> {
+    Router::new()
+        .route("/register", post(api_register))
+        .route("/verify-email", post(api_verify_email))
+        .route("/forgot-password", post(api_forgot_password))
+        .route("/reset-password", post(api_reset_password))
+        .route("/magic-link", post(api_request_magic_link))
+        .route("/magic-link/verify", post(api_verify_magic_link))
+}
+
+/// Always answers the same for a valid request, taken email or not.
+async fn api_register(
+    State(state): State<WebState>,
+    Json(input): Json<RegisterInput>,
+) -> Result<(StatusCode, Json<Value>), ApiError> {
+    services::account::register(&state, input).await?;
+    Ok((
+        StatusCode::ACCEPTED,
+        Json(json!({ "message": "check your email for a link to finish signing up" })),
+    ))
+}
+
+async fn api_verify_email(
+    State(state): State<WebState>,
+    Json(input): Json<VerifyEmailInput>,
+) -> Result<StatusCode, ApiError> {
+    services::account::verify_email(&state, &input.token).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Answers the same whether or not the email has an account.
+async fn api_forgot_password(
+    State(state): State<WebState>,
+    Json(input): Json<ForgotPasswordInput>,
+) -> Result<(StatusCode, Json<Value>), ApiError> {
+    services::account::request_password_reset(&state, input).await?;
+    Ok((
+        StatusCode::ACCEPTED,
+        Json(json!({ "message": "if that email has an account, a reset link is on its way" })),
+    ))
+}
+
+async fn api_reset_password(
+    State(state): State<WebState>,
+    Json(input): Json<ResetPasswordInput>,
+) -> Result<StatusCode, ApiError> {
+    services::account::reset_password(&state, input).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Answers the same whether or not the email has an account.
+async fn api_request_magic_link(
+    State(state): State<WebState>,
+    Json(input): Json<MagicLinkInput>,
+) -> Result<(StatusCode, Json<Value>), ApiError> {
+    services::account::request_magic_link(&state, input).await?;
+    Ok((
+        StatusCode::ACCEPTED,
+        Json(json!({ "message": "if that email has an account, a login link is on its way" })),
+    ))
+}
+
+/// Signs this client's session in, as `POST /login` does.
+async fn api_verify_magic_link(
+    State(state): State<WebState>,
+    session: Session,
+    Json(input): Json<VerifyEmailInput>,
+) -> Result<StatusCode, ApiError> {
+    let (user_id, epoch) = services::account::consume_magic_link(&state, &input.token).await?;
+    start_session(&session, user_id, epoch)
+        .await
+        .map_err(anyhow::Error::from)?;
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -0,0 +1,129 @@
+use axum::{
+    body::Body,
+    extract::{DefaultBodyLimit, Multipart, Path, State},
+    response::{IntoResponse, Response},
+    routing::get,
+    Json, Router,
+};
+use http::{
+    header::{CONTENT_DISPOSITION, CONTENT_TYPE, X_CONTENT_TYPE_OPTIONS},
+    HeaderValue, Request, StatusCode,
+};
+use tower::ServiceExt;
+use tower_http::services::ServeFile;
+
+use crate::{
+    api::error::ApiError,
+    middleware::ApiUser,
+    models::{attachment::Attachment, share::SampleRole, state::WebState},
+    services::{self, attachment::MAX_ATTACHMENT_BYTES},
+};
+
+pub fn router() -> Router<WebState> {
+    Router::new()
+        .route(
+            "/samples/{id}/attachments",
+            get(api_list_attachments)
+                .post(api_upload_attachments)
+                // room for the multipart framing around a file at the limit
+                .layer(DefaultBodyLimit::max(MAX_ATTACHMENT_BYTES + 64 * 1024)),
+        )
+        .route(
+            "/samples/{id}/attachments/{attachment_id}",
+            get(api_download_attachment).delete(api_delete_attachment),
+        )
+}
+
+async fn api_list_attachments(
+    State(state): State<WebState>,
+    ApiUser(user_id): ApiUser,
+    Path(sample_id): Path<i64>,
+) -> Result<Json<Vec<Attachment>>, ApiError> {
+    services::sample::authorize(&state, sample_id, user_id, SampleRole::Viewer).await?;
+    let attachments = services::attachment::get_attachments(&state, sample_id).await?;
+    Ok(Json(attachments))
+}
+
+/// Accepts `multipart/form-data` with one or more `file` parts.
+async fn api_upload_attachments(
+    State(state): State<WebState>,
+    ApiUser(user_id): ApiUser,
+    Path(sample_id): Path<i64>,
+    multipart: Multipart,
+) -> Result<(StatusCode, Json<Vec<Attachment>>), ApiError> {
+    let added = services::attachment::add_uploads(&state, sample_id, multipart, user_id).await?;
+    Ok((StatusCode::CREATED, Json(added)))
+}
+
+/// Streams the blob back with the type recorded at upload. `ServeFile` takes
+/// care of ranges and conditional requests.
+async fn api_download_attachment(
+    State(state): State<WebState>,
+    ApiUser(user_id): ApiUser,
+    Path((sample_id, attachment_id)): Path<(i64, i64)>,
+    req: Request<Body>,
+) -> Result<Response, ApiError> {
+    services::sample::authorize(&state, sample_id, user_id, SampleRole::Viewer).await?;
+    let attachment = services::attachment::get_attachment(&state, sample_id, attachment_id).await?;
+    let path = services::attachment::attachment_path(&attachment);
+
+    let mut resp = ServeFile::new(path)
+        .oneshot(req)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .into_response();
+    if resp.status() == StatusCode::NOT_FOUND {
+        tracing::error!(id = attachment.id, sha256 = %attachment.sha256, "attachment blob missing");
+        return Err(StatusCode::INTERNAL_SERVER_ERROR.into());
+    }
+
+    let headers = resp.headers_mut();
+    headers.insert(
+        CONTENT_TYPE,
+        HeaderValue::from_str(&attachment.content_type).unwrap(),
+    );
+    headers.insert(
+        CONTENT_DISPOSITION,
+        HeaderValue::from_str(&content_disposition(&attachment)).unwrap(),
+    );
+    headers.insert(X_CONTENT_TYPE_OPTIONS, HeaderValue::from_static("nosniff"));
+    Ok(resp)
+}
+
+/// `filename` carries an ASCII fallback, `filename*` the real name.
+fn content_disposition(attachment: &Attachment) -> String {
+    let kind = if attachment.is_inline() {
+        "inline"
+    } else {
+        "attachment"
+    };
+    let fallback: String = attachment
+        .file_name
+        .chars()
+        .map(|c| {
+            if c.is_ascii_graphic() && c != '"' && c != '\\' || c == ' ' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    let mut encoded = String::new();
+    for b in attachment.file_name.bytes() {
+        if b.is_ascii_alphanumeric() || b"-._~".contains(&b) {
+            encoded.push(b as char);
+        } else {
+            encoded.push_str(&format!("%{b:02X}"));
+        }
+    }
+    format!("{kind}; filename=\"{fallback}\"; filename*=UTF-8''{encoded}")
+}
+
+async fn api_delete_attachment(
+    State(state): State<WebState>,
+    ApiUser(user_id): ApiUser,
+    Path((sample_id, attachment_id)): Path<(i64, i64)>,
+) -> Result<StatusCode, ApiError> {
+    services::attachment::remove_attachment(&state, sample_id, attachment_id, user_id).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
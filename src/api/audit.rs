@@ -0,0 +1,37 @@
+use axum::{
+    extract::{Query, State},
+    routing::get,
+    Json, Router,
+};
+use http::{header::LINK, HeaderMap, HeaderValue};
+
+use crate::{
+    api::error::ApiError,
+    middleware::ApiAdminUser,
+    models::{
+        audit::{AuditPage, AuditQuery},
+        state::WebState,
+    },
+    services,
+};
+
+pub fn router() -> Router<WebState> {
+    Router::new().route("/audit", get(api_list_audit))
+}
+
+async fn api_list_audit(
+    State(state): State<WebState>,
+    ApiAdminUser(_): ApiAdminUser,
+    Query(query): Query<AuditQuery>,
+) -> Result<(HeaderMap, Json<AuditPage>), ApiError> {
+    let page = services::audit::get_entries(&state, &query).await?;
+    let mut hm = HeaderMap::new();
+    if let Some(before_id) = page.next_before_id {
+        let link = format!(
+            "</api/audit?{}>; rel=\"next\"",
+            query.next_page_query(before_id)
+        );
+        hm.insert(LINK, HeaderValue::from_str(&link).unwrap());
+    }
+    Ok((hm, Json(page)))
+}
@@ -0,0 +1,32 @@
+use axum::{extract::State, routing::post, Json, Router};
+use http::StatusCode;
+
+use crate::{
+    api::error::ApiError,
+    middleware::ApiUser,
+    models::{
+        bulk::{BulkRequest, BulkResponse},
+        state::WebState,
+    },
+    services,
+};
+
+pub fn router() -> Router<WebState> {
+    Router::new().route("/samples/bulk", post(api_bulk_samples))
+}
+
+/// `200` when the batch committed, `422` when an all-or-nothing batch was
+/// rolled back. Per-item outcomes are in the body either way.
+async fn api_bulk_samples(
+    State(state): State<WebState>,
+    ApiUser(user_id): ApiUser,
+    Json(req): Json<BulkRequest>,
+) -> Result<(StatusCode, Json<BulkResponse>), ApiError> {
+    let response = services::bulk::run_bulk(&state, req, user_id).await?;
+    let status = if response.committed {
+        StatusCode::OK
+    } else {
+        StatusCode::UNPROCESSABLE_ENTITY
+    };
+    Ok((status, Json(response)))
+}
@@ -0,0 +1,70 @@
+use axum::{
+    extract::{Path, State},
+    routing::{get, patch},
+    Json, Router,
+};
+use http::StatusCode;
+
+use crate::{
+    api::error::ApiError,
+    middleware::ApiUser,
+    models::{
+        comment::{Comment, CommentInput, CommentUpdate, ThreadedComment},
+        share::SampleRole,
+        state::WebState,
+    },
+    services,
+};
+
+pub fn router() -> Router<WebState> {
+    Router::new()
+        .route(
+            "/samples/{id}/comments",
+            get(api_list_comments).post(api_add_comment),
+        )
+        .route(
+            "/samples/{id}/comments/{comment_id}",
+            patch(api_update_comment).delete(api_delete_comment),
+        )
+}
+
+/// Comments in thread order; `parent_id` and `depth` describe the nesting.
+async fn api_list_comments(
+    State(state): State<WebState>,
+    ApiUser(user_id): ApiUser,
+    Path(sample_id): Path<i64>,
+) -> Result<Json<Vec<ThreadedComment>>, ApiError> {
+    services::sample::authorize(&state, sample_id, user_id, SampleRole::Viewer).await?;
+    let comments = services::comment::get_comments(&state, sample_id).await?;
+    Ok(Json(comments))
+}
+
+async fn api_add_comment(
+    State(state): State<WebState>,
+    ApiUser(user_id): ApiUser,
+    Path(sample_id): Path<i64>,
+    Json(input): Json<CommentInput>,
+) -> Result<(StatusCode, Json<Comment>), ApiError> {
+    let comment = services::comment::add_comment(&state, sample_id, input, user_id).await?;
+    Ok((StatusCode::CREATED, Json(comment)))
+}
+
+async fn api_update_comment(
+    State(state): State<WebState>,
+    ApiUser(user_id): ApiUser,
+    Path((sample_id, comment_id)): Path<(i64, i64)>,
+    Json(input): Json<CommentUpdate>,
+) -> Result<Json<Comment>, ApiError> {
+    let comment =
+        services::comment::update_comment(&state, sample_id, comment_id, input, user_id).await?;
+    Ok(Json(comment))
+}
+
+async fn api_delete_comment(
+    State(state): State<WebState>,
+    ApiUser(user_id): ApiUser,
+    Path((sample_id, comment_id)): Path<(i64, i64)>,
+) -> Result<StatusCode, ApiError> {
+    services::comment::delete_comment(&state, sample_id, comment_id, user_id).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -0,0 +1,67 @@
+use axum::{
+    extract::{Path, State},
+    routing::{get, patch},
+    Json, Router,
+};
+use http::StatusCode;
+
+use crate::{
+    api::error::ApiError,
+    middleware::{ApiAdminUser, ApiUser},
+    models::{
+        custom_field::{CustomField, CustomFieldInput, CustomFieldUpdate},
+        state::WebState,
+    },
+    services,
+};
+
+pub fn router() -> Router<WebState> {
+    Router::new()
+        .route(
+            "/custom-fields",
+            get(api_list_fields).post(api_create_field),
+        )
+        .route(
+            "/custom-fields/{id}",
+            patch(api_update_field).delete(api_delete_field),
+        )
+}
+
+async fn api_list_fields(
+    State(state): State<WebState>,
+    ApiUser(_): ApiUser,
+) -> Result<Json<Vec<CustomField>>, ApiError> {
+    let fields = services::custom_field::get_fields(&state).await?;
+    Ok(Json(fields))
+}
+
+async fn api_create_field(
+    State(state): State<WebState>,
+    ApiAdminUser(admin_id): ApiAdminUser,
+    Json(input): Json<CustomFieldInput>,
+) -> Result<(StatusCode, Json<CustomField>), ApiError> {
+    let field = services::custom_field::create_field(&state, input, admin_id).await?;
+    tracing::info!(admin_id, key = %field.key, "custom field created");
+    Ok((StatusCode::CREATED, Json(field)))
+}
+
+async fn api_update_field(
+    State(state): State<WebState>,
+    ApiAdminUser(admin_id): ApiAdminUser,
+    Path(id): Path<i64>,
+    Json(input): Json<CustomFieldUpdate>,
+) -> Result<Json<CustomField>, ApiError> {
+    let field = services::custom_field::update_field(&state, id, input, admin_id).await?;
+    Ok(Json(field))
+}
+
+/// Also strips the field's values from every sample.
+async fn api_delete_field(
+    State(state): State<WebState>,
+    ApiAdminUser(admin_id): ApiAdminUser,
+    Path(id): Path<i64>,
+) -> Result<StatusCode, ApiError> {
+    services::custom_field::delete_field(&state, id, admin_id).await?;
+    tracing::info!(admin_id, id, "custom field deleted");
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -0,0 +1,40 @@
+use axum::{
+    response::{IntoResponse, Response},
+    Json,
+};
+use http::StatusCode;
+use serde_json::json;
+
+use crate::services::error::status_code;
+
+/// Error response for the JSON API: a status code plus `{"error": "..."}`.
+/// Unexpected failures are logged and reported without their details.
+#[derive(Debug)]
+pub struct ApiError(pub StatusCode, pub String);
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        (self.0, Json(json!({ "error": self.1 }))).into_response()
+    }
+}
+
+impl From<anyhow::Error> for ApiError {
+    fn from(err: anyhow::Error) -> Self {
+        let status = status_code(&err);
+        if status.is_server_error() {
+            tracing::error!(?err, "api request failed");
+            ApiError(status, "internal server error".into())
+        } else {
+            ApiError(status, err.to_string())
+        }
+    }
+}
+
+impl From<StatusCode> for ApiError {
+    fn from(status: StatusCode) -> Self {
+        ApiError(
+            status,
+            status.canonical_reason().unwrap_or("error").to_lowercase(),
+        )
+    }
+}
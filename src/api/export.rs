@@ -0,0 +1,63 @@
+use axum::{
+    body::Body,
+    extract::{Query, State},
+    response::{IntoResponse, Response},
+    routing::get,
+    Router,
+};
+use futures_util::StreamExt;
+use http::{
+    header::{CONTENT_DISPOSITION, CONTENT_TYPE},
+    HeaderValue,
+};
+
+use crate::{
+    api::error::ApiError,
+    middleware::ApiUser,
+    models::{
+        export::{ExportFormat, ExportQuery},
+        sample::SampleQuery,
+        state::WebState,
+    },
+    services::{self, export},
+};
+
+pub fn router() -> Router<WebState> {
+    Router::new().route("/samples/export", get(api_export_samples))
+}
+
+async fn api_export_samples(
+    State(state): State<WebState>,
+    ApiUser(user_id): ApiUser,
+    Query(q): Query<ExportQuery>,
+    Query(filters): Query<SampleQuery>,
+) -> Result<Response, ApiError> {
+    let body = match q.format {
+        ExportFormat::Xlsx => Body::from(services::export::xlsx(&state, &filters, user_id).await?),
+        ExportFormat::Csv => {
+            let fields = services::custom_field::get_fields(&state).await?;
+            let header =
+                futures_util::stream::once(std::future::ready(export::csv_header(&fields)));
+            let rows = services::export::stream_samples(&state, &filters, user_id)?
+                .map(move |sample| sample.and_then(|s| export::csv_row(&s, &fields)));
+            Body::from_stream(header.chain(rows))
+        }
+        ExportFormat::Ndjson => {
+            let rows = services::export::stream_samples(&state, &filters, user_id)?
+                .map(|sample| sample.and_then(|s| export::ndjson_row(&s)));
+            Body::from_stream(rows)
+        }
+    };
+
+    let disposition = format!("attachment; filename=\"samples.{}\"", q.format.extension());
+    let mut resp = body.into_response();
+    resp.headers_mut().insert(
+        CONTENT_TYPE,
+        HeaderValue::from_static(q.format.content_type()),
+    );
+    resp.headers_mut().insert(
+        CONTENT_DISPOSITION,
+        HeaderValue::from_str(&disposition).unwrap(),
+    );
+    Ok(resp)
+}
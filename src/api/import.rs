@@ -0,0 +1,56 @@
+use axum::{
+    body::Bytes,
+    extract::{DefaultBodyLimit, Query, State},
+    routing::post,
+    Json, Router,
+};
+use http::{header::CONTENT_TYPE, HeaderMap, StatusCode};
+
+use crate::{
+    api::error::ApiError,
+    middleware::ApiUser,
+    models::{
+        import::{ImportFormat, ImportQuery, ImportReport},
+        state::WebState,
+    },
+    services::{self, import::MAX_IMPORT_BYTES},
+};
+
+pub fn router() -> Router<WebState> {
+    Router::new().route(
+        "/samples/import",
+        post(api_import_samples).layer(DefaultBodyLimit::max(MAX_IMPORT_BYTES)),
+    )
+}
+
+/// Imports the request body as CSV or NDJSON. The format comes from `?format=`
+/// or the content type. Rows with errors make the response a `422` and
+/// nothing is written.
+async fn api_import_samples(
+    State(state): State<WebState>,
+    ApiUser(user_id): ApiUser,
+    Query(q): Query<ImportQuery>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<(StatusCode, Json<ImportReport>), ApiError> {
+    let format = q
+        .format
+        .or_else(|| {
+            headers
+                .get(CONTENT_TYPE)
+                .and_then(|v| v.to_str().ok())
+                .and_then(ImportFormat::detect)
+        })
+        .ok_or(ApiError(
+            StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            "send text/csv or application/x-ndjson, or pass ?format=".into(),
+        ))?;
+
+    let report = services::import::run_import(&state, format, &body, q.dry_run, user_id).await?;
+    let status = if report.errors.is_empty() {
+        StatusCode::OK
+    } else {
+        StatusCode::UNPROCESSABLE_ENTITY
+    };
+    Ok((status, Json(report)))
+}
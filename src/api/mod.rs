@@ -1,9 +1,61 @@
-use crate::models::state::WebState;
-use axum::Router;
+use crate::middleware::audit_context;
+use crate::models::{audit::Channel, state::WebState};
+use account::router as account_router;
+use attachment::router as attachment_router;
+use audit::router as audit_router;
+use axum::{middleware::from_fn, Router};
+use bulk::router as bulk_router;
+use comment::router as comment_router;
+use custom_field::router as custom_field_router;
+use export::router as export_router;
+use import::router as import_router;
+use notification::router as notification_router;
+use revision::router as revision_router;
 use sample::router as sample_router;
+use sample_template::router as sample_template_router;
+use saved_view::router as saved_view_router;
+use schedule::router as schedule_router;
+use setting::router as setting_router;
+use share::router as share_router;
+use stats::router as stats_router;
 
+pub mod account;
+pub mod attachment;
+pub mod audit;
+pub mod bulk;
+pub mod comment;
+pub mod custom_field;
+pub mod error;
+pub mod export;
+pub mod import;
+pub mod notification;
+pub mod revision;
 pub mod sample;
+pub mod sample_template;
+pub mod saved_view;
+pub mod schedule;
+pub mod setting;
+pub mod share;
+pub mod stats;
 
 pub fn router() -> Router<WebState> {
-    Router::new().merge(sample_router())
+    Router::new()
+        .merge(account_router())
+        .merge(sample_router())
+        .merge(revision_router())
+        .merge(bulk_router())
+        .merge(import_router())
+        .merge(export_router())
+        .merge(attachment_router())
+        .merge(comment_router())
+        .merge(share_router())
+        .merge(custom_field_router())
+        .merge(sample_template_router())
+        .merge(schedule_router())
+        .merge(audit_router())
+        .merge(stats_router())
+        .merge(saved_view_router())
+        .merge(notification_router())
+        .merge(setting_router())
+        .layer(from_fn(|req, next| audit_context(Channel::Api, req, next)))
 }
@@ -0,0 +1,101 @@
+use axum::{
+    extract::{Path, Query, State},
+    routing::{get, post},
+    Json, Router,
+};
+use http::{header::LINK, HeaderMap, HeaderValue, StatusCode};
+
+use crate::{
+    api::error::ApiError,
+    middleware::ApiUser,
+    models::{
+        notification::{
+            Notification, NotificationPage, NotificationQuery, UnreadCount, WatchStatus,
+        },
+        state::WebState,
+    },
+    services,
+};
+
+pub fn router() -> Router<WebState> {
+    Router::new()
+        .route(
+            "/samples/{id}/watch",
+            get(api_watch_status)
+                .post(api_watch_sample)
+                .delete(api_unwatch_sample),
+        )
+        .route("/notifications", get(api_list_notifications))
+        .route("/notifications/unread-count", get(api_unread_count))
+        .route("/notifications/read-all", post(api_mark_all_read))
+        .route("/notifications/{id}/read", post(api_mark_read))
+}
+
+async fn api_watch_status(
+    State(state): State<WebState>,
+    ApiUser(user_id): ApiUser,
+    Path(sample_id): Path<i64>,
+) -> Result<Json<WatchStatus>, ApiError> {
+    let status = services::notification::watch_status(&state, sample_id, user_id).await?;
+    Ok(Json(status))
+}
+
+async fn api_watch_sample(
+    State(state): State<WebState>,
+    ApiUser(user_id): ApiUser,
+    Path(sample_id): Path<i64>,
+) -> Result<Json<WatchStatus>, ApiError> {
+    let status = services::notification::watch_sample(&state, sample_id, user_id).await?;
+    Ok(Json(status))
+}
+
+async fn api_unwatch_sample(
+    State(state): State<WebState>,
+    ApiUser(user_id): ApiUser,
+    Path(sample_id): Path<i64>,
+) -> Result<Json<WatchStatus>, ApiError> {
+    let status = services::notification::unwatch_sample(&state, sample_id, user_id).await?;
+    Ok(Json(status))
+}
+
+async fn api_list_notifications(
+    State(state): State<WebState>,
+    ApiUser(user_id): ApiUser,
+    Query(query): Query<NotificationQuery>,
+) -> Result<(HeaderMap, Json<NotificationPage>), ApiError> {
+    let page = services::notification::get_notifications(&state, &query, user_id).await?;
+    let mut hm = HeaderMap::new();
+    if let Some(before_id) = page.next_before_id {
+        let link = format!(
+            "</api/notifications?{}>; rel=\"next\"",
+            query.next_page_query(before_id)
+        );
+        hm.insert(LINK, HeaderValue::from_str(&link).unwrap());
+    }
+    Ok((hm, Json(page)))
+}
+
+async fn api_unread_count(
+    State(state): State<WebState>,
+    ApiUser(user_id): ApiUser,
+) -> Result<Json<UnreadCount>, ApiError> {
+    let unread = services::notification::unread_count(&state, user_id).await?;
+    Ok(Json(UnreadCount { unread }))
+}
+
+async fn api_mark_read(
+    State(state): State<WebState>,
+    ApiUser(user_id): ApiUser,
+    Path(id): Path<i64>,
+) -> Result<Json<Notification>, ApiError> {
+    let notification = services::notification::mark_read(&state, id, user_id).await?;
+    Ok(Json(notification))
+}
+
+async fn api_mark_all_read(
+    State(state): State<WebState>,
+    ApiUser(user_id): ApiUser,
+) -> Result<StatusCode, ApiError> {
+    services::notification::mark_all_read(&state, user_id).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
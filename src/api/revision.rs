@@ -0,0 +1,73 @@
+use axum::{
+    extract::{Path, Query, State},
+    routing::{get, post},
+    Json, Router,
+};
+use http::StatusCode;
+
+use crate::{
+    api::error::ApiError,
+    middleware::ApiUser,
+    models::{
+        revision::{RevisionDiff, RevisionDiffQuery, SampleRevision},
+        sample::Sample,
+        share::SampleRole,
+        state::WebState,
+    },
+    services,
+};
+
+pub fn router() -> Router<WebState> {
+    Router::new()
+        .route("/samples/{id}/revisions", get(api_list_revisions))
+        .route("/samples/{id}/revisions/diff", get(api_diff_revisions))
+        .route("/samples/{id}/revisions/{rev}", get(api_get_revision))
+        .route(
+            "/samples/{id}/revisions/{rev}/restore",
+            post(api_restore_revision),
+        )
+}
+
+async fn api_list_revisions(
+    State(state): State<WebState>,
+    ApiUser(user_id): ApiUser,
+    Path(sample_id): Path<i64>,
+) -> Result<Json<Vec<SampleRevision>>, ApiError> {
+    services::sample::authorize(&state, sample_id, user_id, SampleRole::Viewer).await?;
+    let revisions = services::revision::get_revisions(&state, sample_id).await?;
+    Ok(Json(revisions))
+}
+
+async fn api_get_revision(
+    State(state): State<WebState>,
+    ApiUser(user_id): ApiUser,
+    Path((sample_id, rev)): Path<(i64, i64)>,
+) -> Result<Json<SampleRevision>, ApiError> {
+    services::sample::authorize(&state, sample_id, user_id, SampleRole::Viewer).await?;
+    services::revision::get_revision(&state, sample_id, rev)
+        .await?
+        .map(Json)
+        .ok_or(StatusCode::NOT_FOUND.into())
+}
+
+async fn api_diff_revisions(
+    State(state): State<WebState>,
+    ApiUser(user_id): ApiUser,
+    Path(sample_id): Path<i64>,
+    Query(q): Query<RevisionDiffQuery>,
+) -> Result<Json<RevisionDiff>, ApiError> {
+    services::sample::authorize(&state, sample_id, user_id, SampleRole::Viewer).await?;
+    services::revision::diff_revisions(&state, sample_id, q.from, q.to)
+        .await?
+        .map(Json)
+        .ok_or(StatusCode::NOT_FOUND.into())
+}
+
+async fn api_restore_revision(
+    State(state): State<WebState>,
+    ApiUser(user_id): ApiUser,
+    Path((sample_id, rev)): Path<(i64, i64)>,
+) -> Result<Json<Sample>, ApiError> {
+    let sample = services::revision::restore_revision(&state, sample_id, rev, user_id).await?;
+    Ok(Json(sample))
+}
@@ -1,22 +1,37 @@
 use axum::{
-    extract::{Path, State},
-    routing::get,
+    extract::{Path, Query, State},
+    routing::{get, post},
     Json, Router,
 };
-use http::StatusCode;
+use http::{
+    header::{ETAG, IF_MATCH, LINK},
+    HeaderMap, HeaderValue, StatusCode,
+};
 
 use crate::{
-    middleware::AuthedUser,
+    api::error::ApiError,
+    middleware::ApiUser,
     models::{
-        sample::{Sample, SampleInput},
+        sample::{
+            Sample, SampleInput, SamplePage, SampleQuery, SampleSearchHit, SampleSearchQuery,
+        },
+        share::SampleRole,
         state::WebState,
     },
     services,
 };
 
+const IDEMPOTENCY_KEY: &str = "idempotency-key";
+const IDEMPOTENT_REPLAYED: &str = "idempotent-replayed";
+
 pub fn router() -> Router<WebState> {
     Router::new()
         .route("/samples", get(api_list_samples).post(api_create_sample))
+        .route("/samples/search", get(api_search_samples))
+        .route("/samples/trash", get(api_list_trash))
+        .route("/samples/{id}/restore", post(api_restore_sample))
+        .route("/samples/{id}/reopen", post(api_reopen_sample))
+        .route("/samples/{id}/clone", post(api_clone_sample))
         .route(
             "/samples/{id}",
             get(api_get_sample)
@@ -25,55 +40,150 @@ pub fn router() -> Router<WebState> {
         )
 }
 
+/// With an `Idempotency-Key` header, a retry returns the first result with
+/// `Idempotent-Replayed: true` instead of creating another sample.
 async fn api_create_sample(
     State(state): State<WebState>,
-    AuthedUser(user_id): AuthedUser,
+    ApiUser(user_id): ApiUser,
+    headers: HeaderMap,
     Json(input): Json<SampleInput>,
-) -> Result<Json<Sample>, StatusCode> {
-    let sample = services::sample::create_sample(&state, input, user_id)
-        .await
-        .map_err(|_| StatusCode::BAD_REQUEST)?;
-    Ok(Json(sample))
+) -> Result<(HeaderMap, Json<Sample>), ApiError> {
+    // a key that isn't valid header text is rejected as blank
+    let key = headers
+        .get(IDEMPOTENCY_KEY)
+        .map(|v| v.to_str().unwrap_or_default());
+    let mut hm = HeaderMap::new();
+    let sample = match key {
+        Some(key) => {
+            let (sample, replayed) =
+                services::sample::create_sample_once(&state, input, user_id, key).await?;
+            if replayed {
+                hm.insert(IDEMPOTENT_REPLAYED, HeaderValue::from_static("true"));
+            }
+            sample
+        }
+        None => services::sample::create_sample(&state, input, user_id).await?,
+    };
+    Ok((hm, Json(sample)))
 }
 
 async fn api_list_samples(
     State(state): State<WebState>,
-    AuthedUser(_): AuthedUser,
-) -> Result<Json<Vec<Sample>>, StatusCode> {
-    let samples = services::sample::get_samples(&state).await;
-    Ok(Json(samples))
+    ApiUser(user_id): ApiUser,
+    Query(query): Query<SampleQuery>,
+) -> Result<(HeaderMap, Json<SamplePage>), ApiError> {
+    let page = services::sample::get_samples(&state, &query, user_id).await?;
+
+    let mut hm = HeaderMap::new();
+    if let Some(cursor) = &page.next_cursor {
+        let link = format!(
+            "</api/samples?{}>; rel=\"next\"",
+            query.next_page_query(cursor)
+        );
+        hm.insert(LINK, HeaderValue::from_str(&link).unwrap());
+    }
+    Ok((hm, Json(page)))
+}
+
+async fn api_search_samples(
+    State(state): State<WebState>,
+    ApiUser(user_id): ApiUser,
+    Query(query): Query<SampleSearchQuery>,
+) -> Result<Json<Vec<SampleSearchHit>>, ApiError> {
+    let hits = services::sample::search_samples(&state, &query, user_id).await?;
+    Ok(Json(hits))
+}
+
+/// Reads the version out of a required `If-Match` header. `*` matches any
+/// version, anything else must be one of the ETags we hand out.
+fn if_match_version(headers: &HeaderMap) -> Result<Option<i64>, ApiError> {
+    let raw = headers
+        .get(IF_MATCH)
+        .ok_or(StatusCode::PRECONDITION_REQUIRED)?
+        .to_str()
+        .map_err(|_| StatusCode::BAD_REQUEST)?
+        .trim();
+    if raw == "*" {
+        return Ok(None);
+    }
+    raw.trim_start_matches("W/")
+        .trim_matches('"')
+        .parse()
+        .map(Some)
+        .map_err(|_| ApiError(StatusCode::BAD_REQUEST, "invalid If-Match header".into()))
+}
+
+fn etag_header(sample: &Sample) -> HeaderMap {
+    let mut hm = HeaderMap::new();
+    hm.insert(ETAG, HeaderValue::from_str(&sample.etag()).unwrap());
+    hm
 }
 
 async fn api_get_sample(
     State(state): State<WebState>,
-    AuthedUser(_): AuthedUser,
+    ApiUser(user_id): ApiUser,
     Path(sample_id): Path<i64>,
-) -> Result<Json<Sample>, StatusCode> {
-    services::sample::get_sample_by_id(&state, &sample_id)
-        .await
-        .map(Json)
-        .ok_or(StatusCode::NOT_FOUND)
+) -> Result<(HeaderMap, Json<Sample>), ApiError> {
+    let sample =
+        services::sample::authorize(&state, sample_id, user_id, SampleRole::Viewer).await?;
+    Ok((etag_header(&sample), Json(sample)))
 }
 
 async fn api_update_sample(
     State(state): State<WebState>,
-    AuthedUser(_): AuthedUser,
+    ApiUser(user_id): ApiUser,
     Path(sample_id): Path<i64>,
-    Json(input): Json<SampleInput>,
-) -> Result<Json<Sample>, StatusCode> {
-    services::sample::update_sample_by_id(&state, input, sample_id)
-        .await
-        .map_err(|_| StatusCode::BAD_REQUEST)
-        .map(Json)
+    headers: HeaderMap,
+    Json(mut input): Json<SampleInput>,
+) -> Result<(HeaderMap, Json<Sample>), ApiError> {
+    input.version = if_match_version(&headers)?;
+    let sample = services::sample::update_sample_by_id(&state, input, sample_id, user_id).await?;
+    Ok((etag_header(&sample), Json(sample)))
 }
 
 async fn api_delete_sample(
     State(state): State<WebState>,
-    AuthedUser(_): AuthedUser,
+    ApiUser(user_id): ApiUser,
+    Path(sample_id): Path<i64>,
+    headers: HeaderMap,
+) -> Result<Json<()>, ApiError> {
+    let version = if_match_version(&headers)?;
+    services::sample::delete_sample_by_id(&state, sample_id, version, user_id).await?;
+    Ok(Json(()))
+}
+
+async fn api_list_trash(
+    State(state): State<WebState>,
+    ApiUser(user_id): ApiUser,
+) -> Result<Json<Vec<Sample>>, ApiError> {
+    let samples = services::sample::get_trashed_samples(&state, user_id).await?;
+    Ok(Json(samples))
+}
+
+async fn api_restore_sample(
+    State(state): State<WebState>,
+    ApiUser(user_id): ApiUser,
+    Path(sample_id): Path<i64>,
+) -> Result<Json<Sample>, ApiError> {
+    let sample = services::sample::restore_sample_by_id(&state, sample_id, user_id).await?;
+    Ok(Json(sample))
+}
+
+async fn api_reopen_sample(
+    State(state): State<WebState>,
+    ApiUser(user_id): ApiUser,
+    Path(sample_id): Path<i64>,
+) -> Result<Json<Sample>, ApiError> {
+    let sample = services::sample::reopen_sample_by_id(&state, sample_id, user_id).await?;
+    Ok(Json(sample))
+}
+
+/// Copies the sample into a new draft owned by the caller.
+async fn api_clone_sample(
+    State(state): State<WebState>,
+    ApiUser(user_id): ApiUser,
     Path(sample_id): Path<i64>,
-) -> Result<Json<()>, StatusCode> {
-    services::sample::delete_sample_by_id(&state, sample_id)
-        .await
-        .map_err(|_| StatusCode::BAD_REQUEST)
-        .map(Json)
+) -> Result<(StatusCode, Json<Sample>), ApiError> {
+    let sample = services::sample::clone_sample(&state, sample_id, user_id).await?;
+    Ok((StatusCode::CREATED, Json(sample)))
 }
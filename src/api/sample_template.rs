@@ -0,0 +1,77 @@
+use axum::{
+    extract::{Path, State},
+    routing::{get, post},
+    Json, Router,
+};
+use http::StatusCode;
+
+use crate::{
+    api::error::ApiError,
+    middleware::ApiUser,
+    models::{
+        sample_template::{SampleTemplate, SampleTemplateInput, TemplateFromSample},
+        state::WebState,
+    },
+    services,
+};
+
+pub fn router() -> Router<WebState> {
+    Router::new()
+        .route(
+            "/sample-templates",
+            get(api_list_templates).post(api_create_template),
+        )
+        .route(
+            "/sample-templates/{id}",
+            get(api_get_template).delete(api_delete_template),
+        )
+        .route("/samples/{id}/template", post(api_template_from_sample))
+}
+
+async fn api_list_templates(
+    State(state): State<WebState>,
+    ApiUser(_): ApiUser,
+) -> Result<Json<Vec<SampleTemplate>>, ApiError> {
+    let templates = services::sample_template::get_templates(&state).await?;
+    Ok(Json(templates))
+}
+
+async fn api_get_template(
+    State(state): State<WebState>,
+    ApiUser(_): ApiUser,
+    Path(id): Path<i64>,
+) -> Result<Json<SampleTemplate>, ApiError> {
+    let template = services::sample_template::get_template(&state, id).await?;
+    Ok(Json(template))
+}
+
+async fn api_create_template(
+    State(state): State<WebState>,
+    ApiUser(user_id): ApiUser,
+    Json(input): Json<SampleTemplateInput>,
+) -> Result<(StatusCode, Json<SampleTemplate>), ApiError> {
+    let template = services::sample_template::create_template(&state, input, user_id).await?;
+    Ok((StatusCode::CREATED, Json(template)))
+}
+
+/// Saves the sample's description, tags and custom values as a template.
+async fn api_template_from_sample(
+    State(state): State<WebState>,
+    ApiUser(user_id): ApiUser,
+    Path(sample_id): Path<i64>,
+    Json(input): Json<TemplateFromSample>,
+) -> Result<(StatusCode, Json<SampleTemplate>), ApiError> {
+    let template =
+        services::sample_template::template_from_sample(&state, sample_id, input.name, user_id)
+            .await?;
+    Ok((StatusCode::CREATED, Json(template)))
+}
+
+async fn api_delete_template(
+    State(state): State<WebState>,
+    ApiUser(user_id): ApiUser,
+    Path(id): Path<i64>,
+) -> Result<StatusCode, ApiError> {
+    services::sample_template::delete_template(&state, id, user_id).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
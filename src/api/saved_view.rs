@@ -0,0 +1,95 @@
+use axum::{
+    extract::{Path, State},
+    routing::{get, post},
+    Json, Router,
+};
+use http::StatusCode;
+
+use crate::{
+    api::error::ApiError,
+    middleware::ApiUser,
+    models::{
+        saved_view::{SavedView, SavedViewInput},
+        state::WebState,
+    },
+    services,
+};
+
+pub fn router() -> Router<WebState> {
+    Router::new()
+        .route("/saved-views", get(api_list_views).post(api_save_view))
+        .route(
+            "/saved-views/{id}",
+            get(api_get_view)
+                .patch(api_update_view)
+                .delete(api_delete_view),
+        )
+        .route(
+            "/saved-views/{id}/default",
+            post(api_pin_view).delete(api_unpin_view),
+        )
+}
+
+async fn api_list_views(
+    State(state): State<WebState>,
+    ApiUser(user_id): ApiUser,
+) -> Result<Json<Vec<SavedView>>, ApiError> {
+    let views = services::saved_view::get_views(&state, user_id).await?;
+    Ok(Json(views))
+}
+
+/// Saving under a name that's already taken replaces that view.
+async fn api_save_view(
+    State(state): State<WebState>,
+    ApiUser(user_id): ApiUser,
+    Json(input): Json<SavedViewInput>,
+) -> Result<(StatusCode, Json<SavedView>), ApiError> {
+    let view = services::saved_view::save_view(&state, input, user_id).await?;
+    Ok((StatusCode::CREATED, Json(view)))
+}
+
+async fn api_get_view(
+    State(state): State<WebState>,
+    ApiUser(user_id): ApiUser,
+    Path(id): Path<i64>,
+) -> Result<Json<SavedView>, ApiError> {
+    let view = services::saved_view::get_view(&state, id, user_id).await?;
+    Ok(Json(view))
+}
+
+async fn api_update_view(
+    State(state): State<WebState>,
+    ApiUser(user_id): ApiUser,
+    Path(id): Path<i64>,
+    Json(input): Json<SavedViewInput>,
+) -> Result<Json<SavedView>, ApiError> {
+    let view = services::saved_view::update_view(&state, id, input, user_id).await?;
+    Ok(Json(view))
+}
+
+async fn api_delete_view(
+    State(state): State<WebState>,
+    ApiUser(user_id): ApiUser,
+    Path(id): Path<i64>,
+) -> Result<StatusCode, ApiError> {
+    services::saved_view::delete_view(&state, id, user_id).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn api_pin_view(
+    State(state): State<WebState>,
+    ApiUser(user_id): ApiUser,
+    Path(id): Path<i64>,
+) -> Result<Json<SavedView>, ApiError> {
+    let view = services::saved_view::set_default(&state, id, true, user_id).await?;
+    Ok(Json(view))
+}
+
+async fn api_unpin_view(
+    State(state): State<WebState>,
+    ApiUser(user_id): ApiUser,
+    Path(id): Path<i64>,
+) -> Result<Json<SavedView>, ApiError> {
+    let view = services::saved_view::set_default(&state, id, false, user_id).await?;
+    Ok(Json(view))
+}
@@ -0,0 +1,58 @@
+use axum::{
+    extract::{Path, State},
+    routing::{delete, get},
+    Json, Router,
+};
+use http::StatusCode;
+
+use crate::{
+    api::error::ApiError,
+    middleware::ApiUser,
+    models::{
+        schedule::{ScheduleInput, ScheduledAction},
+        state::WebState,
+    },
+    services,
+};
+
+pub fn router() -> Router<WebState> {
+    Router::new()
+        .route(
+            "/samples/{id}/schedule",
+            get(api_list_actions).post(api_schedule_action),
+        )
+        .route(
+            "/samples/{id}/schedule/{action_id}",
+            delete(api_cancel_action),
+        )
+}
+
+async fn api_list_actions(
+    State(state): State<WebState>,
+    ApiUser(user_id): ApiUser,
+    Path(sample_id): Path<i64>,
+) -> Result<Json<Vec<ScheduledAction>>, ApiError> {
+    let actions = services::schedule::get_actions(&state, sample_id, user_id).await?;
+    Ok(Json(actions))
+}
+
+/// Takes a target `status` and either `run_at` or `after_days`.
+async fn api_schedule_action(
+    State(state): State<WebState>,
+    ApiUser(user_id): ApiUser,
+    Path(sample_id): Path<i64>,
+    Json(input): Json<ScheduleInput>,
+) -> Result<(StatusCode, Json<ScheduledAction>), ApiError> {
+    let action = services::schedule::schedule_action(&state, sample_id, input, user_id).await?;
+    Ok((StatusCode::CREATED, Json(action)))
+}
+
+/// Cancels an action that hasn't run yet.
+async fn api_cancel_action(
+    State(state): State<WebState>,
+    ApiUser(user_id): ApiUser,
+    Path((sample_id, action_id)): Path<(i64, i64)>,
+) -> Result<StatusCode, ApiError> {
+    services::schedule::cancel_action(&state, sample_id, action_id, user_id).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
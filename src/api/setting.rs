@@ -0,0 +1,35 @@
+use axum::{extract::State, routing::get, Json, Router};
+
+use crate::{
+    api::error::ApiError,
+    middleware::ApiAdminUser,
+    models::{
+        setting::{AppSettings, AppSettingsUpdate},
+        state::WebState,
+    },
+    services,
+};
+
+pub fn router() -> Router<WebState> {
+    Router::new().route(
+        "/settings",
+        get(api_get_settings).patch(api_update_settings),
+    )
+}
+
+async fn api_get_settings(
+    State(state): State<WebState>,
+    ApiAdminUser(_): ApiAdminUser,
+) -> Result<Json<AppSettings>, ApiError> {
+    let settings = services::setting::get_settings(&state).await?;
+    Ok(Json(settings))
+}
+
+async fn api_update_settings(
+    State(state): State<WebState>,
+    ApiAdminUser(admin_id): ApiAdminUser,
+    Json(update): Json<AppSettingsUpdate>,
+) -> Result<Json<AppSettings>, ApiError> {
+    let settings = services::setting::update_settings(&state, update, admin_id).await?;
+    Ok(Json(settings))
+}
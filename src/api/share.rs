@@ -0,0 +1,54 @@
+use axum::{
+    extract::{Path, State},
+    routing::{delete, get},
+    Json, Router,
+};
+use http::StatusCode;
+
+use crate::{
+    api::error::ApiError,
+    middleware::ApiUser,
+    models::{
+        share::{SampleShare, ShareInput},
+        state::WebState,
+    },
+    services,
+};
+
+pub fn router() -> Router<WebState> {
+    Router::new()
+        .route(
+            "/samples/{id}/shares",
+            get(api_list_shares).post(api_share_sample),
+        )
+        .route("/samples/{id}/shares/{user_id}", delete(api_unshare_sample))
+}
+
+async fn api_list_shares(
+    State(state): State<WebState>,
+    ApiUser(user_id): ApiUser,
+    Path(sample_id): Path<i64>,
+) -> Result<Json<Vec<SampleShare>>, ApiError> {
+    let shares = services::share::get_shares(&state, sample_id, user_id).await?;
+    Ok(Json(shares))
+}
+
+/// Grants or changes a user's role, looked up by email. Owners only.
+async fn api_share_sample(
+    State(state): State<WebState>,
+    ApiUser(user_id): ApiUser,
+    Path(sample_id): Path<i64>,
+    Json(input): Json<ShareInput>,
+) -> Result<Json<SampleShare>, ApiError> {
+    let share = services::share::share_sample(&state, sample_id, input, user_id).await?;
+    Ok(Json(share))
+}
+
+async fn api_unshare_sample(
+    State(state): State<WebState>,
+    ApiUser(user_id): ApiUser,
+    Path((sample_id, target)): Path<(i64, i64)>,
+) -> Result<StatusCode, ApiError> {
+    services::share::unshare_sample(&state, sample_id, target, user_id).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -0,0 +1,28 @@
+use axum::{
+    extract::{Query, State},
+    routing::get,
+    Json, Router,
+};
+
+use crate::{
+    api::error::ApiError,
+    middleware::ApiUser,
+    models::{
+        state::WebState,
+        stats::{SampleStats, StatsQuery},
+    },
+    services,
+};
+
+pub fn router() -> Router<WebState> {
+    Router::new().route("/samples/stats", get(api_sample_stats))
+}
+
+async fn api_sample_stats(
+    State(state): State<WebState>,
+    ApiUser(user_id): ApiUser,
+    Query(query): Query<StatsQuery>,
+) -> Result<Json<SampleStats>, ApiError> {
+    let stats = services::stats::get_stats(&state, &query, user_id).await?;
+    Ok(Json(stats))
+}
@@ -6,7 +6,7 @@ use argon2::{
 use sqlx::SqlitePool;
 use sqlx::{sqlite::SqlitePoolOptions, Pool, Sqlite};
 
-async fn connect(db_url: &str) -> Result<SqlitePool> {
+pub(crate) async fn connect(db_url: &str) -> Result<SqlitePool> {
     let pool = SqlitePoolOptions::new()
         .max_connections(5)
         .connect(db_url)
@@ -43,7 +43,7 @@ async fn seed_admin(db: &SqlitePool) -> anyhow::Result<()> {
             .to_string();
 
         sqlx::query!(
-            "INSERT INTO users (email, password_hash) VALUES (?, ?)",
+            "INSERT INTO users (email, password_hash, email_verified_at) VALUES (?, ?, datetime('now'))",
             email,
             hash
         )
@@ -52,6 +52,10 @@ async fn seed_admin(db: &SqlitePool) -> anyhow::Result<()> {
 
         tracing::info!("seeded admin user");
     }
+
+    sqlx::query!("UPDATE users SET is_admin = 1 WHERE email = ?", email)
+        .execute(db)
+        .await?;
     Ok(())
 }
 
@@ -72,7 +76,7 @@ async fn seed_user(db: &SqlitePool) -> anyhow::Result<()> {
             .to_string();
 
         sqlx::query!(
-            "INSERT INTO users (email, password_hash) VALUES (?, ?)",
+            "INSERT INTO users (email, password_hash, email_verified_at) VALUES (?, ?, datetime('now'))",
             email,
             hash
         )
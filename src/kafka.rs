@@ -1,24 +1,32 @@
 use crate::{
+    mailer::Mailer,
     models::{
+        attachment::Attachment,
+        audit::Channel,
+        comment::Comment,
         kafka::{KafkaCommand, KafkaEvent},
         sample::Sample,
         state::WebState,
     },
-    services,
+    services::{
+        self,
+        audit::{with_context, AuditContext},
+    },
 };
-use anyhow::Result;
+use anyhow::{bail, Result};
 use futures_util::StreamExt;
 use rdkafka::{
     consumer::{Consumer, StreamConsumer},
     producer::{FutureProducer, FutureRecord},
     ClientConfig, Message,
 };
-use sqlx::{Pool, Sqlite, SqlitePool};
-use std::time::Duration;
+use sqlx::{Pool, Sqlite};
+use std::{sync::Arc, time::Duration};
 
 #[derive(Clone)]
 pub struct EventBus {
-    producer: FutureProducer,
+    /// `None` drops every event; only tests build a bus without a broker.
+    producer: Option<FutureProducer>,
     topic: String,
 }
 
@@ -29,11 +37,19 @@ impl EventBus {
             .set("message.timeout.ms", "5000")
             .create()?;
         Ok(Self {
-            producer,
+            producer: Some(producer),
             topic: topic.to_string(),
         })
     }
 
+    #[cfg(test)]
+    pub(crate) fn disabled() -> Self {
+        Self {
+            producer: None,
+            topic: String::new(),
+        }
+    }
+
     pub async fn sample_created(&self, s: Sample) -> Result<()> {
         self.send(KafkaEvent::SampleCreated { sample: s }).await
     }
@@ -43,8 +59,25 @@ impl EventBus {
     pub async fn sample_deleted(&self, id: i64) -> Result<()> {
         self.send(KafkaEvent::SampleDeleted { id }).await
     }
+    pub async fn sample_restored(&self, s: Sample) -> Result<()> {
+        self.send(KafkaEvent::SampleRestored { sample: s }).await
+    }
+    pub async fn attachment_added(&self, a: Attachment) -> Result<()> {
+        self.send(KafkaEvent::AttachmentAdded { attachment: a })
+            .await
+    }
+    pub async fn attachment_removed(&self, sample_id: i64, id: i64) -> Result<()> {
+        self.send(KafkaEvent::AttachmentRemoved { sample_id, id })
+            .await
+    }
+    pub async fn comment_added(&self, c: Comment) -> Result<()> {
+        self.send(KafkaEvent::CommentAdded { comment: c }).await
+    }
 
     async fn send(&self, ev: KafkaEvent) -> Result<()> {
+        let Some(producer) = &self.producer else {
+            return Ok(());
+        };
         let payload = serde_json::to_vec(&ev)?;
         tracing::info!(
             "publishing to topic={}, bytes={}, event={:?}",
@@ -53,8 +86,7 @@ impl EventBus {
             ev
         );
 
-        let dr = self
-            .producer
+        let dr = producer
             .send(
                 FutureRecord::to(&self.topic)
                     .key("sample")
@@ -73,12 +105,7 @@ impl EventBus {
     }
 }
 
-pub async fn run_command_consumer(
-    db: SqlitePool,
-    brokers: String,
-    topic: String,
-    event_bus: EventBus,
-) -> Result<()> {
+pub async fn run_command_consumer(state: WebState, brokers: String, topic: String) -> Result<()> {
     let consumer: StreamConsumer = ClientConfig::new()
         .set("group.id", "sample-app")
         .set("bootstrap.servers", &brokers)
@@ -94,8 +121,23 @@ pub async fn run_command_consumer(
     while let Some(msg) = stream.next().await {
         if let Ok(m) = msg {
             if let Some(Ok(payload)) = m.payload_view::<str>() {
-                if let Ok(cmd) = serde_json::from_str::<KafkaCommand>(payload) {
-                    handle_command(&db, cmd, &event_bus).await.ok();
+                match serde_json::from_str::<KafkaCommand>(payload) {
+                    Ok(cmd) => {
+                        let ctx = AuditContext {
+                            channel: Channel::Kafka,
+                            request_id: Some(format!(
+                                "{}/{}/{}",
+                                m.topic(),
+                                m.partition(),
+                                m.offset()
+                            )),
+                        };
+                        let handled = with_context(ctx, handle_command(&state, cmd));
+                        if let Err(e) = handled.await {
+                            tracing::warn!(error = %e, "command rejected");
+                        }
+                    }
+                    Err(e) => tracing::warn!(error = %e, "invalid command payload"),
                 }
             }
         }
@@ -104,29 +146,52 @@ pub async fn run_command_consumer(
     Ok(())
 }
 
-async fn handle_command(db: &SqlitePool, cmd: KafkaCommand, event_bus: &EventBus) -> Result<()> {
-    let state = WebState {
-        db: db.clone(),
-        events: event_bus.clone(),
-    };
+/// Who a command without a `user_id` acts as, from `KAFKA_DEFAULT_USER_ID`.
+fn default_command_user() -> Option<i64> {
+    std::env::var("KAFKA_DEFAULT_USER_ID")
+        .ok()
+        .and_then(|id| id.parse().ok())
+}
 
+async fn handle_command(state: &WebState, cmd: KafkaCommand) -> Result<()> {
     tracing::info!("event received, event={:?}", cmd);
 
     match cmd {
-        KafkaCommand::CreateSample { input, user_id } => {
-            services::sample::create_sample(&state, input, user_id).await?;
+        KafkaCommand::CreateSample {
+            input,
+            user_id,
+            idempotency_key: Some(key),
+        } => {
+            let (sample, replayed) =
+                services::sample::create_sample_once(state, input, user_id, &key).await?;
+            if replayed {
+                tracing::info!(id = sample.id, key, "duplicate create command ignored");
+            }
+        }
+        KafkaCommand::CreateSample {
+            input,
+            user_id,
+            idempotency_key: None,
+        } => {
+            services::sample::create_sample(state, input, user_id).await?;
         }
-        KafkaCommand::UpdateSample { id, input, .. } => {
-            services::sample::update_sample_by_id(&state, input, id).await?;
+        KafkaCommand::UpdateSample { id, input, user_id } => {
+            services::sample::update_sample_by_id(state, input, id, user_id).await?;
         }
-        KafkaCommand::DeleteSample { id } => {
-            services::sample::delete_sample_by_id(&state, id).await?;
+        KafkaCommand::DeleteSample { id, user_id } => {
+            let Some(user_id) = user_id.or_else(default_command_user) else {
+                bail!("DeleteSample {id} has no user_id and KAFKA_DEFAULT_USER_ID is not set");
+            };
+            services::sample::delete_sample_by_id(state, id, None, user_id).await?;
+        }
+        KafkaCommand::ReopenSample { id, user_id } => {
+            services::sample::reopen_sample_by_id(state, id, user_id).await?;
         }
     }
     Ok(())
 }
 
-pub async fn setup_kafka(db: Pool<Sqlite>) -> Result<EventBus> {
+pub async fn setup_kafka(db: Pool<Sqlite>, mailer: Arc<dyn Mailer>) -> Result<EventBus> {
     let brokers = std::env::var("KAFKA_BROKERS").unwrap_or_else(|_| "127.0.0.1:19092".into());
     let events_topic =
         std::env::var("KAFKA_EVENTS_TOPIC").unwrap_or_else(|_| "sample-events".into());
@@ -134,16 +199,59 @@ pub async fn setup_kafka(db: Pool<Sqlite>) -> Result<EventBus> {
         std::env::var("KAFKA_COMMANDS_TOPIC").unwrap_or_else(|_| "sample-commands".into());
     let event_bus = EventBus::new(&brokers, &events_topic)?;
 
-    let db_for_consumer = db.clone();
-    let eb_for_consumer = event_bus.clone();
+    let state = WebState {
+        db,
+        events: event_bus.clone(),
+        mailer,
+    };
 
     tokio::spawn(async move {
-        if let Err(e) =
-            run_command_consumer(db_for_consumer, brokers, commands_topic, eb_for_consumer).await
-        {
+        if let Err(e) = run_command_consumer(state, brokers, commands_topic).await {
             tracing::error!(?e, "command consumer crashed");
         }
     });
 
     Ok(event_bus)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::share::SampleRole;
+    use crate::testing;
+
+    #[tokio::test]
+    async fn delete_commands_need_a_user() {
+        let state = testing::state().await;
+        let uid = testing::user(&state.db, "ann@example.com").await;
+        let sample = testing::sample(&state, uid, "Blue dye").await;
+
+        let legacy = format!(r#"{{"DeleteSample":{{"id":{}}}}}"#, sample.id);
+        let cmd = serde_json::from_str::<KafkaCommand>(&legacy).unwrap();
+        assert!(handle_command(&state, cmd).await.is_err());
+        assert!(
+            services::sample::authorize(&state, sample.id, uid, SampleRole::Viewer)
+                .await
+                .is_ok()
+        );
+
+        let cmd = KafkaCommand::DeleteSample {
+            id: sample.id,
+            user_id: Some(uid),
+        };
+        let json = serde_json::to_string(&cmd).unwrap();
+        assert_eq!(
+            json,
+            format!(
+                r#"{{"DeleteSample":{{"id":{},"user_id":{uid}}}}}"#,
+                sample.id
+            )
+        );
+        handle_command(&state, cmd).await.unwrap();
+        assert!(
+            services::sample::authorize(&state, sample.id, uid, SampleRole::Viewer)
+                .await
+                .is_err()
+        );
+    }
+}
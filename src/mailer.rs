@@ -0,0 +1,137 @@
+use anyhow::{bail, Context, Result};
+use chrono::Utc;
+use futures_util::future::BoxFuture;
+use lettre::{
+    message::{header::ContentType, Mailbox},
+    transport::smtp::authentication::Credentials,
+    AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor,
+};
+use std::{path::PathBuf, sync::Arc};
+
+/// A plain-text email.
+#[derive(Debug, Clone)]
+pub struct Email {
+    pub to: String,
+    pub subject: String,
+    pub body: String,
+}
+
+/// Sends mail on the app's behalf. The backend is picked at start-up from
+/// `MAILER`, see [`setup_mailer`].
+pub trait Mailer: Send + Sync {
+    fn send<'a>(&'a self, email: &'a Email) -> BoxFuture<'a, Result<()>>;
+}
+
+/// Writes each email to its own file under `MAIL_DIR` and logs where, so
+/// nothing leaves the machine. For development and tests.
+pub struct FileMailer {
+    dir: PathBuf,
+}
+
+impl FileMailer {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        FileMailer { dir: dir.into() }
+    }
+}
+
+impl Mailer for FileMailer {
+    fn send<'a>(&'a self, email: &'a Email) -> BoxFuture<'a, Result<()>> {
+        Box::pin(async move {
+            tokio::fs::create_dir_all(&self.dir).await?;
+            let name = format!(
+                "{}-{}.eml",
+                Utc::now().format("%Y%m%dT%H%M%S"),
+                uuid::Uuid::new_v4()
+            );
+            let path = self.dir.join(name);
+            let text = format!(
+                "To: {}\nSubject: {}\n\n{}\n",
+                email.to, email.subject, email.body
+            );
+            tokio::fs::write(&path, text).await?;
+            tracing::info!(to = %email.to, subject = %email.subject, path = %path.display(), "wrote email");
+            Ok(())
+        })
+    }
+}
+
+/// Sends through an SMTP server. `SMTP_TLS` is `none` (the default, for a
+/// local stand-in such as Mailpit), `starttls` or `tls`.
+pub struct SmtpMailer {
+    transport: AsyncSmtpTransport<Tokio1Executor>,
+    from: Mailbox,
+}
+
+impl SmtpMailer {
+    pub fn from_env() -> Result<Self> {
+        let host = std::env::var("SMTP_HOST").unwrap_or_else(|_| "127.0.0.1".into());
+        let port: u16 = std::env::var("SMTP_PORT")
+            .unwrap_or_else(|_| "1025".into())
+            .parse()
+            .context("SMTP_PORT must be a port number")?;
+        let tls = std::env::var("SMTP_TLS").unwrap_or_else(|_| "none".into());
+        let mut builder = match tls.as_str() {
+            "none" => AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(&host),
+            "starttls" => AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&host)?,
+            "tls" => AsyncSmtpTransport::<Tokio1Executor>::relay(&host)?,
+            other => bail!("unknown SMTP_TLS `{other}`, expected none, starttls or tls"),
+        }
+        .port(port);
+        if let Ok(username) = std::env::var("SMTP_USERNAME") {
+            let password = std::env::var("SMTP_PASSWORD").unwrap_or_default();
+            builder = builder.credentials(Credentials::new(username, password));
+        }
+        Ok(SmtpMailer {
+            transport: builder.build(),
+            from: from_address()?,
+        })
+    }
+}
+
+impl Mailer for SmtpMailer {
+    fn send<'a>(&'a self, email: &'a Email) -> BoxFuture<'a, Result<()>> {
+        Box::pin(async move {
+            let to: Mailbox = email
+                .to
+                .parse()
+                .with_context(|| format!("bad recipient address {}", email.to))?;
+            let message = Message::builder()
+                .from(self.from.clone())
+                .to(to)
+                .subject(&email.subject)
+                .header(ContentType::TEXT_PLAIN)
+                .body(email.body.clone())?;
+            self.transport.send(message).await?;
+            tracing::info!(to = %email.to, subject = %email.subject, "sent email");
+            Ok(())
+        })
+    }
+}
+
+fn from_address() -> Result<Mailbox> {
+    let from =
+        std::env::var("MAIL_FROM").unwrap_or_else(|_| "Sample App <noreply@example.com>".into());
+    from.parse()
+        .with_context(|| format!("MAIL_FROM is not a valid address: {from}"))
+}
+
+/// Picks the backend from `MAILER`: `file` (the default) or `smtp`.
+pub fn setup_mailer() -> Result<Arc<dyn Mailer>> {
+    let kind = std::env::var("MAILER").unwrap_or_else(|_| "file".into());
+    let mailer: Arc<dyn Mailer> = match kind.as_str() {
+        "file" => {
+            let dir = std::env::var("MAIL_DIR").unwrap_or_else(|_| "data/mail".into());
+            Arc::new(FileMailer::new(dir))
+        }
+        "smtp" => Arc::new(SmtpMailer::from_env()?),
+        other => bail!("unknown MAILER `{other}`, expected file or smtp"),
+    };
+    tracing::info!(mailer = %kind, "mailer ready");
+    Ok(mailer)
+}
+
+/// Absolute link to `path` for use in emails, based on `APP_BASE_URL`.
+pub fn app_url(path: &str) -> String {
+    let base = std::env::var("APP_BASE_URL").unwrap_or_else(|_| "http://127.0.0.1:3000".into());
+    format!("{}{path}", base.trim_end_matches('/'))
+}
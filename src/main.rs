@@ -2,10 +2,15 @@ mod api;
 mod db;
 mod kafka;
 mod log;
+mod mailer;
 mod middleware;
 mod models;
+mod purge;
+mod scheduler;
 mod services;
 mod templates;
+#[cfg(test)]
+mod testing;
 mod web;
 
 use axum::Router;
@@ -14,6 +19,9 @@ use tower_http::services::ServeDir;
 
 use crate::api::router as api_router;
 use crate::kafka::setup_kafka;
+use crate::mailer::setup_mailer;
+use crate::purge::setup_purge;
+use crate::scheduler::setup_scheduler;
 use crate::{db::setup_db, web::router as web_router};
 
 #[tokio::main]
@@ -21,12 +29,16 @@ async fn main() -> anyhow::Result<()> {
     dotenvy::dotenv().ok();
     let tracing = log::setup_logging();
     let db = setup_db().await?;
-    let event_bus = setup_kafka(db.clone()).await?;
+    let mailer = setup_mailer()?;
+    let event_bus = setup_kafka(db.clone(), mailer.clone()).await?;
+    setup_purge(db.clone())?;
 
     let web_state = models::state::WebState {
         db: db.clone(),
         events: event_bus.clone(),
+        mailer,
     };
+    setup_scheduler(web_state.clone())?;
 
     let app = Router::new()
         .merge(web_router())
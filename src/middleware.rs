@@ -1,24 +1,36 @@
-use crate::web::auth::SESSION_USER_ID;
+use crate::models::audit::Channel;
+use crate::services::api_token;
+use crate::services::audit::{with_context, AuditContext};
+use crate::web::auth::{SESSION_EPOCH, SESSION_USER_ID};
 use axum::response::IntoResponse;
 use axum::{
-    extract::FromRequestParts,
+    extract::{FromRef, FromRequestParts, Request},
+    middleware::Next,
     response::{Redirect, Response},
 };
 use base64::{engine::general_purpose, Engine as _};
 use http::HeaderMap;
-use http::{header::CONTENT_TYPE, request::Parts, HeaderValue, StatusCode};
+use http::{
+    header::{AUTHORIZATION, CONTENT_TYPE, WWW_AUTHENTICATE},
+    request::Parts,
+    HeaderValue, StatusCode,
+};
+use sqlx::SqlitePool;
 use tower_sessions::cookie::time::{self, Duration};
 use tower_sessions::cookie::Key;
 use tower_sessions::service::SignedCookie;
 use tower_sessions::{Expiry, MemoryStore, Session, SessionManagerLayer};
 
 // ------ Auth related middleware
+/// The signed-in user, from the session cookie only. The pages use this, so
+/// an API token can't get at them, and in particular can't mint more tokens.
 #[derive(Debug, Clone)]
 pub struct AuthedUser(pub i64);
 
 impl<S> FromRequestParts<S> for AuthedUser
 where
     S: Send + Sync,
+    SqlitePool: FromRef<S>,
 {
     type Rejection = Response;
 
@@ -27,14 +39,140 @@ where
             .await
             .map_err(|_| unauthorized_redirect(parts))?;
 
-        if let Ok(Some(uid)) = session.get::<i64>(SESSION_USER_ID).await {
+        let Ok(Some(uid)) = session.get::<i64>(SESSION_USER_ID).await else {
+            return Err(unauthorized_redirect(parts));
+        };
+        // sessions from before a password reset are no longer any good
+        let epoch = session.get::<i64>(SESSION_EPOCH).await.ok().flatten();
+        let db = SqlitePool::from_ref(state);
+        let current = sqlx::query_scalar!("SELECT session_epoch FROM users WHERE id = ?", uid)
+            .fetch_optional(&db)
+            .await
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR.into_response())?;
+        if current.is_some() && current == epoch {
             Ok(AuthedUser(uid))
         } else {
+            let _ = session.flush().await;
             Err(unauthorized_redirect(parts))
         }
     }
 }
 
+/// The caller of an `/api` route, from a personal access token sent as
+/// `Authorization: Bearer` or else from the session cookie.
+#[derive(Debug, Clone)]
+pub struct ApiUser(pub i64);
+
+impl<S> FromRequestParts<S> for ApiUser
+where
+    S: Send + Sync,
+    SqlitePool: FromRef<S>,
+{
+    type Rejection = Response;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        if let Some(token) = bearer_token(&parts.headers) {
+            return authenticate_bearer(parts, &SqlitePool::from_ref(state), &token).await;
+        }
+        let AuthedUser(uid) = AuthedUser::from_request_parts(parts, state).await?;
+        Ok(ApiUser(uid))
+    }
+}
+
+/// The token from an `Authorization: Bearer` header, if there is one.
+fn bearer_token(headers: &HeaderMap) -> Option<String> {
+    let value = headers.get(AUTHORIZATION)?.to_str().ok()?;
+    let (scheme, token) = value.split_once(' ')?;
+    scheme
+        .eq_ignore_ascii_case("bearer")
+        .then(|| token.trim().to_string())
+}
+
+/// A bad token is refused outright rather than falling back to the session,
+/// and a read-only token can't make changes.
+async fn authenticate_bearer(
+    parts: &Parts,
+    db: &SqlitePool,
+    token: &str,
+) -> Result<ApiUser, Response> {
+    let found = api_token::authenticate(db, token)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR.into_response())?;
+    match found {
+        Some((uid, scope)) if scope.allows(&parts.method) => Ok(ApiUser(uid)),
+        Some(_) => Err(bearer_error(
+            StatusCode::FORBIDDEN,
+            r#"Bearer error="insufficient_scope""#,
+        )),
+        None => Err(bearer_error(
+            StatusCode::UNAUTHORIZED,
+            r#"Bearer error="invalid_token""#,
+        )),
+    }
+}
+
+fn bearer_error(status: StatusCode, challenge: &'static str) -> Response {
+    let mut resp = Response::new(axum::body::Body::empty());
+    *resp.status_mut() = status;
+    resp.headers_mut()
+        .insert(WWW_AUTHENTICATE, HeaderValue::from_static(challenge));
+    resp
+}
+
+/// A signed-in user with `users.is_admin` set. Everyone else gets a `403`.
+#[derive(Debug, Clone)]
+pub struct AdminUser(pub i64);
+
+impl<S> FromRequestParts<S> for AdminUser
+where
+    S: Send + Sync,
+    SqlitePool: FromRef<S>,
+{
+    type Rejection = Response;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let AuthedUser(uid) = AuthedUser::from_request_parts(parts, state).await?;
+        require_admin(&SqlitePool::from_ref(state), uid).await?;
+        Ok(AdminUser(uid))
+    }
+}
+
+/// [`AdminUser`] for `/api` routes, which also takes a Bearer token.
+#[derive(Debug, Clone)]
+pub struct ApiAdminUser(pub i64);
+
+impl<S> FromRequestParts<S> for ApiAdminUser
+where
+    S: Send + Sync,
+    SqlitePool: FromRef<S>,
+{
+    type Rejection = Response;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let ApiUser(uid) = ApiUser::from_request_parts(parts, state).await?;
+        require_admin(&SqlitePool::from_ref(state), uid).await?;
+        Ok(ApiAdminUser(uid))
+    }
+}
+
+async fn require_admin(db: &SqlitePool, uid: i64) -> Result<(), Response> {
+    let is_admin = sqlx::query_scalar!(
+        r#"SELECT is_admin AS "is_admin!: bool" FROM users WHERE id = ?"#,
+        uid
+    )
+    .fetch_optional(db)
+    .await
+    .ok()
+    .flatten()
+    .unwrap_or(false);
+
+    if is_admin {
+        Ok(())
+    } else {
+        Err(StatusCode::FORBIDDEN.into_response())
+    }
+}
+
 fn unauthorized_redirect(parts: &Parts) -> Response {
     if is_htmx(&parts.headers) {
         let mut resp = Response::new(axum::body::Body::empty());
@@ -51,6 +189,33 @@ fn unauthorized_redirect(parts: &Parts) -> Response {
     Redirect::to("/login").into_response()
 }
 
+// --------------- Audit context
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+/// Tags everything the request writes to the audit log with `channel` and a
+/// request id. A caller-supplied `X-Request-Id` is kept, otherwise one is
+/// generated, and either way it is echoed back on the response.
+pub async fn audit_context(channel: Channel, req: Request, next: Next) -> Response {
+    let request_id = req
+        .headers()
+        .get(REQUEST_ID_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .map(str::trim)
+        .filter(|v| !v.is_empty() && v.len() <= 128)
+        .map(str::to_string)
+        .unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+
+    let ctx = AuditContext {
+        channel,
+        request_id: Some(request_id.clone()),
+    };
+    let mut resp = with_context(ctx, next.run(req)).await;
+    if let Ok(value) = HeaderValue::from_str(&request_id) {
+        resp.headers_mut().insert(REQUEST_ID_HEADER, value);
+    }
+    resp
+}
+
 // --------------- Session and session related
 pub fn setup_sessions() -> SessionManagerLayer<MemoryStore, SignedCookie> {
     let secure_session = std::env::var("SECURE_SESSION")
@@ -98,3 +263,112 @@ fn is_json(headers: &HeaderMap) -> bool {
         .unwrap_or(&HeaderValue::from_str("").unwrap())
         == "application/json"
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::api_token::{ApiTokenInput, TokenScope};
+    use crate::models::state::WebState;
+    use crate::testing;
+    use axum::{body::Body, routing::get, Router};
+    use http::Method;
+    use tower::ServiceExt;
+
+    async fn whoami(ApiUser(uid): ApiUser) -> String {
+        uid.to_string()
+    }
+
+    async fn new_token(state: &WebState, uid: i64, scope: TokenScope) -> (i64, String) {
+        let input = ApiTokenInput {
+            name: "ci".into(),
+            scope,
+            expires_in_days: None,
+        };
+        let created = api_token::create_token(state, input, uid).await.unwrap();
+        (created.token.id, created.secret)
+    }
+
+    fn bearer(method: Method, uri: &str, token: &str) -> Request {
+        Request::builder()
+            .method(method)
+            .uri(uri)
+            .header(AUTHORIZATION, format!("Bearer {token}"))
+            .body(Body::empty())
+            .unwrap()
+    }
+
+    async fn call(db: &SqlitePool, method: Method, token: &str) -> Response {
+        let app = Router::new()
+            .route("/", get(whoami).post(whoami))
+            .with_state(db.clone());
+        app.oneshot(bearer(method, "/", token)).await.unwrap()
+    }
+
+    fn challenge(resp: &Response) -> &str {
+        resp.headers()[WWW_AUTHENTICATE].to_str().unwrap()
+    }
+
+    #[tokio::test]
+    async fn bearer_tokens_are_held_to_their_scope() {
+        let state = testing::state().await;
+        let uid = testing::user(&state.db, "ann@example.com").await;
+        let (_, read) = new_token(&state, uid, TokenScope::Read).await;
+        let (_, write) = new_token(&state, uid, TokenScope::Write).await;
+
+        let resp = call(&state.db, Method::GET, &read).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(resp.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert_eq!(body, uid.to_string());
+
+        let resp = call(&state.db, Method::POST, &read).await;
+        assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+        assert!(challenge(&resp).contains("insufficient_scope"));
+        let resp = call(&state.db, Method::POST, &write).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn a_bad_token_does_not_fall_back_to_the_session() {
+        let state = testing::state().await;
+        let resp = call(&state.db, Method::GET, "sat_nope").await;
+        assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+        assert!(challenge(&resp).contains("invalid_token"));
+    }
+
+    #[tokio::test]
+    async fn bearer_tokens_only_open_the_api() {
+        let state = testing::state().await;
+        let uid = testing::user(&state.db, "ann@example.com").await;
+        let (id, token) = new_token(&state, uid, TokenScope::Write).await;
+        let app = Router::new()
+            .merge(crate::web::router())
+            .nest("/api", crate::api::router())
+            .with_state(state.clone())
+            .layer(setup_sessions());
+
+        let resp = app
+            .clone()
+            .oneshot(bearer(Method::GET, "/api/samples", &token))
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+
+        for (method, uri) in [
+            (Method::GET, "/settings/tokens".to_string()),
+            (Method::POST, "/settings/tokens".to_string()),
+            (Method::DELETE, format!("/settings/tokens/{id}")),
+            (Method::GET, "/samples".to_string()),
+        ] {
+            let resp = app
+                .clone()
+                .oneshot(bearer(method, &uri, &token))
+                .await
+                .unwrap();
+            assert!(resp.status().is_redirection(), "{uri}");
+            assert_eq!(resp.headers()["location"], "/login");
+        }
+        assert_eq!(api_token::get_tokens(&state, uid).await.unwrap().len(), 1);
+    }
+}
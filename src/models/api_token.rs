@@ -0,0 +1,73 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+use crate::models::blank_as_none;
+
+/// What a personal access token may do. `read` tokens only get through on
+/// read-only requests such as `GET`; `write` tokens can do whatever their
+/// user can.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
+#[serde(rename_all = "lowercase")]
+#[sqlx(type_name = "TEXT", rename_all = "lowercase")]
+pub enum TokenScope {
+    Read,
+    Write,
+}
+
+impl TokenScope {
+    pub const ALL: [TokenScope; 2] = [TokenScope::Read, TokenScope::Write];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TokenScope::Read => "read",
+            TokenScope::Write => "write",
+        }
+    }
+
+    /// Whether a token with this scope may make a `method` request.
+    pub fn allows(&self, method: &http::Method) -> bool {
+        match self {
+            TokenScope::Read => method.is_safe(),
+            TokenScope::Write => true,
+        }
+    }
+}
+
+impl fmt::Display for TokenScope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// A personal access token as its owner sees it. The secret itself is only
+/// shown once, when the token is created.
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct ApiToken {
+    pub id: i64,
+    pub user_id: i64,
+    pub name: String,
+    /// The start of the token, to tell tokens apart.
+    pub prefix: String,
+    pub scope: TokenScope,
+    pub expires_at: Option<String>,
+    pub last_used_at: Option<String>,
+    pub created_at: String,
+    pub expired: bool,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ApiTokenInput {
+    pub name: String,
+    pub scope: TokenScope,
+    /// Days until the token stops working. Unset means it never does.
+    #[serde(default, deserialize_with = "blank_as_none")]
+    pub expires_in_days: Option<i64>,
+}
+
+/// A token just created, with the secret to hand to the user.
+#[derive(Debug, Clone, Serialize)]
+pub struct NewApiToken {
+    #[serde(flatten)]
+    pub token: ApiToken,
+    pub secret: String,
+}
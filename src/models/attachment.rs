@@ -0,0 +1,33 @@
+use serde::{Deserialize, Serialize};
+
+/// A file attached to a sample. The bytes live on disk under their SHA-256,
+/// so identical uploads share one blob.
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct Attachment {
+    pub id: i64,
+    pub sample_id: i64,
+    pub file_name: String,
+    pub content_type: String,
+    pub size: i64,
+    pub sha256: String,
+    pub uploaded_by: i64,
+    pub created_at: String,
+}
+
+impl Attachment {
+    /// Images and PDFs open in the browser, everything else downloads.
+    pub fn is_inline(&self) -> bool {
+        self.content_type.starts_with("image/") || self.content_type == "application/pdf"
+    }
+
+    pub fn human_size(&self) -> String {
+        let size = self.size as f64;
+        if size < 1024.0 {
+            format!("{} B", self.size)
+        } else if size < 1024.0 * 1024.0 {
+            format!("{:.1} KB", size / 1024.0)
+        } else {
+            format!("{:.1} MB", size / (1024.0 * 1024.0))
+        }
+    }
+}
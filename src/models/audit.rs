@@ -0,0 +1,155 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sqlx::types::Json;
+use std::fmt;
+use std::str::FromStr;
+
+use crate::models::blank_as_none;
+
+/// Where a change came from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
+#[serde(rename_all = "lowercase")]
+#[sqlx(type_name = "TEXT", rename_all = "lowercase")]
+pub enum Channel {
+    Web,
+    Api,
+    Kafka,
+    /// Background tasks such as the scheduler and the trash purge.
+    System,
+}
+
+impl Channel {
+    pub const ALL: [Channel; 4] = [Channel::Web, Channel::Api, Channel::Kafka, Channel::System];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Channel::Web => "web",
+            Channel::Api => "api",
+            Channel::Kafka => "kafka",
+            Channel::System => "system",
+        }
+    }
+}
+
+impl FromStr for Channel {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Channel::ALL
+            .into_iter()
+            .find(|c| c.as_str().eq_ignore_ascii_case(s.trim()))
+            .ok_or_else(|| format!("unknown channel `{s}`, expected web, api, kafka or system"))
+    }
+}
+
+impl fmt::Display for Channel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct AuditEntry {
+    pub id: i64,
+    pub actor_id: Option<i64>,
+    pub actor_email: Option<String>,
+    pub channel: Channel,
+    pub action: String,
+    pub sample_id: Option<i64>,
+    pub before: Option<Json<Value>>,
+    pub after: Option<Json<Value>>,
+    pub request_id: Option<String>,
+    pub created_at: String,
+}
+
+impl AuditEntry {
+    pub fn before_pretty(&self) -> Option<String> {
+        self.before
+            .as_ref()
+            .and_then(|v| serde_json::to_string_pretty(&v.0).ok())
+    }
+
+    pub fn after_pretty(&self) -> Option<String> {
+        self.after
+            .as_ref()
+            .and_then(|v| serde_json::to_string_pretty(&v.0).ok())
+    }
+}
+
+/// Filters for the audit log, newest first. Every field is optional.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct AuditQuery {
+    #[serde(
+        default,
+        deserialize_with = "blank_as_none",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub actor_id: Option<i64>,
+    #[serde(
+        default,
+        deserialize_with = "blank_as_none",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub channel: Option<Channel>,
+    /// Exact action, or a prefix ending in `.` such as `sample.`.
+    #[serde(
+        default,
+        deserialize_with = "blank_as_none",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub action: Option<String>,
+    #[serde(
+        default,
+        deserialize_with = "blank_as_none",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub sample_id: Option<i64>,
+    #[serde(
+        default,
+        deserialize_with = "blank_as_none",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub request_id: Option<String>,
+    #[serde(
+        default,
+        deserialize_with = "blank_as_none",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub since: Option<String>,
+    #[serde(
+        default,
+        deserialize_with = "blank_as_none",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub until: Option<String>,
+    #[serde(
+        default,
+        deserialize_with = "blank_as_none",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub limit: Option<i64>,
+    /// Only entries older than this id, for paging.
+    #[serde(
+        default,
+        deserialize_with = "blank_as_none",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub before_id: Option<i64>,
+}
+
+impl AuditQuery {
+    /// Query string for the next page after `before_id`.
+    pub fn next_page_query(&self, before_id: i64) -> String {
+        let next = AuditQuery {
+            before_id: Some(before_id),
+            ..self.clone()
+        };
+        serde_urlencoded::to_string(&next).unwrap_or_default()
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuditPage {
+    pub items: Vec<AuditEntry>,
+    pub next_before_id: Option<i64>,
+}
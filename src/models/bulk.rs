@@ -0,0 +1,66 @@
+use serde::{Deserialize, Serialize};
+
+use crate::models::sample::{Sample, SampleInput};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BulkMode {
+    /// The first failure rolls back every operation.
+    #[default]
+    AllOrNothing,
+    /// Each operation commits or fails on its own.
+    PerItem,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum BulkOperation {
+    Create {
+        input: SampleInput,
+    },
+    Update {
+        id: i64,
+        input: SampleInput,
+    },
+    Delete {
+        id: i64,
+        #[serde(default)]
+        version: Option<i64>,
+    },
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct BulkRequest {
+    #[serde(default)]
+    pub mode: BulkMode,
+    pub operations: Vec<BulkOperation>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BulkItemStatus {
+    Ok,
+    Failed,
+    /// Succeeded on its own but was undone because another item failed.
+    RolledBack,
+    /// Not attempted because an earlier item failed.
+    Skipped,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct BulkItemResult {
+    pub index: usize,
+    pub status: BulkItemStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sample: Option<Sample>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct BulkResponse {
+    pub committed: bool,
+    pub results: Vec<BulkItemResult>,
+}
@@ -0,0 +1,42 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct Comment {
+    pub id: i64,
+    pub sample_id: i64,
+    pub parent_id: Option<i64>,
+    pub author_id: i64,
+    pub author_email: String,
+    /// Empty once the comment is deleted; the row stays so replies keep
+    /// their place in the thread.
+    pub body: String,
+    pub created_at: String,
+    pub updated_at: Option<String>,
+    pub deleted_at: Option<String>,
+}
+
+/// A comment in thread order, with how many replies deep it sits.
+#[derive(Debug, Clone, Serialize)]
+pub struct ThreadedComment {
+    #[serde(flatten)]
+    pub comment: Comment,
+    pub depth: usize,
+}
+
+impl ThreadedComment {
+    /// Indent used by the comments panel, capped so deep threads stay readable.
+    pub fn indent_rem(&self) -> usize {
+        self.depth.min(6) * 2
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct CommentInput {
+    pub body: String,
+    pub parent_id: Option<i64>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct CommentUpdate {
+    pub body: String,
+}
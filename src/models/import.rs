@@ -0,0 +1,45 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ImportFormat {
+    Csv,
+    Ndjson,
+}
+
+impl ImportFormat {
+    /// Guesses the format from a content type or file name.
+    pub fn detect(hint: &str) -> Option<Self> {
+        let hint = hint.to_ascii_lowercase();
+        if hint.contains("csv") {
+            Some(ImportFormat::Csv)
+        } else if hint.contains("ndjson") || hint.contains("jsonl") || hint.contains("json") {
+            Some(ImportFormat::Ndjson)
+        } else {
+            None
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct ImportQuery {
+    pub format: Option<ImportFormat>,
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ImportRowError {
+    pub line: u64,
+    pub error: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ImportReport {
+    pub dry_run: bool,
+    pub committed: bool,
+    pub total: usize,
+    pub valid: usize,
+    pub errors: Vec<ImportRowError>,
+    pub created: Vec<i64>,
+}
@@ -1,6 +1,10 @@
 use serde::{Deserialize, Serialize};
 
-use crate::models::sample::{Sample, SampleInput};
+use crate::models::{
+    attachment::Attachment,
+    comment::Comment,
+    sample::{Sample, SampleInput},
+};
 
 #[allow(clippy::enum_variant_names)]
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -8,6 +12,10 @@ pub enum KafkaEvent {
     SampleCreated { sample: Sample },
     SampleUpdated { sample: Sample },
     SampleDeleted { id: i64 },
+    SampleRestored { sample: Sample },
+    AttachmentAdded { attachment: Attachment },
+    AttachmentRemoved { sample_id: i64, id: i64 },
+    CommentAdded { comment: Comment },
 }
 
 #[allow(clippy::enum_variant_names)]
@@ -16,6 +24,10 @@ pub enum KafkaCommand {
     CreateSample {
         input: SampleInput,
         user_id: i64,
+        /// Makes a redelivered command return the first result instead of
+        /// creating another sample.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        idempotency_key: Option<String>,
     },
     UpdateSample {
         id: i64,
@@ -24,5 +36,14 @@ pub enum KafkaCommand {
     },
     DeleteSample {
         id: i64,
+        /// Producers from before sample sharing don't send one. Those
+        /// commands act as `KAFKA_DEFAULT_USER_ID`, or are refused if it
+        /// isn't set.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        user_id: Option<i64>,
+    },
+    ReopenSample {
+        id: i64,
+        user_id: i64,
     },
 }
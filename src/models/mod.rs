@@ -1,4 +1,40 @@
+use serde::{Deserialize, Deserializer};
+use std::{fmt, str::FromStr};
+
+pub mod api_token;
+pub mod attachment;
+pub mod audit;
+pub mod bulk;
+pub mod comment;
+pub mod custom_field;
+pub mod export;
+pub mod import;
 pub mod kafka;
+pub mod notification;
+pub mod revision;
 pub mod sample;
+pub mod sample_template;
+pub mod saved_view;
+pub mod schedule;
+pub mod setting;
+pub mod share;
 pub mod state;
+pub mod stats;
 pub mod user;
+
+/// Filter forms send untouched fields as `key=`, which count as unset.
+pub(crate) fn blank_as_none<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: FromStr,
+    T::Err: fmt::Display,
+{
+    match Option::<String>::deserialize(deserializer)? {
+        Some(raw) if !raw.trim().is_empty() => raw
+            .trim()
+            .parse()
+            .map(Some)
+            .map_err(serde::de::Error::custom),
+        _ => Ok(None),
+    }
+}
@@ -0,0 +1,107 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+use crate::models::blank_as_none;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
+#[serde(rename_all = "lowercase")]
+#[sqlx(type_name = "TEXT", rename_all = "lowercase")]
+pub enum NotificationKind {
+    /// Someone made a new sample from the watched one.
+    Copied,
+    Updated,
+    Deleted,
+    Restored,
+}
+
+impl NotificationKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            NotificationKind::Copied => "copied",
+            NotificationKind::Updated => "updated",
+            NotificationKind::Deleted => "deleted",
+            NotificationKind::Restored => "restored",
+        }
+    }
+}
+
+impl fmt::Display for NotificationKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Something that happened to a sample the user watches. `sample_id` is unset
+/// once the sample has been purged.
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct Notification {
+    pub id: i64,
+    pub user_id: i64,
+    pub sample_id: Option<i64>,
+    pub actor_id: Option<i64>,
+    pub kind: NotificationKind,
+    pub message: String,
+    pub read_at: Option<String>,
+    pub created_at: String,
+}
+
+impl Notification {
+    pub fn is_read(&self) -> bool {
+        self.read_at.is_some()
+    }
+}
+
+/// The caller's notifications, newest first.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct NotificationQuery {
+    /// Only unread notifications.
+    #[serde(
+        default,
+        deserialize_with = "blank_as_none",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub unread: Option<bool>,
+    #[serde(
+        default,
+        deserialize_with = "blank_as_none",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub limit: Option<i64>,
+    /// Only notifications older than this id, for paging.
+    #[serde(
+        default,
+        deserialize_with = "blank_as_none",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub before_id: Option<i64>,
+}
+
+impl NotificationQuery {
+    /// Query string for the next page after `before_id`.
+    pub fn next_page_query(&self, before_id: i64) -> String {
+        let next = NotificationQuery {
+            before_id: Some(before_id),
+            ..self.clone()
+        };
+        serde_urlencoded::to_string(&next).unwrap_or_default()
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NotificationPage {
+    pub items: Vec<Notification>,
+    /// Unread notifications in total, not just on this page.
+    pub unread: i64,
+    pub next_before_id: Option<i64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UnreadCount {
+    pub unread: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WatchStatus {
+    pub sample_id: i64,
+    pub watching: bool,
+}
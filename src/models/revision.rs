@@ -0,0 +1,44 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use sqlx::types::Json;
+
+use crate::models::sample::SampleStatus;
+
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct SampleRevision {
+    pub id: i64,
+    pub sample_id: i64,
+    pub revision: i64,
+    pub name: String,
+    pub description: Option<String>,
+    pub status: SampleStatus,
+    /// `None` for revisions from before tags were recorded.
+    pub tags: Option<Json<Vec<String>>>,
+    /// `None` for revisions from before custom values were recorded.
+    pub custom_fields: Option<Json<Map<String, Value>>>,
+    pub actor_id: i64,
+    pub actor_email: String,
+    pub restored_from: Option<i64>,
+    pub created_at: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FieldChange {
+    /// The column, or `cf.<key>` for a custom field.
+    pub field: String,
+    pub from: Option<String>,
+    pub to: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RevisionDiff {
+    pub from: SampleRevision,
+    pub to: SampleRevision,
+    pub changes: Vec<FieldChange>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct RevisionDiffQuery {
+    pub from: i64,
+    pub to: i64,
+}
@@ -1,4 +1,72 @@
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize};
+use serde_json::{Map, Value};
+use sqlx::types::Json;
+use std::fmt;
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
+#[serde(rename_all = "lowercase")]
+#[sqlx(type_name = "TEXT", rename_all = "lowercase")]
+pub enum SampleStatus {
+    Draft,
+    Active,
+    Archived,
+}
+
+impl SampleStatus {
+    pub const ALL: [SampleStatus; 3] = [
+        SampleStatus::Draft,
+        SampleStatus::Active,
+        SampleStatus::Archived,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SampleStatus::Draft => "draft",
+            SampleStatus::Active => "active",
+            SampleStatus::Archived => "archived",
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            SampleStatus::Draft => "Draft",
+            SampleStatus::Active => "Active",
+            SampleStatus::Archived => "Archived",
+        }
+    }
+
+    /// Statuses a regular update may move to from here, including staying put.
+    /// Archived samples only leave through an explicit reopen.
+    pub fn allowed_next(&self) -> &'static [SampleStatus] {
+        match self {
+            SampleStatus::Draft => &SampleStatus::ALL,
+            SampleStatus::Active => &SampleStatus::ALL,
+            SampleStatus::Archived => &[SampleStatus::Archived],
+        }
+    }
+
+    pub fn can_transition_to(&self, next: SampleStatus) -> bool {
+        self.allowed_next().contains(&next)
+    }
+}
+
+impl FromStr for SampleStatus {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        SampleStatus::ALL
+            .into_iter()
+            .find(|status| status.as_str().eq_ignore_ascii_case(s.trim()))
+            .ok_or_else(|| format!("unknown status `{s}`, expected draft, active or archived"))
+    }
+}
+
+impl fmt::Display for SampleStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
 
 // Domain model
 #[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
@@ -6,15 +74,197 @@ pub struct Sample {
     pub id: i64,
     pub name: String,
     pub description: Option<String>,
-    pub status: String,
+    pub status: SampleStatus,
     pub created_at: String,
     pub updated_at: Option<String>,
     pub created_by: i64,
+    pub deleted_at: Option<String>,
+    pub version: i64,
+    pub tags: Json<Vec<String>>,
+    pub custom_fields: Json<Map<String, Value>>,
+}
+
+impl Sample {
+    pub fn etag(&self) -> String {
+        format!("\"{}\"", self.version)
+    }
+
+    /// A custom value as the form shows it, empty when unset.
+    pub fn custom_value(&self, key: &str) -> String {
+        self.custom_fields
+            .get(key)
+            .map(form_value)
+            .unwrap_or_default()
+    }
+}
+
+pub(crate) fn form_value(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        Value::Null => String::new(),
+        other => other.to_string(),
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SampleInput {
     pub name: String,
     pub description: Option<String>,
-    pub status: String,
+    pub status: SampleStatus,
+    /// `None` leaves the sample's tags as they are.
+    #[serde(default, deserialize_with = "deserialize_tags")]
+    pub tags: Option<Vec<String>>,
+    /// Version the client last saw. Updates are rejected when the sample has
+    /// moved on since; `None` skips the check.
+    #[serde(default)]
+    pub version: Option<i64>,
+    /// Values keyed by custom field. `None` leaves them as they are, otherwise
+    /// they replace the sample's current values.
+    #[serde(default)]
+    pub custom_fields: Option<Map<String, Value>>,
+}
+
+impl SampleInput {
+    /// Custom values as `cf.<key>` form fields, for re-posting the input.
+    pub fn custom_form_values(&self) -> Vec<(String, String)> {
+        self.custom_fields
+            .iter()
+            .flatten()
+            .map(|(key, value)| (format!("cf.{key}"), form_value(value)))
+            .collect()
+    }
+}
+
+/// Tags come in as a JSON array from the API and Kafka, and as a single
+/// comma separated field from the HTML form.
+fn deserialize_tags<'de, D>(deserializer: D) -> Result<Option<Vec<String>>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Tags {
+        List(Vec<String>),
+        Csv(String),
+    }
+
+    Ok(
+        Option::<Tags>::deserialize(deserializer)?.map(|tags| match tags {
+            Tags::List(list) => list,
+            Tags::Csv(csv) => csv.split(',').map(str::to_string).collect(),
+        }),
+    )
+}
+
+// Listing
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SampleSort {
+    #[default]
+    Id,
+    Name,
+    Status,
+    CreatedAt,
+    UpdatedAt,
+}
+
+impl SampleSort {
+    pub fn column(&self) -> &'static str {
+        match self {
+            SampleSort::Id => "id",
+            SampleSort::Name => "name",
+            SampleSort::Status => "status",
+            SampleSort::CreatedAt => "created_at",
+            SampleSort::UpdatedAt => "updated_at",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SortOrder {
+    Asc,
+    #[default]
+    Desc,
+}
+
+/// Query string accepted by the sample list endpoints. Every field is optional,
+/// an empty query returns the newest samples first.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SampleQuery {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub limit: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cursor: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub status: Option<SampleStatus>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub created_by: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tag: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub created_after: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub created_before: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub updated_after: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub updated_before: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sort: Option<SampleSort>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub order: Option<SortOrder>,
+}
+
+impl SampleQuery {
+    /// Query string for the page that starts at `cursor`, keeping every filter.
+    pub fn next_page_query(&self, cursor: &str) -> String {
+        let next = SampleQuery {
+            cursor: Some(cursor.to_string()),
+            ..self.clone()
+        };
+        serde_urlencoded::to_string(&next).unwrap_or_default()
+    }
+
+    /// Query string for exporting every sample that matches these filters.
+    pub fn export_query(&self, format: &str) -> String {
+        let filters = SampleQuery {
+            limit: None,
+            cursor: None,
+            ..self.clone()
+        };
+        let mut qs = format!("format={format}");
+        let rest = serde_urlencoded::to_string(&filters).unwrap_or_default();
+        if !rest.is_empty() {
+            qs.push('&');
+            qs.push_str(&rest);
+        }
+        qs
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SamplePage {
+    pub items: Vec<Sample>,
+    pub next_cursor: Option<String>,
+}
+
+// Search
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct SampleSearchQuery {
+    #[serde(default)]
+    pub q: String,
+    pub limit: Option<i64>,
+}
+
+/// A ranked full-text match. Snippets are HTML-escaped with the matched terms
+/// wrapped in `<mark>` so they can be rendered as-is.
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+pub struct SampleSearchHit {
+    #[serde(flatten)]
+    #[sqlx(flatten)]
+    pub sample: Sample,
+    pub rank: f64,
+    pub name_highlight: String,
+    pub description_snippet: Option<String>,
 }
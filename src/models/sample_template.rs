@@ -0,0 +1,44 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use sqlx::types::Json;
+
+use crate::models::sample::form_value;
+
+/// A named set of starting values for new samples.
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct SampleTemplate {
+    pub id: i64,
+    pub name: String,
+    pub description: Option<String>,
+    pub tags: Json<Vec<String>>,
+    pub custom_fields: Json<Map<String, Value>>,
+    pub created_by: i64,
+    pub created_at: String,
+}
+
+impl SampleTemplate {
+    /// A custom value as the form shows it, empty when unset.
+    pub fn custom_value(&self, key: &str) -> String {
+        self.custom_fields
+            .get(key)
+            .map(form_value)
+            .unwrap_or_default()
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct SampleTemplateInput {
+    pub name: String,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub custom_fields: Map<String, Value>,
+}
+
+/// Saves an existing sample's values as a template.
+#[derive(Debug, Clone, Deserialize)]
+pub struct TemplateFromSample {
+    pub name: String,
+}
@@ -0,0 +1,143 @@
+use serde::{Deserialize, Serialize};
+use sqlx::types::Json;
+use std::fmt;
+use std::str::FromStr;
+
+use crate::models::sample::SampleQuery;
+
+/// A column the samples list can show. The actions column is always there.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SampleColumn {
+    Id,
+    Name,
+    Tags,
+    Status,
+    CreatedAt,
+    UpdatedAt,
+    Version,
+}
+
+impl SampleColumn {
+    pub const ALL: [SampleColumn; 7] = [
+        SampleColumn::Id,
+        SampleColumn::Name,
+        SampleColumn::Tags,
+        SampleColumn::Status,
+        SampleColumn::CreatedAt,
+        SampleColumn::UpdatedAt,
+        SampleColumn::Version,
+    ];
+
+    /// What the list shows when no columns are picked.
+    pub const DEFAULT: [SampleColumn; 4] = [
+        SampleColumn::Id,
+        SampleColumn::Name,
+        SampleColumn::Tags,
+        SampleColumn::Status,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SampleColumn::Id => "id",
+            SampleColumn::Name => "name",
+            SampleColumn::Tags => "tags",
+            SampleColumn::Status => "status",
+            SampleColumn::CreatedAt => "created_at",
+            SampleColumn::UpdatedAt => "updated_at",
+            SampleColumn::Version => "version",
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            SampleColumn::Id => "ID",
+            SampleColumn::Name => "Name",
+            SampleColumn::Tags => "Tags",
+            SampleColumn::Status => "Status",
+            SampleColumn::CreatedAt => "Created",
+            SampleColumn::UpdatedAt => "Updated",
+            SampleColumn::Version => "Version",
+        }
+    }
+
+    /// Puts `columns` in display order without repeats, falling back to the
+    /// default set when none are left.
+    pub fn normalize(columns: &[SampleColumn]) -> Vec<SampleColumn> {
+        let picked: Vec<_> = SampleColumn::ALL
+            .into_iter()
+            .filter(|c| columns.contains(c))
+            .collect();
+        if picked.is_empty() {
+            SampleColumn::DEFAULT.to_vec()
+        } else {
+            picked
+        }
+    }
+}
+
+impl FromStr for SampleColumn {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        SampleColumn::ALL
+            .into_iter()
+            .find(|c| c.as_str().eq_ignore_ascii_case(s.trim()))
+            .ok_or_else(|| format!("unknown column `{s}`"))
+    }
+}
+
+impl fmt::Display for SampleColumn {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct SavedView {
+    pub id: i64,
+    pub user_id: i64,
+    pub name: String,
+    pub filters: Json<SampleQuery>,
+    pub columns: Json<Vec<SampleColumn>>,
+    pub is_default: bool,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+impl SavedView {
+    /// Query string that opens the list with this view applied.
+    pub fn list_query(&self) -> String {
+        let rest = list_query(&self.filters.0, &self.columns.0);
+        if rest.is_empty() {
+            format!("view={}", self.id)
+        } else {
+            format!("view={}&{rest}", self.id)
+        }
+    }
+}
+
+/// Query string for the samples list with `filters` and `columns`, the
+/// columns repeated as `col`.
+pub fn list_query(filters: &SampleQuery, columns: &[SampleColumn]) -> String {
+    let mut query = serde_urlencoded::to_string(filters).unwrap_or_default();
+    for column in columns {
+        if !query.is_empty() {
+            query.push('&');
+        }
+        query.push_str("col=");
+        query.push_str(column.as_str());
+    }
+    query
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct SavedViewInput {
+    pub name: String,
+    #[serde(default)]
+    pub filters: SampleQuery,
+    #[serde(default)]
+    pub columns: Vec<SampleColumn>,
+    #[serde(default)]
+    pub is_default: bool,
+}
@@ -0,0 +1,58 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+use crate::models::sample::SampleStatus;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
+#[serde(rename_all = "lowercase")]
+#[sqlx(type_name = "TEXT", rename_all = "lowercase")]
+pub enum ActionState {
+    Pending,
+    /// Claimed by the scheduler and being applied.
+    Running,
+    Done,
+    Failed,
+    Cancelled,
+}
+
+impl ActionState {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ActionState::Pending => "pending",
+            ActionState::Running => "running",
+            ActionState::Done => "done",
+            ActionState::Failed => "failed",
+            ActionState::Cancelled => "cancelled",
+        }
+    }
+}
+
+impl fmt::Display for ActionState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// A status change to apply to a sample once `run_at` (UTC) has passed.
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct ScheduledAction {
+    pub id: i64,
+    pub sample_id: i64,
+    pub to_status: SampleStatus,
+    pub run_at: String,
+    pub state: ActionState,
+    pub error: Option<String>,
+    pub created_by: i64,
+    pub created_at: String,
+    pub finished_at: Option<String>,
+}
+
+/// Schedules a status change either at `run_at` or `after_days` from now.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ScheduleInput {
+    pub status: SampleStatus,
+    #[serde(default)]
+    pub run_at: Option<String>,
+    #[serde(default)]
+    pub after_days: Option<i64>,
+}
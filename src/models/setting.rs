@@ -0,0 +1,15 @@
+use serde::{Deserialize, Serialize};
+
+/// Switches admins can change while the app runs.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AppSettings {
+    /// Whether users may log in with a link mailed to them instead of a
+    /// password. Off unless an admin turns it on.
+    pub magic_links_enabled: bool,
+}
+
+/// A partial update. Unset fields stay as they are.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct AppSettingsUpdate {
+    pub magic_links_enabled: Option<bool>,
+}
@@ -0,0 +1,51 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// What a user may do with a sample. Each role includes the ones before it:
+/// viewers read and comment, editors also change the sample and its
+/// attachments, owners also delete, restore and share it.
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, sqlx::Type,
+)]
+#[serde(rename_all = "lowercase")]
+#[sqlx(type_name = "TEXT", rename_all = "lowercase")]
+pub enum SampleRole {
+    Viewer,
+    Editor,
+    Owner,
+}
+
+impl SampleRole {
+    pub const ALL: [SampleRole; 3] = [SampleRole::Viewer, SampleRole::Editor, SampleRole::Owner];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SampleRole::Viewer => "viewer",
+            SampleRole::Editor => "editor",
+            SampleRole::Owner => "owner",
+        }
+    }
+}
+
+impl fmt::Display for SampleRole {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// A user's entry in a sample's ACL.
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct SampleShare {
+    pub sample_id: i64,
+    pub user_id: i64,
+    pub email: String,
+    pub role: SampleRole,
+    pub granted_by: i64,
+    pub created_at: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ShareInput {
+    pub email: String,
+    pub role: SampleRole,
+}
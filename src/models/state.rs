@@ -1,12 +1,14 @@
 use axum::extract::FromRef;
 use sqlx::SqlitePool;
+use std::sync::Arc;
 
-use crate::kafka::EventBus;
+use crate::{kafka::EventBus, mailer::Mailer};
 
 #[derive(Clone)]
 pub struct WebState {
     pub db: SqlitePool,
     pub events: EventBus,
+    pub mailer: Arc<dyn Mailer>,
 }
 
 impl FromRef<WebState> for SqlitePool {
@@ -0,0 +1,111 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+use crate::models::{blank_as_none, sample::SampleStatus};
+
+/// Width of one histogram bar. Buckets are named by the date they start on;
+/// weeks start on Monday.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Bucket {
+    #[default]
+    Day,
+    Week,
+    Month,
+}
+
+impl Bucket {
+    pub const ALL: [Bucket; 3] = [Bucket::Day, Bucket::Week, Bucket::Month];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Bucket::Day => "day",
+            Bucket::Week => "week",
+            Bucket::Month => "month",
+        }
+    }
+
+    /// How many buckets back the histograms reach when no `since` is given.
+    pub fn default_span(&self) -> u32 {
+        match self {
+            Bucket::Day => 30,
+            Bucket::Week => 12,
+            Bucket::Month => 12,
+        }
+    }
+}
+
+impl FromStr for Bucket {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Bucket::ALL
+            .into_iter()
+            .find(|b| b.as_str().eq_ignore_ascii_case(s.trim()))
+            .ok_or_else(|| format!("unknown bucket `{s}`, expected day, week or month"))
+    }
+}
+
+impl fmt::Display for Bucket {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct StatsQuery {
+    #[serde(
+        default,
+        deserialize_with = "blank_as_none",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub bucket: Option<Bucket>,
+    /// Start of the histograms, `YYYY-MM-DD`.
+    #[serde(
+        default,
+        deserialize_with = "blank_as_none",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub since: Option<String>,
+    /// Last day of the histograms, `YYYY-MM-DD`. Defaults to today.
+    #[serde(
+        default,
+        deserialize_with = "blank_as_none",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub until: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct StatusCount {
+    pub status: SampleStatus,
+    pub count: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct CreatorCount {
+    pub user_id: i64,
+    pub email: String,
+    pub count: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct BucketCount {
+    pub bucket: String,
+    pub count: i64,
+}
+
+/// Counts over the live samples the caller can see. The histograms cover
+/// `since..=until` with a zero for every empty bucket.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SampleStats {
+    pub total: i64,
+    pub by_status: Vec<StatusCount>,
+    pub by_creator: Vec<CreatorCount>,
+    pub bucket: Bucket,
+    pub since: String,
+    pub until: String,
+    pub created: Vec<BucketCount>,
+    pub updated: Vec<BucketCount>,
+}
@@ -1,4 +1,4 @@
-// use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Serialize};
 
 // #[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
 // pub struct User {
@@ -7,3 +7,40 @@
 //     pub password_hash: String,
 //     pub created_at: String,
 // }
+
+/// What a mailed token lets its holder do. Each is single use.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
+#[serde(rename_all = "snake_case")]
+#[sqlx(type_name = "TEXT", rename_all = "snake_case")]
+pub enum TokenPurpose {
+    VerifyEmail,
+    ResetPassword,
+    MagicLink,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct RegisterInput {
+    pub email: String,
+    pub password: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct VerifyEmailInput {
+    pub token: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ForgotPasswordInput {
+    pub email: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct MagicLinkInput {
+    pub email: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ResetPasswordInput {
+    pub token: String,
+    pub password: String,
+}
@@ -0,0 +1,51 @@
+use anyhow::{ensure, Result};
+use sqlx::SqlitePool;
+use std::time::Duration;
+
+use crate::services;
+
+/// Spawns the background task that empties the trash. Samples are purged once
+/// they've been deleted for `TRASH_RETENTION_DAYS`, checked every
+/// `TRASH_PURGE_INTERVAL_SECS`. Attachment blobs left without a row, expired
+/// idempotency keys and expired emailed tokens are swept on the same schedule.
+pub fn setup_purge(db: SqlitePool) -> Result<()> {
+    let retention_days: i64 = std::env::var("TRASH_RETENTION_DAYS")
+        .unwrap_or_else(|_| "30".into())
+        .parse()?;
+    let interval_secs: u64 = std::env::var("TRASH_PURGE_INTERVAL_SECS")
+        .unwrap_or_else(|_| "3600".into())
+        .parse()?;
+    ensure!(
+        interval_secs > 0,
+        "TRASH_PURGE_INTERVAL_SECS must be greater than 0"
+    );
+
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_secs(interval_secs));
+        loop {
+            interval.tick().await;
+            match services::sample::purge_trashed_samples(&db, retention_days).await {
+                Ok(0) => {}
+                Ok(purged) => tracing::info!(purged, "purged trashed samples"),
+                Err(e) => tracing::error!(?e, "failed to purge trashed samples"),
+            }
+            match services::attachment::sweep_orphaned_blobs(&db).await {
+                Ok(0) => {}
+                Ok(removed) => tracing::info!(removed, "removed orphaned attachment blobs"),
+                Err(e) => tracing::error!(?e, "failed to sweep attachment blobs"),
+            }
+            match services::idempotency::purge_expired(&db).await {
+                Ok(0) => {}
+                Ok(expired) => tracing::info!(expired, "dropped expired idempotency keys"),
+                Err(e) => tracing::error!(?e, "failed to drop expired idempotency keys"),
+            }
+            match services::account::purge_expired_tokens(&db).await {
+                Ok(0) => {}
+                Ok(expired) => tracing::info!(expired, "dropped expired user tokens"),
+                Err(e) => tracing::error!(?e, "failed to drop expired user tokens"),
+            }
+        }
+    });
+
+    Ok(())
+}
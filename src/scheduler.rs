@@ -0,0 +1,38 @@
+use anyhow::{ensure, Result};
+use std::time::Duration;
+
+use crate::models::state::WebState;
+use crate::services;
+
+/// Spawns the task that applies scheduled status changes, checking for due
+/// actions every `SCHEDULER_INTERVAL_SECS`. Actions a previous run left half
+/// applied are settled first.
+pub fn setup_scheduler(state: WebState) -> Result<()> {
+    let interval_secs: u64 = std::env::var("SCHEDULER_INTERVAL_SECS")
+        .unwrap_or_else(|_| "30".into())
+        .parse()?;
+    ensure!(
+        interval_secs > 0,
+        "SCHEDULER_INTERVAL_SECS must be greater than 0"
+    );
+
+    tokio::spawn(async move {
+        match services::schedule::recover_interrupted(&state.db).await {
+            Ok(0) => {}
+            Ok(recovered) => tracing::info!(recovered, "recovered interrupted scheduled actions"),
+            Err(e) => tracing::error!(?e, "failed to recover scheduled actions"),
+        }
+
+        let mut interval = tokio::time::interval(Duration::from_secs(interval_secs));
+        loop {
+            interval.tick().await;
+            match services::schedule::run_due_actions(&state).await {
+                Ok(0) => {}
+                Ok(applied) => tracing::info!(applied, "applied scheduled actions"),
+                Err(e) => tracing::error!(?e, "failed to run scheduled actions"),
+            }
+        }
+    });
+
+    Ok(())
+}
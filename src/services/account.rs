@@ -0,0 +1,727 @@
+use anyhow::{bail, Result};
+use argon2::{
+    password_hash::{rand_core::OsRng, PasswordHasher, SaltString},
+    Argon2,
+};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine as _};
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use sqlx::{SqliteConnection, SqlitePool};
+
+use crate::mailer::{app_url, Email};
+use crate::models::{
+    state::WebState,
+    user::{ForgotPasswordInput, MagicLinkInput, RegisterInput, ResetPasswordInput, TokenPurpose},
+};
+use crate::services::{audit, error::ServiceError, setting};
+
+pub const MIN_PASSWORD_LEN: usize = 8;
+pub const MAX_PASSWORD_LEN: usize = 128;
+pub const MAX_EMAIL_LEN: usize = 254;
+
+/// How long a verification link works, from `EMAIL_VERIFICATION_TTL_HOURS`.
+pub fn verification_ttl_hours() -> i64 {
+    std::env::var("EMAIL_VERIFICATION_TTL_HOURS")
+        .ok()
+        .and_then(|h| h.parse().ok())
+        .filter(|h| *h > 0)
+        .unwrap_or(24)
+}
+
+/// How long a password reset link works, from `PASSWORD_RESET_TTL_MINUTES`.
+pub fn reset_ttl_minutes() -> i64 {
+    std::env::var("PASSWORD_RESET_TTL_MINUTES")
+        .ok()
+        .and_then(|m| m.parse().ok())
+        .filter(|m| *m > 0)
+        .unwrap_or(60)
+}
+
+/// How long a magic login link works, from `MAGIC_LINK_TTL_MINUTES`.
+pub fn magic_link_ttl_minutes() -> i64 {
+    std::env::var("MAGIC_LINK_TTL_MINUTES")
+        .ok()
+        .and_then(|m| m.parse().ok())
+        .filter(|m| *m > 0)
+        .unwrap_or(15)
+}
+
+/// Trims the address and checks it looks like one. Delivery is the real test.
+fn clean_email(raw: &str) -> Result<String> {
+    let email = raw.trim().to_lowercase();
+    let valid = match email.split_once('@') {
+        Some((local, domain)) => {
+            !local.is_empty()
+                && domain.contains('.')
+                && !domain.starts_with('.')
+                && !domain.ends_with('.')
+                && !email.contains(char::is_whitespace)
+        }
+        None => false,
+    };
+    if !valid || email.len() > MAX_EMAIL_LEN {
+        bail!(ServiceError::Invalid(format!(
+            "{} is not a valid email address",
+            raw.trim()
+        )));
+    }
+    Ok(email)
+}
+
+fn check_password(password: &str) -> Result<()> {
+    let len = password.chars().count();
+    if !(MIN_PASSWORD_LEN..=MAX_PASSWORD_LEN).contains(&len) {
+        bail!(ServiceError::Invalid(format!(
+            "password must be {MIN_PASSWORD_LEN} to {MAX_PASSWORD_LEN} characters"
+        )));
+    }
+    Ok(())
+}
+
+pub fn hash_password(password: &str) -> Result<String> {
+    let salt = SaltString::generate(&mut OsRng);
+    let hash = Argon2::default()
+        .hash_password(password.as_bytes(), &salt)
+        .map_err(|e| anyhow::anyhow!("failed to hash password: {e}"))?;
+    Ok(hash.to_string())
+}
+
+pub(crate) fn token_hash(token: &str) -> String {
+    hex::encode(Sha256::digest(token.as_bytes()))
+}
+
+/// Creates a token for `purpose`, replacing any the user already had for it,
+/// and returns it. Only its hash is stored.
+pub(crate) async fn issue_token(
+    conn: &mut SqliteConnection,
+    user_id: i64,
+    purpose: TokenPurpose,
+    ttl: &str,
+) -> Result<String> {
+    let token = URL_SAFE_NO_PAD.encode(rand::random::<[u8; 32]>());
+    let hash = token_hash(&token);
+    sqlx::query!(
+        "DELETE FROM user_tokens WHERE user_id = ? AND purpose = ?",
+        user_id,
+        purpose
+    )
+    .execute(&mut *conn)
+    .await?;
+    sqlx::query!(
+        r#"
+            INSERT INTO user_tokens (token_hash, user_id, purpose, expires_at)
+            VALUES (?, ?, ?, datetime('now', ?))
+        "#,
+        hash,
+        user_id,
+        purpose,
+        ttl
+    )
+    .execute(&mut *conn)
+    .await?;
+    Ok(token)
+}
+
+/// Uses up a token, returning its user if it was valid and unexpired. The
+/// token is gone afterwards either way.
+pub(crate) async fn consume_token(
+    conn: &mut SqliteConnection,
+    token: &str,
+    purpose: TokenPurpose,
+) -> Result<Option<i64>> {
+    let hash = token_hash(token.trim());
+    let row = sqlx::query!(
+        r#"
+            DELETE FROM user_tokens WHERE token_hash = ? AND purpose = ?
+            RETURNING user_id AS "user_id!", expires_at > datetime('now') AS "live!: bool"
+        "#,
+        hash,
+        purpose
+    )
+    .fetch_optional(conn)
+    .await?;
+    Ok(row.filter(|r| r.live).map(|r| r.user_id))
+}
+
+/// Drops tokens past their expiry. Returns how many went.
+pub async fn purge_expired_tokens(db: &SqlitePool) -> Result<u64> {
+    let result = sqlx::query!("DELETE FROM user_tokens WHERE expires_at <= datetime('now')")
+        .execute(db)
+        .await?;
+    Ok(result.rows_affected())
+}
+
+fn verification_email(to: &str, token: &str) -> Email {
+    let link = app_url(&format!("/verify-email?token={token}"));
+    Email {
+        to: to.to_string(),
+        subject: "Confirm your email".to_string(),
+        body: format!(
+            "Welcome to Sample App.\n\nOpen this link to confirm your email and finish signing up:\n\n{link}\n\nThe link works once and expires in {} hours. If you didn't sign up, ignore this email.",
+            verification_ttl_hours()
+        ),
+    }
+}
+
+fn resent_verification_email(to: &str, token: &str) -> Email {
+    let link = app_url(&format!("/verify-email?token={token}"));
+    Email {
+        to: to.to_string(),
+        subject: "Confirm your email".to_string(),
+        body: format!(
+            "Someone signed up to Sample App again with this email, which has an account waiting to be confirmed.\n\nOpen this link to confirm it:\n\n{link}\n\nThe account keeps the password from the first sign-up. If you don't know it, choose a new one at {} after confirming. The link works once and expires in {} hours. If you didn't sign up, ignore this email.",
+            app_url("/forgot-password"),
+            verification_ttl_hours()
+        ),
+    }
+}
+
+fn already_registered_email(to: &str) -> Email {
+    Email {
+        to: to.to_string(),
+        subject: "You already have an account".to_string(),
+        body: format!(
+            "Someone tried to sign up to Sample App with this email, but it already has an account.\n\nIf that was you, log in at {}.\n\nIf it wasn't, you can ignore this email.",
+            app_url("/login")
+        ),
+    }
+}
+
+/// Signs up a new, unverified user and mails them a verification link. The
+/// answer is the same whether or not the email is taken: a verified owner
+/// is told by email instead, and an unverified sign-up is sent a fresh link.
+/// The password of an existing account is never changed here, or anyone could
+/// sign up first with someone else's email and keep the account once they
+/// confirm it. The mail goes out in the background, so how long the answer
+/// takes doesn't tell which mail was sent.
+pub async fn register(state: &WebState, input: RegisterInput) -> Result<()> {
+    let email = clean_email(&input.email)?;
+    check_password(&input.password)?;
+    let hash = hash_password(&input.password)?;
+    let ttl = format!("+{} hours", verification_ttl_hours());
+
+    let mut tx = state.db.begin().await?;
+    let existing = sqlx::query!(
+        r#"
+            SELECT id AS "id!", email_verified_at IS NOT NULL AS "verified!: bool"
+            FROM users WHERE email = ? COLLATE NOCASE
+        "#,
+        email
+    )
+    .fetch_optional(&mut *tx)
+    .await?;
+    let mail = match existing {
+        Some(user) if user.verified => already_registered_email(&email),
+        Some(user) => {
+            let token = issue_token(&mut tx, user.id, TokenPurpose::VerifyEmail, &ttl).await?;
+            resent_verification_email(&email, &token)
+        }
+        None => {
+            let user_id = sqlx::query_scalar!(
+                r#"INSERT INTO users (email, password_hash) VALUES (?, ?) RETURNING id AS "id!""#,
+                email,
+                hash
+            )
+            .fetch_one(&mut *tx)
+            .await?;
+            audit::record(
+                &mut tx,
+                Some(user_id),
+                "user.registered",
+                None,
+                None,
+                Some(json!({ "id": user_id, "email": email })),
+            )
+            .await?;
+            let token = issue_token(&mut tx, user_id, TokenPurpose::VerifyEmail, &ttl).await?;
+            verification_email(&email, &token)
+        }
+    };
+    tx.commit().await?;
+
+    let mailer = state.mailer.clone();
+    tokio::spawn(async move {
+        if let Err(e) = mailer.send(&mail).await {
+            tracing::error!(?e, "failed to send registration email");
+        }
+    });
+    Ok(())
+}
+
+/// Marks the token's user verified so they can log in.
+pub async fn verify_email(state: &WebState, token: &str) -> Result<()> {
+    let mut tx = state.db.begin().await?;
+    let Some(user_id) = consume_token(&mut tx, token, TokenPurpose::VerifyEmail).await? else {
+        bail!(ServiceError::Invalid(
+            "this verification link is invalid or has expired, sign up again to get a new one"
+                .into()
+        ));
+    };
+    sqlx::query!(
+        "UPDATE users SET email_verified_at = COALESCE(email_verified_at, datetime('now')) WHERE id = ?",
+        user_id
+    )
+    .execute(&mut *tx)
+    .await?;
+    audit::record(&mut tx, Some(user_id), "user.verified", None, None, None).await?;
+    tx.commit().await?;
+    Ok(())
+}
+
+fn reset_email(to: &str, token: &str) -> Email {
+    let link = app_url(&format!("/reset-password?token={token}"));
+    Email {
+        to: to.to_string(),
+        subject: "Reset your password".to_string(),
+        body: format!(
+            "Someone asked to reset the password for this Sample App account.\n\nOpen this link to choose a new one:\n\n{link}\n\nThe link works once and expires in {} minutes. If you didn't ask, ignore this email and your password stays the same.",
+            reset_ttl_minutes()
+        ),
+    }
+}
+
+/// Mails a reset link if `email` has an account, replacing any earlier link.
+/// Succeeds either way so the caller can't tell, and the mail is sent in the
+/// background so the response time doesn't tell either.
+pub async fn request_password_reset(state: &WebState, input: ForgotPasswordInput) -> Result<()> {
+    let email = input.email.trim();
+    if email.is_empty() {
+        bail!(ServiceError::Invalid("email is required".into()));
+    }
+    let ttl = format!("+{} minutes", reset_ttl_minutes());
+
+    let mut tx = state.db.begin().await?;
+    let user = sqlx::query!(
+        r#"SELECT id AS "id!", email AS "email!" FROM users WHERE email = ? COLLATE NOCASE"#,
+        email
+    )
+    .fetch_optional(&mut *tx)
+    .await?;
+    let Some(user) = user else {
+        return Ok(());
+    };
+    let token = issue_token(&mut tx, user.id, TokenPurpose::ResetPassword, &ttl).await?;
+    tx.commit().await?;
+
+    let mail = reset_email(&user.email, &token);
+    let mailer = state.mailer.clone();
+    tokio::spawn(async move {
+        if let Err(e) = mailer.send(&mail).await {
+            tracing::error!(?e, "failed to send password reset email");
+        }
+    });
+    Ok(())
+}
+
+/// Sets a new password with a reset token, logs the user out of every
+/// session and revokes their API tokens. Following the link proves the
+/// address, so it also confirms an unverified account.
+pub async fn reset_password(state: &WebState, input: ResetPasswordInput) -> Result<()> {
+    // checked first so a rejected password doesn't use up the link
+    check_password(&input.password)?;
+    let hash = hash_password(&input.password)?;
+
+    let mut tx = state.db.begin().await?;
+    let Some(user_id) = consume_token(&mut tx, &input.token, TokenPurpose::ResetPassword).await?
+    else {
+        bail!(ServiceError::Invalid(
+            "this reset link is invalid or has expired, ask for a new one".into()
+        ));
+    };
+    sqlx::query!(
+        r#"
+            UPDATE users
+            SET password_hash = ?,
+                session_epoch = session_epoch + 1,
+                email_verified_at = COALESCE(email_verified_at, datetime('now'))
+            WHERE id = ?
+        "#,
+        hash,
+        user_id
+    )
+    .execute(&mut *tx)
+    .await?;
+    // a reset after a break-in has to shut out the intruder's tokens too
+    sqlx::query!("DELETE FROM api_tokens WHERE user_id = ?", user_id)
+        .execute(&mut *tx)
+        .await?;
+    audit::record(
+        &mut tx,
+        Some(user_id),
+        "user.password_reset",
+        None,
+        None,
+        None,
+    )
+    .await?;
+    tx.commit().await?;
+    Ok(())
+}
+
+fn magic_link_email(to: &str, token: &str) -> Email {
+    let link = app_url(&format!("/login/magic/verify?token={token}"));
+    Email {
+        to: to.to_string(),
+        subject: "Your login link".to_string(),
+        body: format!(
+            "Someone asked to log in to Sample App as this account.\n\nOpen this link to log in:\n\n{link}\n\nThe link works once and expires in {} minutes. If you didn't ask, ignore this email.",
+            magic_link_ttl_minutes()
+        ),
+    }
+}
+
+async fn check_magic_links_enabled(state: &WebState) -> Result<()> {
+    if !setting::magic_links_enabled(state).await? {
+        bail!(ServiceError::Forbidden("login links are turned off"));
+    }
+    Ok(())
+}
+
+/// Mails a login link if `email` has a confirmed account, replacing any
+/// earlier link. Like a reset request it succeeds either way and sends in the
+/// background, so the caller can't tell whether the account exists.
+pub async fn request_magic_link(state: &WebState, input: MagicLinkInput) -> Result<()> {
+    check_magic_links_enabled(state).await?;
+    let email = input.email.trim();
+    if email.is_empty() {
+        bail!(ServiceError::Invalid("email is required".into()));
+    }
+    let ttl = format!("+{} minutes", magic_link_ttl_minutes());
+
+    let mut tx = state.db.begin().await?;
+    let user = sqlx::query!(
+        r#"
+            SELECT id AS "id!", email AS "email!" FROM users
+            WHERE email = ? COLLATE NOCASE AND email_verified_at IS NOT NULL
+        "#,
+        email
+    )
+    .fetch_optional(&mut *tx)
+    .await?;
+    let Some(user) = user else {
+        return Ok(());
+    };
+    let token = issue_token(&mut tx, user.id, TokenPurpose::MagicLink, &ttl).await?;
+    tx.commit().await?;
+
+    let mail = magic_link_email(&user.email, &token);
+    let mailer = state.mailer.clone();
+    tokio::spawn(async move {
+        if let Err(e) = mailer.send(&mail).await {
+            tracing::error!(?e, "failed to send login link email");
+        }
+    });
+    Ok(())
+}
+
+/// Uses up a login link and returns the user and their `session_epoch`, for
+/// starting a session.
+pub async fn consume_magic_link(state: &WebState, token: &str) -> Result<(i64, i64)> {
+    check_magic_links_enabled(state).await?;
+    let mut tx = state.db.begin().await?;
+    let Some(user_id) = consume_token(&mut tx, token, TokenPurpose::MagicLink).await? else {
+        bail!(ServiceError::Invalid(
+            "this login link is invalid or has expired, ask for a new one".into()
+        ));
+    };
+    let epoch = sqlx::query_scalar!("SELECT session_epoch FROM users WHERE id = ?", user_id)
+        .fetch_one(&mut *tx)
+        .await?;
+    audit::record(
+        &mut tx,
+        Some(user_id),
+        "user.magic_link_login",
+        None,
+        None,
+        None,
+    )
+    .await?;
+    tx.commit().await?;
+    Ok((user_id, epoch))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::setting::AppSettingsUpdate;
+    use crate::services::error::status_code;
+    use crate::testing;
+    use axum::http::StatusCode;
+
+    fn signup(email: &str, password: &str) -> RegisterInput {
+        RegisterInput {
+            email: email.to_string(),
+            password: password.to_string(),
+        }
+    }
+
+    /// The token from the link in a verification email.
+    fn link_token(email: &Email) -> String {
+        let (_, rest) = email.body.split_once("token=").expect("a link");
+        rest.split_whitespace().next().unwrap().to_string()
+    }
+
+    async fn verified(db: &SqlitePool, email: &str) -> Option<bool> {
+        sqlx::query_scalar!(
+            r#"SELECT email_verified_at IS NOT NULL AS "verified!: bool" FROM users WHERE email = ?"#,
+            email
+        )
+        .fetch_optional(db)
+        .await
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn a_verification_link_works_once() {
+        let (state, outbox) = testing::state_with_outbox().await;
+        register(&state, signup(" New@Example.com ", "long enough"))
+            .await
+            .unwrap();
+        assert_eq!(verified(&state.db, "new@example.com").await, Some(false));
+
+        let sent = outbox.wait_for(1).await;
+        assert_eq!(sent.len(), 1);
+        assert_eq!(sent[0].to, "new@example.com");
+        let token = link_token(&sent[0]);
+        verify_email(&state, &token).await.unwrap();
+        assert_eq!(verified(&state.db, "new@example.com").await, Some(true));
+
+        let err = verify_email(&state, &token).await.unwrap_err();
+        assert_eq!(status_code(&err), StatusCode::UNPROCESSABLE_ENTITY);
+    }
+
+    #[tokio::test]
+    async fn a_taken_email_is_told_by_mail_only() {
+        let (state, outbox) = testing::state_with_outbox().await;
+        testing::user(&state.db, "ann@example.com").await;
+
+        register(&state, signup("ANN@example.com", "long enough"))
+            .await
+            .unwrap();
+        let sent = outbox.wait_for(1).await;
+        assert_eq!(sent[0].subject, "You already have an account");
+        assert!(!sent[0].body.contains("token="));
+        let users = sqlx::query_scalar!(r#"SELECT COUNT(1) AS "n!: i64" FROM users"#)
+            .fetch_one(&state.db)
+            .await
+            .unwrap();
+        assert_eq!(users, 1);
+    }
+
+    #[tokio::test]
+    async fn signing_up_again_replaces_the_link_but_not_the_password() {
+        let (state, outbox) = testing::state_with_outbox().await;
+        let password_hash = || {
+            sqlx::query_scalar!("SELECT password_hash FROM users WHERE email = 'new@example.com'")
+                .fetch_one(&state.db)
+        };
+        register(&state, signup("new@example.com", "long enough"))
+            .await
+            .unwrap();
+        let first = password_hash().await.unwrap();
+        outbox.wait_for(1).await;
+        register(&state, signup("new@example.com", "someone else's"))
+            .await
+            .unwrap();
+        assert_eq!(password_hash().await.unwrap(), first);
+        let sent = outbox.wait_for(2).await;
+        assert!(sent[1].body.contains("keeps the password"));
+        let (old, new) = (link_token(&sent[0]), link_token(&sent[1]));
+
+        assert!(verify_email(&state, &old).await.is_err());
+        assert_eq!(verified(&state.db, "new@example.com").await, Some(false));
+        verify_email(&state, &new).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn expired_links_fail_and_are_purged() {
+        let (state, outbox) = testing::state_with_outbox().await;
+        for (i, email) in ["ann@example.com", "bob@example.com"]
+            .into_iter()
+            .enumerate()
+        {
+            register(&state, signup(email, "long enough"))
+                .await
+                .unwrap();
+            outbox.wait_for(i + 1).await;
+        }
+        sqlx::query!("UPDATE user_tokens SET expires_at = datetime('now', '-1 minutes')")
+            .execute(&state.db)
+            .await
+            .unwrap();
+
+        let token = link_token(&outbox.sent()[0]);
+        let err = verify_email(&state, &token).await.unwrap_err();
+        assert_eq!(status_code(&err), StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(verified(&state.db, "ann@example.com").await, Some(false));
+        assert_eq!(purge_expired_tokens(&state.db).await.unwrap(), 2);
+        assert_eq!(purge_expired_tokens(&state.db).await.unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn bad_addresses_and_passwords_send_nothing() {
+        let (state, outbox) = testing::state_with_outbox().await;
+        let long = "x".repeat(MAX_PASSWORD_LEN + 1);
+        for input in [
+            signup("not-an-email", "long enough"),
+            signup("a@b", "long enough"),
+            signup("a b@example.com", "long enough"),
+            signup("new@example.com", "short"),
+            signup("new@example.com", &long),
+        ] {
+            let err = register(&state, input).await.unwrap_err();
+            assert_eq!(status_code(&err), StatusCode::UNPROCESSABLE_ENTITY);
+        }
+        assert!(outbox.sent().is_empty());
+        assert_eq!(verified(&state.db, "new@example.com").await, None);
+    }
+
+    #[tokio::test]
+    async fn tokens_work_once() {
+        let state = testing::state().await;
+        let uid = testing::user(&state.db, "user@example.com").await;
+        let mut conn = state.db.acquire().await.unwrap();
+        let token = issue_token(&mut conn, uid, TokenPurpose::ResetPassword, "+60 minutes")
+            .await
+            .unwrap();
+
+        let first = consume_token(&mut conn, &token, TokenPurpose::ResetPassword).await;
+        assert_eq!(first.unwrap(), Some(uid));
+        let second = consume_token(&mut conn, &token, TokenPurpose::ResetPassword).await;
+        assert_eq!(second.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn expired_tokens_are_rejected_and_removed() {
+        let state = testing::state().await;
+        let uid = testing::user(&state.db, "user@example.com").await;
+        let mut conn = state.db.acquire().await.unwrap();
+        let token = issue_token(&mut conn, uid, TokenPurpose::ResetPassword, "-1 minutes")
+            .await
+            .unwrap();
+
+        let used = consume_token(&mut conn, &token, TokenPurpose::ResetPassword).await;
+        assert_eq!(used.unwrap(), None);
+        let left = sqlx::query_scalar!(r#"SELECT COUNT(1) AS "count!: i64" FROM user_tokens"#)
+            .fetch_one(&mut *conn)
+            .await
+            .unwrap();
+        assert_eq!(left, 0);
+    }
+
+    #[tokio::test]
+    async fn tokens_only_work_for_their_purpose_and_latest_issue() {
+        let state = testing::state().await;
+        let uid = testing::user(&state.db, "user@example.com").await;
+        let mut conn = state.db.acquire().await.unwrap();
+        let old = issue_token(&mut conn, uid, TokenPurpose::ResetPassword, "+60 minutes")
+            .await
+            .unwrap();
+        let new = issue_token(&mut conn, uid, TokenPurpose::ResetPassword, "+60 minutes")
+            .await
+            .unwrap();
+
+        let replaced = consume_token(&mut conn, &old, TokenPurpose::ResetPassword).await;
+        assert_eq!(replaced.unwrap(), None);
+        let wrong_purpose = consume_token(&mut conn, &new, TokenPurpose::VerifyEmail).await;
+        assert_eq!(wrong_purpose.unwrap(), None);
+        let used = consume_token(&mut conn, &new, TokenPurpose::ResetPassword).await;
+        assert_eq!(used.unwrap(), Some(uid));
+    }
+
+    #[tokio::test]
+    async fn a_reset_link_is_single_use_and_ends_sessions() {
+        let (state, outbox) = testing::state_with_outbox().await;
+        let uid = testing::user(&state.db, "user@example.com").await;
+        let forgot = |email: &str| ForgotPasswordInput {
+            email: email.to_string(),
+        };
+        request_password_reset(&state, forgot("nobody@example.com"))
+            .await
+            .unwrap();
+        request_password_reset(&state, forgot("USER@example.com"))
+            .await
+            .unwrap();
+        let sent = outbox.wait_for(1).await;
+        assert_eq!(sent.len(), 1);
+        assert_eq!(sent[0].to, "user@example.com");
+        let token = link_token(&sent[0]);
+        let reset = |password: &str| ResetPasswordInput {
+            token: token.clone(),
+            password: password.to_string(),
+        };
+
+        // a rejected password leaves the link usable
+        assert!(reset_password(&state, reset("short")).await.is_err());
+        reset_password(&state, reset("new password")).await.unwrap();
+        let err = reset_password(&state, reset("other password"))
+            .await
+            .unwrap_err();
+        assert_eq!(status_code(&err), StatusCode::UNPROCESSABLE_ENTITY);
+
+        let epoch = sqlx::query_scalar!("SELECT session_epoch FROM users WHERE id = ?", uid)
+            .fetch_one(&state.db)
+            .await
+            .unwrap();
+        assert_eq!(epoch, 1);
+    }
+
+    async fn allow_magic_links(state: &WebState, enabled: bool) {
+        let update = AppSettingsUpdate {
+            magic_links_enabled: Some(enabled),
+        };
+        setting::update_settings(state, update, 1).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn magic_links_log_in_once_when_turned_on() {
+        let (state, outbox) = testing::state_with_outbox().await;
+        let uid = testing::user(&state.db, "ann@example.com").await;
+        let ask = |email: &str| MagicLinkInput {
+            email: email.to_string(),
+        };
+        let err = request_magic_link(&state, ask("ann@example.com"))
+            .await
+            .unwrap_err();
+        assert_eq!(status_code(&err), StatusCode::FORBIDDEN);
+
+        allow_magic_links(&state, true).await;
+        register(&state, signup("new@example.com", "long enough"))
+            .await
+            .unwrap();
+        // unconfirmed accounts get no link
+        request_magic_link(&state, ask("new@example.com"))
+            .await
+            .unwrap();
+        request_magic_link(&state, ask("Ann@example.com"))
+            .await
+            .unwrap();
+        let sent = outbox.wait_for(2).await;
+        assert_eq!(sent.len(), 2);
+        assert_eq!(sent[1].to, "ann@example.com");
+        let token = link_token(&sent[1]);
+
+        assert_eq!(consume_magic_link(&state, &token).await.unwrap(), (uid, 0));
+        let err = consume_magic_link(&state, &token).await.unwrap_err();
+        assert_eq!(status_code(&err), StatusCode::UNPROCESSABLE_ENTITY);
+    }
+
+    #[tokio::test]
+    async fn turning_magic_links_off_voids_mailed_ones() {
+        let (state, outbox) = testing::state_with_outbox().await;
+        testing::user(&state.db, "ann@example.com").await;
+        allow_magic_links(&state, true).await;
+        let ask = MagicLinkInput {
+            email: "ann@example.com".into(),
+        };
+        request_magic_link(&state, ask).await.unwrap();
+        let token = link_token(&outbox.wait_for(1).await[0]);
+
+        allow_magic_links(&state, false).await;
+        let err = consume_magic_link(&state, &token).await.unwrap_err();
+        assert_eq!(status_code(&err), StatusCode::FORBIDDEN);
+        allow_magic_links(&state, true).await;
+        let err = consume_magic_link(&state, &token).await.unwrap_err();
+        assert_eq!(status_code(&err), StatusCode::UNPROCESSABLE_ENTITY);
+    }
+}
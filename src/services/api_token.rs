@@ -0,0 +1,286 @@
+use anyhow::{bail, Result};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine as _};
+use serde_json::json;
+use sqlx::{SqliteConnection, SqlitePool};
+
+use crate::models::{
+    api_token::{ApiToken, ApiTokenInput, NewApiToken, TokenScope},
+    state::WebState,
+};
+use crate::services::{account::token_hash, audit, error::ServiceError};
+
+pub const MAX_NAME_LEN: usize = 100;
+pub const MAX_EXPIRY_DAYS: i64 = 365;
+pub const MAX_TOKENS_PER_USER: i64 = 50;
+/// Marks the string as one of ours, for secret scanners and for people.
+pub const TOKEN_PREFIX: &str = "sat_";
+/// How much of the token the list shows.
+const SHOWN_LEN: usize = TOKEN_PREFIX.len() + 6;
+
+pub async fn get_tokens(state: &WebState, user_id: i64) -> Result<Vec<ApiToken>> {
+    let tokens = sqlx::query_as!(
+        ApiToken,
+        r#"
+            SELECT
+                id           AS "id!",
+                user_id      AS "user_id!",
+                name         AS "name!",
+                prefix       AS "prefix!",
+                scope        AS "scope!: TokenScope",
+                expires_at   AS "expires_at?",
+                last_used_at AS "last_used_at?",
+                created_at   AS "created_at!",
+                COALESCE(expires_at <= datetime('now'), 0) AS "expired!: bool"
+            FROM api_tokens
+            WHERE user_id = ?
+            ORDER BY id DESC
+        "#,
+        user_id
+    )
+    .fetch_all(&state.db)
+    .await?;
+    Ok(tokens)
+}
+
+async fn fetch_token(conn: &mut SqliteConnection, id: i64, user_id: i64) -> Result<ApiToken> {
+    let token = sqlx::query_as!(
+        ApiToken,
+        r#"
+            SELECT
+                id           AS "id!",
+                user_id      AS "user_id!",
+                name         AS "name!",
+                prefix       AS "prefix!",
+                scope        AS "scope!: TokenScope",
+                expires_at   AS "expires_at?",
+                last_used_at AS "last_used_at?",
+                created_at   AS "created_at!",
+                COALESCE(expires_at <= datetime('now'), 0) AS "expired!: bool"
+            FROM api_tokens
+            WHERE id = ? AND user_id = ?
+        "#,
+        id,
+        user_id
+    )
+    .fetch_optional(conn)
+    .await?;
+    match token {
+        Some(token) => Ok(token),
+        None => bail!(ServiceError::NotFound("token", id)),
+    }
+}
+
+/// What the audit log keeps of a token. Never the secret or its hash.
+fn snapshot(token: &ApiToken) -> serde_json::Value {
+    json!({
+        "id": token.id,
+        "name": token.name,
+        "prefix": token.prefix,
+        "scope": token.scope,
+        "expires_at": token.expires_at,
+    })
+}
+
+/// Creates a token for the user and returns it with its secret, which is not
+/// stored and can't be shown again.
+pub async fn create_token(
+    state: &WebState,
+    input: ApiTokenInput,
+    user_id: i64,
+) -> Result<NewApiToken> {
+    let name = input.name.trim();
+    if name.is_empty() || name.chars().count() > MAX_NAME_LEN {
+        bail!(ServiceError::Invalid(format!(
+            "name must be 1 to {MAX_NAME_LEN} characters"
+        )));
+    }
+    let expiry = match input.expires_in_days {
+        Some(days) if !(1..=MAX_EXPIRY_DAYS).contains(&days) => {
+            bail!(ServiceError::Invalid(format!(
+                "expiry must be 1 to {MAX_EXPIRY_DAYS} days, or none"
+            )));
+        }
+        Some(days) => Some(format!("+{days} days")),
+        None => None,
+    };
+
+    let mut tx = state.db.begin().await?;
+    let count = sqlx::query_scalar!(
+        r#"SELECT COUNT(1) AS "count!: i64" FROM api_tokens WHERE user_id = ?"#,
+        user_id
+    )
+    .fetch_one(&mut *tx)
+    .await?;
+    if count >= MAX_TOKENS_PER_USER {
+        bail!(ServiceError::Invalid(format!(
+            "you already have {MAX_TOKENS_PER_USER} tokens, revoke one first"
+        )));
+    }
+
+    let secret = format!(
+        "{TOKEN_PREFIX}{}",
+        URL_SAFE_NO_PAD.encode(rand::random::<[u8; 32]>())
+    );
+    let hash = token_hash(&secret);
+    let prefix = &secret[..SHOWN_LEN];
+    let id = sqlx::query_scalar!(
+        r#"
+            INSERT INTO api_tokens (user_id, name, token_hash, prefix, scope, expires_at)
+            VALUES (?, ?, ?, ?, ?, CASE WHEN ? IS NULL THEN NULL ELSE datetime('now', ?) END)
+            RETURNING id AS "id!"
+        "#,
+        user_id,
+        name,
+        hash,
+        prefix,
+        input.scope,
+        expiry,
+        expiry
+    )
+    .fetch_one(&mut *tx)
+    .await?;
+    let token = fetch_token(&mut tx, id, user_id).await?;
+    audit::record(
+        &mut tx,
+        Some(user_id),
+        "api_token.created",
+        None,
+        None,
+        Some(snapshot(&token)),
+    )
+    .await?;
+    tx.commit().await?;
+    Ok(NewApiToken { token, secret })
+}
+
+/// Revokes one of the user's tokens. Another user's reads as not found.
+pub async fn revoke_token(state: &WebState, id: i64, user_id: i64) -> Result<()> {
+    let mut tx = state.db.begin().await?;
+    let token = fetch_token(&mut tx, id, user_id).await?;
+    sqlx::query!("DELETE FROM api_tokens WHERE id = ?", id)
+        .execute(&mut *tx)
+        .await?;
+    audit::record(
+        &mut tx,
+        Some(user_id),
+        "api_token.revoked",
+        None,
+        Some(snapshot(&token)),
+        None,
+    )
+    .await?;
+    tx.commit().await?;
+    Ok(())
+}
+
+/// Looks up a live token from an `Authorization: Bearer` header and marks it
+/// used. Returns its user and scope, or `None` for an unknown, revoked or
+/// expired token.
+pub async fn authenticate(db: &SqlitePool, secret: &str) -> Result<Option<(i64, TokenScope)>> {
+    let hash = token_hash(secret.trim());
+    let row = sqlx::query!(
+        r#"
+            UPDATE api_tokens SET last_used_at = datetime('now')
+            WHERE token_hash = ? AND (expires_at IS NULL OR expires_at > datetime('now'))
+            RETURNING user_id AS "user_id!", scope AS "scope!: TokenScope"
+        "#,
+        hash
+    )
+    .fetch_optional(db)
+    .await?;
+    Ok(row.map(|r| (r.user_id, r.scope)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::error::status_code;
+    use crate::testing;
+    use http::StatusCode;
+
+    fn input(name: &str, expires_in_days: Option<i64>) -> ApiTokenInput {
+        ApiTokenInput {
+            name: name.into(),
+            scope: TokenScope::Read,
+            expires_in_days,
+        }
+    }
+
+    #[tokio::test]
+    async fn a_secret_is_only_good_until_revoked() {
+        let state = testing::state().await;
+        let uid = testing::user(&state.db, "ann@example.com").await;
+        let created = create_token(&state, input(" ci ", Some(30)), uid)
+            .await
+            .unwrap();
+        assert!(created.secret.starts_with(TOKEN_PREFIX));
+        assert_eq!(created.token.name, "ci");
+        assert!(created.secret.starts_with(&created.token.prefix));
+        assert!(created.token.expires_at.is_some());
+
+        let found = authenticate(&state.db, &created.secret).await.unwrap();
+        assert_eq!(found, Some((uid, TokenScope::Read)));
+        let listed = get_tokens(&state, uid).await.unwrap();
+        assert!(listed[0].last_used_at.is_some());
+
+        revoke_token(&state, created.token.id, uid).await.unwrap();
+        assert_eq!(
+            authenticate(&state.db, &created.secret).await.unwrap(),
+            None
+        );
+        assert!(get_tokens(&state, uid).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn expired_tokens_are_refused() {
+        let state = testing::state().await;
+        let uid = testing::user(&state.db, "ann@example.com").await;
+        let created = create_token(&state, input("ci", Some(1)), uid)
+            .await
+            .unwrap();
+        sqlx::query!(
+            "UPDATE api_tokens SET expires_at = datetime('now', '-1 minutes') WHERE id = ?",
+            created.token.id
+        )
+        .execute(&state.db)
+        .await
+        .unwrap();
+        assert_eq!(
+            authenticate(&state.db, &created.secret).await.unwrap(),
+            None
+        );
+        assert!(get_tokens(&state, uid).await.unwrap()[0].expired);
+    }
+
+    #[tokio::test]
+    async fn names_and_expiry_are_checked() {
+        let state = testing::state().await;
+        let uid = testing::user(&state.db, "ann@example.com").await;
+        for bad in [
+            input("  ", None),
+            input("ci", Some(0)),
+            input("ci", Some(366)),
+        ] {
+            let err = create_token(&state, bad, uid).await.unwrap_err();
+            assert_eq!(status_code(&err), StatusCode::UNPROCESSABLE_ENTITY);
+        }
+    }
+
+    #[tokio::test]
+    async fn other_users_tokens_are_out_of_reach() {
+        let state = testing::state().await;
+        let ann = testing::user(&state.db, "ann@example.com").await;
+        let bob = testing::user(&state.db, "bob@example.com").await;
+        let created = create_token(&state, input("ci", None), ann).await.unwrap();
+
+        assert!(get_tokens(&state, bob).await.unwrap().is_empty());
+        let err = revoke_token(&state, created.token.id, bob)
+            .await
+            .unwrap_err();
+        assert_eq!(status_code(&err), StatusCode::NOT_FOUND);
+        assert!(authenticate(&state.db, &created.secret)
+            .await
+            .unwrap()
+            .is_some());
+    }
+}
@@ -0,0 +1,478 @@
+use anyhow::{bail, Result};
+use axum::extract::Multipart;
+use futures_util::{Stream, StreamExt};
+use sha2::{Digest, Sha256};
+use sqlx::{SqliteConnection, SqlitePool};
+use std::{
+    collections::HashSet,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+use tokio::{fs, io::AsyncWriteExt};
+
+use crate::models::{attachment::Attachment, share::SampleRole, state::WebState};
+use crate::services::{
+    audit::{self, snapshot},
+    error::ServiceError,
+    sample::authorize,
+};
+
+pub const MAX_ATTACHMENT_BYTES: usize = 25 * 1024 * 1024;
+const MAX_FILE_NAME_LEN: usize = 255;
+
+/// Content types we keep. Anything that could run in the browser (HTML, SVG,
+/// scripts) is left out since downloads are served from our own origin.
+const ALLOWED_TYPES: &[&str] = &[
+    "image/png",
+    "image/jpeg",
+    "image/gif",
+    "image/webp",
+    "image/tiff",
+    "application/pdf",
+    "text/plain",
+    "text/csv",
+    "application/json",
+    "application/x-ndjson",
+    "application/zip",
+    "application/gzip",
+    "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet",
+    "application/octet-stream",
+];
+
+/// Blobs nobody references are only swept once they're this old, so an upload
+/// that has written its blob but not yet its row is left alone.
+const ORPHAN_GRACE: Duration = Duration::from_secs(3600);
+
+/// Where blobs are stored, `ATTACHMENTS_DIR` or `data/attachments`.
+pub fn attachments_dir() -> PathBuf {
+    std::env::var("ATTACHMENTS_DIR")
+        .unwrap_or_else(|_| "data/attachments".into())
+        .into()
+}
+
+fn blob_path(dir: &Path, sha256: &str) -> PathBuf {
+    dir.join(&sha256[..2]).join(sha256)
+}
+
+pub fn attachment_path(attachment: &Attachment) -> PathBuf {
+    blob_path(&attachments_dir(), &attachment.sha256)
+}
+
+/// Strips any directory part a browser may send and checks what's left.
+fn clean_file_name(raw: &str) -> Result<String> {
+    let name = raw.rsplit(['/', '\\']).next().unwrap_or_default().trim();
+    if name.is_empty() {
+        bail!(ServiceError::Invalid("file name is required".into()));
+    }
+    Ok(name.chars().take(MAX_FILE_NAME_LEN).collect())
+}
+
+/// The type is taken from the file extension, then the type the client sent,
+/// and must be on the allow list.
+fn content_type_for(file_name: &str, declared: Option<&str>) -> Result<String> {
+    let content_type = mime_guess::from_path(file_name)
+        .first_raw()
+        .map(str::to_string)
+        .or_else(|| declared.map(|t| t.split(';').next().unwrap_or(t).trim().to_lowercase()))
+        .unwrap_or_else(|| "application/octet-stream".into());
+    if !ALLOWED_TYPES.contains(&content_type.as_str()) {
+        bail!(ServiceError::UnsupportedType(content_type));
+    }
+    Ok(content_type)
+}
+
+pub async fn get_attachments(state: &WebState, sample_id: i64) -> Result<Vec<Attachment>> {
+    let attachments = sqlx::query_as!(
+        Attachment,
+        r#"
+            SELECT
+                id           AS "id!",
+                sample_id    AS "sample_id!",
+                file_name    AS "file_name!",
+                content_type AS "content_type!",
+                size         AS "size!",
+                sha256       AS "sha256!",
+                uploaded_by  AS "uploaded_by!",
+                created_at   AS "created_at!"
+            FROM sample_attachments
+            WHERE sample_id = ?
+            ORDER BY id
+        "#,
+        sample_id
+    )
+    .fetch_all(&state.db)
+    .await?;
+    Ok(attachments)
+}
+
+pub async fn get_attachment(state: &WebState, sample_id: i64, id: i64) -> Result<Attachment> {
+    let mut conn = state.db.acquire().await?;
+    fetch_attachment(&mut conn, sample_id, id).await
+}
+
+async fn fetch_attachment(
+    conn: &mut SqliteConnection,
+    sample_id: i64,
+    id: i64,
+) -> Result<Attachment> {
+    let attachment = sqlx::query_as!(
+        Attachment,
+        r#"
+            SELECT
+                a.id           AS "id!",
+                a.sample_id    AS "sample_id!",
+                a.file_name    AS "file_name!",
+                a.content_type AS "content_type!",
+                a.size         AS "size!",
+                a.sha256       AS "sha256!",
+                a.uploaded_by  AS "uploaded_by!",
+                a.created_at   AS "created_at!"
+            FROM sample_attachments a
+            JOIN samples s ON s.id = a.sample_id
+            WHERE a.id = ? AND a.sample_id = ? AND s.deleted_at IS NULL
+        "#,
+        id,
+        sample_id
+    )
+    .fetch_optional(conn)
+    .await?;
+    attachment.ok_or_else(|| ServiceError::NotFound("attachment", id).into())
+}
+
+/// Streams an upload to disk while hashing it, then moves it to its
+/// content-addressed path. The size limit is enforced as bytes arrive.
+async fn write_blob<S, B, E>(dir: &Path, mut body: S) -> Result<(String, i64)>
+where
+    S: Stream<Item = Result<B, E>> + Unpin,
+    B: AsRef<[u8]>,
+    E: std::error::Error + Send + Sync + 'static,
+{
+    let tmp_dir = dir.join("tmp");
+    fs::create_dir_all(&tmp_dir).await?;
+    let tmp_path = tmp_dir.join(uuid::Uuid::new_v4().to_string());
+
+    let written: Result<(String, i64)> = async {
+        let mut file = fs::File::create(&tmp_path).await?;
+        let mut hasher = Sha256::new();
+        let mut size = 0usize;
+        while let Some(chunk) = body.next().await {
+            let chunk = chunk?;
+            let chunk = chunk.as_ref();
+            size += chunk.len();
+            if size > MAX_ATTACHMENT_BYTES {
+                bail!(ServiceError::TooLarge {
+                    limit: MAX_ATTACHMENT_BYTES
+                });
+            }
+            hasher.update(chunk);
+            file.write_all(chunk).await?;
+        }
+        file.sync_all().await?;
+        if size == 0 {
+            bail!(ServiceError::Invalid("file is empty".into()));
+        }
+        Ok((hex::encode(hasher.finalize()), size as i64))
+    }
+    .await;
+
+    let (sha256, size) = match written {
+        Ok(blob) => blob,
+        Err(e) => {
+            fs::remove_file(&tmp_path).await.ok();
+            return Err(e);
+        }
+    };
+
+    let path = blob_path(dir, &sha256);
+    if fs::try_exists(&path).await? {
+        fs::remove_file(&tmp_path).await?;
+        // the sweep spares blobs modified within ORPHAN_GRACE, so one that is
+        // about to be referenced again must look fresh
+        let blob = fs::OpenOptions::new().write(true).open(&path).await?;
+        blob.into_std().await.set_modified(SystemTime::now())?;
+    } else {
+        fs::create_dir_all(path.parent().unwrap()).await?;
+        fs::rename(&tmp_path, &path).await?;
+    }
+    Ok((sha256, size))
+}
+
+pub async fn add_attachment<S, B, E>(
+    state: &WebState,
+    sample_id: i64,
+    file_name: &str,
+    declared_type: Option<&str>,
+    body: S,
+    uid: i64,
+) -> Result<Attachment>
+where
+    S: Stream<Item = Result<B, E>> + Unpin,
+    B: AsRef<[u8]>,
+    E: std::error::Error + Send + Sync + 'static,
+{
+    authorize(state, sample_id, uid, SampleRole::Editor).await?;
+    let file_name = clean_file_name(file_name)?;
+    let content_type = content_type_for(&file_name, declared_type)?;
+
+    let (sha256, size) = write_blob(&attachments_dir(), body).await?;
+
+    let mut tx = state.db.begin().await?;
+    let id = sqlx::query_scalar!(
+        r#"
+            INSERT INTO sample_attachments
+                (sample_id, file_name, content_type, size, sha256, uploaded_by)
+            VALUES (?, ?, ?, ?, ?, ?)
+            RETURNING id AS "id!"
+        "#,
+        sample_id,
+        file_name,
+        content_type,
+        size,
+        sha256,
+        uid
+    )
+    .fetch_one(&mut *tx)
+    .await?;
+    let attachment = fetch_attachment(&mut tx, sample_id, id).await?;
+    audit::record(
+        &mut tx,
+        Some(uid),
+        "attachment.added",
+        Some(sample_id),
+        None,
+        snapshot(&attachment),
+    )
+    .await?;
+    tx.commit().await?;
+
+    state.events.attachment_added(attachment.clone()).await.ok();
+    Ok(attachment)
+}
+
+/// Saves every `file` part of a multipart upload. Parts are streamed to disk
+/// one at a time, and a body cut off by the request size limit reports as
+/// too large rather than as a server error.
+pub async fn add_uploads(
+    state: &WebState,
+    sample_id: i64,
+    mut multipart: Multipart,
+    uid: i64,
+) -> Result<Vec<Attachment>> {
+    let mut added = Vec::new();
+    while let Some(field) = multipart.next_field().await.map_err(multipart_error)? {
+        if field.name() != Some("file") {
+            continue;
+        }
+        let file_name = field.file_name().unwrap_or_default().to_string();
+        let declared_type = field.content_type().map(str::to_string);
+        let body = field.map(|chunk| chunk.map_err(multipart_error));
+        let attachment = add_attachment(
+            state,
+            sample_id,
+            &file_name,
+            declared_type.as_deref(),
+            body,
+            uid,
+        )
+        .await?;
+        added.push(attachment);
+    }
+    if added.is_empty() {
+        bail!(ServiceError::Invalid("no file was uploaded".into()));
+    }
+    Ok(added)
+}
+
+fn multipart_error(e: axum::extract::multipart::MultipartError) -> ServiceError {
+    if e.status() == http::StatusCode::PAYLOAD_TOO_LARGE {
+        ServiceError::TooLarge {
+            limit: MAX_ATTACHMENT_BYTES,
+        }
+    } else {
+        ServiceError::Invalid(e.body_text())
+    }
+}
+
+/// Drops the attachment's row. The blob may be shared with other attachments,
+/// so it is left for `sweep_orphaned_blobs`.
+pub async fn remove_attachment(state: &WebState, sample_id: i64, id: i64, uid: i64) -> Result<()> {
+    authorize(state, sample_id, uid, SampleRole::Editor).await?;
+    let mut tx = state.db.begin().await?;
+    let attachment = fetch_attachment(&mut tx, sample_id, id).await?;
+    sqlx::query!(
+        "DELETE FROM sample_attachments WHERE id = ? AND sample_id = ?",
+        id,
+        sample_id
+    )
+    .execute(&mut *tx)
+    .await?;
+    audit::record(
+        &mut tx,
+        Some(uid),
+        "attachment.removed",
+        Some(sample_id),
+        snapshot(&attachment),
+        None,
+    )
+    .await?;
+    tx.commit().await?;
+    state.events.attachment_removed(sample_id, id).await.ok();
+    Ok(())
+}
+
+fn older_than_grace(modified: std::io::Result<SystemTime>) -> bool {
+    modified
+        .ok()
+        .and_then(|m| m.elapsed().ok())
+        .is_some_and(|age| age > ORPHAN_GRACE)
+}
+
+/// Checks a blob again just before it goes, in case an upload started
+/// pointing at it after the sweep read the referenced set.
+async fn is_referenced(db: &SqlitePool, sha256: &str) -> Result<bool> {
+    let referenced = sqlx::query_scalar!(
+        r#"SELECT EXISTS (SELECT 1 FROM sample_attachments WHERE sha256 = ?) AS "referenced!: bool""#,
+        sha256
+    )
+    .fetch_one(db)
+    .await?;
+    Ok(referenced)
+}
+
+/// Deletes blobs no attachment points at any more, along with abandoned
+/// partial uploads. Returns how many files were removed.
+pub async fn sweep_orphaned_blobs(db: &SqlitePool) -> Result<u64> {
+    let dir = attachments_dir();
+    if !fs::try_exists(&dir).await? {
+        return Ok(0);
+    }
+    let referenced: HashSet<String> =
+        sqlx::query_scalar!("SELECT DISTINCT sha256 FROM sample_attachments")
+            .fetch_all(db)
+            .await?
+            .into_iter()
+            .collect();
+
+    let mut removed = 0;
+    let mut shards = fs::read_dir(&dir).await?;
+    while let Some(shard) = shards.next_entry().await? {
+        if !shard.file_type().await?.is_dir() {
+            continue;
+        }
+        let is_tmp = shard.file_name() == "tmp";
+        let mut files = fs::read_dir(shard.path()).await?;
+        while let Some(file) = files.next_entry().await? {
+            let name = file.file_name().to_string_lossy().into_owned();
+            let orphaned = is_tmp || !referenced.contains(&name);
+            if orphaned
+                && older_than_grace(file.metadata().await?.modified())
+                && (is_tmp || !is_referenced(db, &name).await?)
+            {
+                fs::remove_file(file.path()).await?;
+                removed += 1;
+            }
+        }
+    }
+    Ok(removed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir() -> PathBuf {
+        std::env::temp_dir().join(format!("sample-blobs-{}", uuid::Uuid::new_v4()))
+    }
+
+    fn body(chunks: &[&[u8]]) -> impl Stream<Item = std::io::Result<Vec<u8>>> + Unpin {
+        let chunks: Vec<_> = chunks.iter().map(|c| Ok(c.to_vec())).collect();
+        futures_util::stream::iter(chunks)
+    }
+
+    async fn tmp_files(dir: &Path) -> usize {
+        let mut entries = fs::read_dir(dir.join("tmp")).await.unwrap();
+        let mut count = 0;
+        while entries.next_entry().await.unwrap().is_some() {
+            count += 1;
+        }
+        count
+    }
+
+    #[tokio::test]
+    async fn identical_uploads_share_one_blob() {
+        let dir = temp_dir();
+        let (a, size) = write_blob(&dir, body(&[b"hello ", b"world"]))
+            .await
+            .unwrap();
+        let (b, _) = write_blob(&dir, body(&[b"hello world"])).await.unwrap();
+
+        assert_eq!(a, b);
+        assert_eq!(size, 11);
+        assert_eq!(a, hex::encode(Sha256::digest(b"hello world")));
+        assert_eq!(fs::read(blob_path(&dir, &a)).await.unwrap(), b"hello world");
+        assert_eq!(tmp_files(&dir).await, 0);
+    }
+
+    #[tokio::test]
+    async fn reusing_a_blob_saves_it_from_the_sweep() {
+        let dir = temp_dir();
+        let (sha, _) = write_blob(&dir, body(&[b"hello"])).await.unwrap();
+        let old = SystemTime::now() - ORPHAN_GRACE * 2;
+        let blob = std::fs::File::options()
+            .write(true)
+            .open(blob_path(&dir, &sha))
+            .unwrap();
+        blob.set_modified(old).unwrap();
+        let modified = || std::fs::metadata(blob_path(&dir, &sha)).and_then(|m| m.modified());
+        assert!(older_than_grace(modified()));
+
+        write_blob(&dir, body(&[b"hello"])).await.unwrap();
+        assert!(!older_than_grace(modified()));
+    }
+
+    #[tokio::test]
+    async fn empty_and_oversized_uploads_leave_nothing_behind() {
+        let dir = temp_dir();
+        let e = write_blob(&dir, body(&[])).await.unwrap_err();
+        assert!(matches!(
+            e.downcast_ref::<ServiceError>(),
+            Some(ServiceError::Invalid(_))
+        ));
+
+        let chunk = vec![0u8; 1024 * 1024];
+        let chunks: Vec<&[u8]> = (0..=MAX_ATTACHMENT_BYTES / chunk.len())
+            .map(|_| chunk.as_slice())
+            .collect();
+        let e = write_blob(&dir, body(&chunks)).await.unwrap_err();
+        assert!(matches!(
+            e.downcast_ref::<ServiceError>(),
+            Some(ServiceError::TooLarge { .. })
+        ));
+        assert_eq!(tmp_files(&dir).await, 0);
+    }
+
+    #[test]
+    fn file_names_lose_their_directories() {
+        assert_eq!(
+            clean_file_name("C:\\Users\\me\\scan.pdf").unwrap(),
+            "scan.pdf"
+        );
+        assert_eq!(clean_file_name("../../etc/passwd").unwrap(), "passwd");
+        assert!(clean_file_name("dir/ ").is_err());
+    }
+
+    #[test]
+    fn only_allowed_types_are_kept() {
+        assert_eq!(
+            content_type_for("scan.pdf", None).unwrap(),
+            "application/pdf"
+        );
+        assert_eq!(
+            content_type_for("blob", Some("text/plain; charset=utf-8")).unwrap(),
+            "text/plain"
+        );
+        // the extension wins over what the client claims
+        assert!(content_type_for("page.html", Some("text/plain")).is_err());
+        assert!(content_type_for("logo.svg", None).is_err());
+    }
+}
@@ -0,0 +1,243 @@
+use anyhow::Result;
+use serde::Serialize;
+use serde_json::Value;
+use sqlx::{types::Json, QueryBuilder, Sqlite, SqliteConnection};
+use std::future::Future;
+
+use crate::models::{
+    audit::{AuditEntry, AuditPage, AuditQuery, Channel},
+    state::WebState,
+};
+use crate::services::sample::parse_datetime;
+
+pub const DEFAULT_PAGE_SIZE: i64 = 100;
+pub const MAX_PAGE_SIZE: i64 = 1000;
+
+/// Where the current unit of work came from. Set once per request, Kafka
+/// command or background job, and picked up by every `record` call under it.
+#[derive(Debug, Clone)]
+pub struct AuditContext {
+    pub channel: Channel,
+    pub request_id: Option<String>,
+}
+
+tokio::task_local! {
+    static CONTEXT: AuditContext;
+}
+
+/// Runs `fut` with `ctx` as its audit context.
+pub async fn with_context<F: Future>(ctx: AuditContext, fut: F) -> F::Output {
+    CONTEXT.scope(ctx, fut).await
+}
+
+/// The context of the running task. Work started outside any request counts
+/// as the system's.
+fn current() -> AuditContext {
+    CONTEXT.try_with(Clone::clone).unwrap_or(AuditContext {
+        channel: Channel::System,
+        request_id: None,
+    })
+}
+
+/// A value as it is stored in `before_json`/`after_json`.
+pub fn snapshot<T: Serialize>(value: &T) -> Option<Value> {
+    serde_json::to_value(value).ok()
+}
+
+/// Appends an entry on the caller's connection, so it commits or rolls back
+/// with the change it describes.
+pub(crate) async fn record(
+    conn: &mut SqliteConnection,
+    actor_id: Option<i64>,
+    action: &str,
+    sample_id: Option<i64>,
+    before: Option<Value>,
+    after: Option<Value>,
+) -> Result<()> {
+    let ctx = current();
+    let before = before.map(Json);
+    let after = after.map(Json);
+    sqlx::query!(
+        r#"
+            INSERT INTO audit_log
+                (actor_id, channel, action, sample_id, before_json, after_json, request_id)
+            VALUES (?, ?, ?, ?, ?, ?, ?)
+        "#,
+        actor_id,
+        ctx.channel,
+        action,
+        sample_id,
+        before,
+        after,
+        ctx.request_id
+    )
+    .execute(conn)
+    .await?;
+    Ok(())
+}
+
+pub async fn get_entries(state: &WebState, query: &AuditQuery) -> Result<AuditPage> {
+    let limit = query
+        .limit
+        .unwrap_or(DEFAULT_PAGE_SIZE)
+        .clamp(1, MAX_PAGE_SIZE);
+
+    let mut qb: QueryBuilder<Sqlite> = QueryBuilder::new(
+        "SELECT a.id, a.actor_id, u.email AS actor_email, a.channel, a.action, a.sample_id, \
+         a.before_json AS before, a.after_json AS after, a.request_id, a.created_at \
+         FROM audit_log a LEFT JOIN users u ON u.id = a.actor_id WHERE 1 = 1",
+    );
+    if let Some(actor_id) = query.actor_id {
+        qb.push(" AND a.actor_id = ").push_bind(actor_id);
+    }
+    if let Some(channel) = query.channel {
+        qb.push(" AND a.channel = ").push_bind(channel);
+    }
+    if let Some(action) = &query.action {
+        if action.ends_with('.') {
+            qb.push(" AND a.action LIKE ")
+                .push_bind(format!("{action}%"));
+        } else {
+            qb.push(" AND a.action = ").push_bind(action.clone());
+        }
+    }
+    if let Some(sample_id) = query.sample_id {
+        qb.push(" AND a.sample_id = ").push_bind(sample_id);
+    }
+    if let Some(request_id) = &query.request_id {
+        qb.push(" AND a.request_id = ")
+            .push_bind(request_id.clone());
+    }
+    if let Some(since) = &query.since {
+        qb.push(" AND a.created_at >= ")
+            .push_bind(parse_datetime(since)?);
+    }
+    if let Some(until) = &query.until {
+        qb.push(" AND a.created_at < ")
+            .push_bind(parse_datetime(until)?);
+    }
+    if let Some(before_id) = query.before_id {
+        qb.push(" AND a.id < ").push_bind(before_id);
+    }
+    // one extra row tells us whether there is another page
+    qb.push(" ORDER BY a.id DESC LIMIT ").push_bind(limit + 1);
+
+    let mut items: Vec<AuditEntry> = qb.build_query_as().fetch_all(&state.db).await?;
+    let next_before_id = if items.len() as i64 > limit {
+        items.truncate(limit as usize);
+        items.last().map(|e| e.id)
+    } else {
+        None
+    };
+    Ok(AuditPage {
+        items,
+        next_before_id,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::share::SampleRole;
+    use crate::services::sample::{create_sample, update_sample_by_id};
+    use crate::testing;
+
+    fn actions(page: &AuditPage) -> Vec<&str> {
+        page.items.iter().map(|e| e.action.as_str()).collect()
+    }
+
+    #[tokio::test]
+    async fn changes_carry_the_context_they_ran_in() {
+        let state = testing::state().await;
+        let uid = testing::user(&state.db, "ann@example.com").await;
+        let ctx = AuditContext {
+            channel: Channel::Api,
+            request_id: Some("req-1".into()),
+        };
+        let sample = with_context(ctx, testing::sample(&state, uid, "Blue dye")).await;
+        update_sample_by_id(&state, testing::input("Red dye"), sample.id, uid)
+            .await
+            .unwrap();
+
+        let query = AuditQuery {
+            sample_id: Some(sample.id),
+            ..Default::default()
+        };
+        let page = get_entries(&state, &query).await.unwrap();
+        assert_eq!(actions(&page), ["sample.updated", "sample.created"]);
+        let (updated, created) = (&page.items[0], &page.items[1]);
+        assert_eq!(created.channel, Channel::Api);
+        assert_eq!(created.request_id.as_deref(), Some("req-1"));
+        assert_eq!(created.actor_email.as_deref(), Some("ann@example.com"));
+        assert!(created.before.is_none());
+        assert_eq!(updated.channel, Channel::System);
+        assert_eq!(updated.before.as_ref().unwrap()["name"], "Blue dye");
+        assert_eq!(updated.after.as_ref().unwrap()["name"], "Red dye");
+    }
+
+    #[tokio::test]
+    async fn failed_changes_leave_no_entry() {
+        let state = testing::state().await;
+        let uid = testing::user(&state.db, "ann@example.com").await;
+        let sample = testing::sample(&state, uid, "Blue dye").await;
+        let mut stale = testing::input("Red dye");
+        stale.version = Some(sample.version + 5);
+        assert!(update_sample_by_id(&state, stale, sample.id, uid)
+            .await
+            .is_err());
+        assert!(create_sample(&state, testing::input(" "), uid)
+            .await
+            .is_err());
+
+        let page = get_entries(&state, &AuditQuery::default()).await.unwrap();
+        assert_eq!(actions(&page), ["sample.created"]);
+    }
+
+    #[tokio::test]
+    async fn entries_cannot_be_changed_or_removed() {
+        let state = testing::state().await;
+        let uid = testing::user(&state.db, "ann@example.com").await;
+        testing::sample(&state, uid, "Blue dye").await;
+
+        let update = sqlx::query("UPDATE audit_log SET action = 'forged'")
+            .execute(&state.db)
+            .await;
+        assert!(update.is_err());
+        let delete = sqlx::query("DELETE FROM audit_log")
+            .execute(&state.db)
+            .await;
+        assert!(delete.is_err());
+        let page = get_entries(&state, &AuditQuery::default()).await.unwrap();
+        assert_eq!(actions(&page), ["sample.created"]);
+    }
+
+    #[tokio::test]
+    async fn entries_filter_by_action_prefix_and_page_by_id() {
+        let state = testing::state().await;
+        let uid = testing::user(&state.db, "ann@example.com").await;
+        for name in ["a", "b", "c"] {
+            testing::sample(&state, uid, name).await;
+        }
+        let s = testing::sample(&state, uid, "d").await;
+        testing::user(&state.db, "bob@example.com").await;
+        testing::share(&state, s.id, uid, "bob@example.com", SampleRole::Viewer).await;
+
+        let mut query = AuditQuery {
+            action: Some("sample.".into()),
+            limit: Some(3),
+            ..Default::default()
+        };
+        let first = get_entries(&state, &query).await.unwrap();
+        assert_eq!(first.items.len(), 3);
+        query.before_id = first.next_before_id;
+        let second = get_entries(&state, &query).await.unwrap();
+        assert_eq!(actions(&second), ["sample.created"]);
+        assert!(second.next_before_id.is_none());
+
+        let exact = AuditQuery {
+            action: Some("share.granted".into()),
+            ..Default::default()
+        };
+        assert_eq!(get_entries(&state, &exact).await.unwrap().items.len(), 1);
+    }
+}
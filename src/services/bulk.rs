@@ -0,0 +1,274 @@
+use anyhow::{bail, Result};
+use sqlx::{Acquire, SqliteConnection};
+
+use crate::models::{
+    bulk::{BulkItemResult, BulkItemStatus, BulkMode, BulkOperation, BulkRequest, BulkResponse},
+    sample::Sample,
+    state::WebState,
+};
+use crate::services::{
+    error::{status_code, ServiceError},
+    sample::{insert_sample, trash_sample, write_update, UpdateKind},
+};
+
+pub const MAX_BULK_OPERATIONS: usize = 1000;
+
+/// What a successful operation did, kept until commit so events are only
+/// published for writes that actually landed.
+enum Applied {
+    Created(Sample),
+    Updated(Sample),
+    Deleted(i64),
+}
+
+async fn apply(conn: &mut SqliteConnection, op: BulkOperation, uid: i64) -> Result<Applied> {
+    match op {
+        BulkOperation::Create { input } => {
+            insert_sample(conn, input, uid).await.map(Applied::Created)
+        }
+        BulkOperation::Update { id, input } => write_update(conn, input, id, uid, UpdateKind::Edit)
+            .await
+            .map(Applied::Updated),
+        BulkOperation::Delete { id, version } => trash_sample(conn, id, version, uid)
+            .await
+            .map(|_| Applied::Deleted(id)),
+    }
+}
+
+fn error_message(err: &anyhow::Error) -> String {
+    if status_code(err).is_server_error() {
+        tracing::error!(?err, "bulk operation failed");
+        "internal server error".into()
+    } else {
+        err.to_string()
+    }
+}
+
+fn ok_result(index: usize, applied: &Applied) -> BulkItemResult {
+    let (sample, id) = match applied {
+        Applied::Created(s) | Applied::Updated(s) => (Some(s.clone()), Some(s.id)),
+        Applied::Deleted(id) => (None, Some(*id)),
+    };
+    BulkItemResult {
+        index,
+        status: BulkItemStatus::Ok,
+        sample,
+        id,
+        error: None,
+    }
+}
+
+/// Runs a batch of creates, updates and deletes in a single transaction.
+/// In per-item mode each operation gets its own savepoint so a failure only
+/// undoes that operation. Events are published once the transaction commits.
+pub async fn run_bulk(state: &WebState, req: BulkRequest, uid: i64) -> Result<BulkResponse> {
+    if req.operations.len() > MAX_BULK_OPERATIONS {
+        bail!(ServiceError::Invalid(format!(
+            "at most {MAX_BULK_OPERATIONS} operations per request"
+        )));
+    }
+
+    let total = req.operations.len();
+    let mut tx = state.db.begin().await?;
+    let mut results = Vec::with_capacity(total);
+    let mut applied = Vec::new();
+    let mut failed = false;
+
+    for (index, op) in req.operations.into_iter().enumerate() {
+        let mut savepoint = tx.begin().await?;
+        match apply(&mut savepoint, op, uid).await {
+            Ok(done) => {
+                savepoint.commit().await?;
+                results.push(ok_result(index, &done));
+                applied.push(done);
+            }
+            Err(e) => {
+                savepoint.rollback().await?;
+                results.push(BulkItemResult {
+                    index,
+                    status: BulkItemStatus::Failed,
+                    sample: None,
+                    id: None,
+                    error: Some(error_message(&e)),
+                });
+                if req.mode == BulkMode::AllOrNothing {
+                    failed = true;
+                    break;
+                }
+            }
+        }
+    }
+
+    if failed {
+        tx.rollback().await?;
+        for r in results.iter_mut() {
+            if r.status == BulkItemStatus::Ok {
+                r.status = BulkItemStatus::RolledBack;
+                r.sample = None;
+                r.id = None;
+            }
+        }
+        for index in results.len()..total {
+            results.push(BulkItemResult {
+                index,
+                status: BulkItemStatus::Skipped,
+                sample: None,
+                id: None,
+                error: None,
+            });
+        }
+        return Ok(BulkResponse {
+            committed: false,
+            results,
+        });
+    }
+
+    tx.commit().await?;
+
+    for done in applied {
+        let published = match done {
+            Applied::Created(s) => state.events.sample_created(s).await,
+            Applied::Updated(s) => state.events.sample_updated(s).await,
+            Applied::Deleted(id) => state.events.sample_deleted(id).await,
+        };
+        if let Err(e) = published {
+            tracing::error!(?e, "failed to publish bulk event");
+        }
+    }
+
+    Ok(BulkResponse {
+        committed: true,
+        results,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+
+    /// Renames `existing`, then deletes a sample that isn't there, then
+    /// creates one.
+    fn batch(mode: BulkMode, existing: i64) -> BulkRequest {
+        BulkRequest {
+            mode,
+            operations: vec![
+                BulkOperation::Update {
+                    id: existing,
+                    input: testing::input("Renamed"),
+                },
+                BulkOperation::Delete {
+                    id: existing + 1000,
+                    version: None,
+                },
+                BulkOperation::Create {
+                    input: testing::input("Created"),
+                },
+            ],
+        }
+    }
+
+    async fn names(state: &WebState) -> Vec<String> {
+        sqlx::query_scalar!("SELECT name FROM samples ORDER BY id")
+            .fetch_all(&state.db)
+            .await
+            .unwrap()
+    }
+
+    fn statuses(response: &BulkResponse) -> Vec<BulkItemStatus> {
+        response.results.iter().map(|r| r.status).collect()
+    }
+
+    #[tokio::test]
+    async fn all_or_nothing_rolls_back_on_failure() {
+        let state = testing::state().await;
+        let uid = testing::user(&state.db, "owner@example.com").await;
+        let sample = testing::sample(&state, uid, "Original").await;
+
+        let response = run_bulk(&state, batch(BulkMode::AllOrNothing, sample.id), uid)
+            .await
+            .unwrap();
+        assert!(!response.committed);
+        assert_eq!(
+            statuses(&response),
+            [
+                BulkItemStatus::RolledBack,
+                BulkItemStatus::Failed,
+                BulkItemStatus::Skipped
+            ]
+        );
+        assert!(response.results[0].sample.is_none());
+        assert!(response.results[1].error.is_some());
+        assert_eq!(names(&state).await, ["Original"]);
+    }
+
+    #[tokio::test]
+    async fn per_item_keeps_the_successes() {
+        let state = testing::state().await;
+        let uid = testing::user(&state.db, "owner@example.com").await;
+        let sample = testing::sample(&state, uid, "Original").await;
+
+        let response = run_bulk(&state, batch(BulkMode::PerItem, sample.id), uid)
+            .await
+            .unwrap();
+        assert!(response.committed);
+        assert_eq!(
+            statuses(&response),
+            [
+                BulkItemStatus::Ok,
+                BulkItemStatus::Failed,
+                BulkItemStatus::Ok
+            ]
+        );
+        assert_eq!(names(&state).await, ["Renamed", "Created"]);
+    }
+
+    #[tokio::test]
+    async fn stale_versions_fail_their_item() {
+        let state = testing::state().await;
+        let uid = testing::user(&state.db, "owner@example.com").await;
+        let sample = testing::sample(&state, uid, "Original").await;
+        let req = BulkRequest {
+            mode: BulkMode::AllOrNothing,
+            operations: vec![
+                BulkOperation::Create {
+                    input: testing::input("Created"),
+                },
+                BulkOperation::Delete {
+                    id: sample.id,
+                    version: Some(sample.version + 1),
+                },
+            ],
+        };
+
+        let response = run_bulk(&state, req, uid).await.unwrap();
+        assert!(!response.committed);
+        assert_eq!(
+            statuses(&response),
+            [BulkItemStatus::RolledBack, BulkItemStatus::Failed]
+        );
+        assert_eq!(names(&state).await, ["Original"]);
+    }
+
+    #[tokio::test]
+    async fn too_many_operations_are_rejected() {
+        let state = testing::state().await;
+        let uid = testing::user(&state.db, "owner@example.com").await;
+        let operations = (0..=MAX_BULK_OPERATIONS)
+            .map(|_| BulkOperation::Create {
+                input: testing::input("Created"),
+            })
+            .collect();
+        let req = BulkRequest {
+            mode: BulkMode::PerItem,
+            operations,
+        };
+
+        let e = run_bulk(&state, req, uid).await.unwrap_err();
+        assert!(matches!(
+            e.downcast_ref::<ServiceError>(),
+            Some(ServiceError::Invalid(_))
+        ));
+        assert!(names(&state).await.is_empty());
+    }
+}
@@ -0,0 +1,372 @@
+use anyhow::{bail, Result};
+use sqlx::SqliteConnection;
+use std::collections::HashMap;
+
+use crate::models::{
+    comment::{Comment, CommentInput, CommentUpdate, ThreadedComment},
+    share::SampleRole,
+    state::WebState,
+};
+use crate::services::{
+    audit::{self, snapshot},
+    error::ServiceError,
+    sample::authorize,
+};
+
+pub const MAX_COMMENT_LEN: usize = 5000;
+
+fn validate_body(body: &str) -> Result<String> {
+    let body = body.trim();
+    if body.is_empty() {
+        bail!(ServiceError::Invalid("comment is empty".into()));
+    }
+    if body.chars().count() > MAX_COMMENT_LEN {
+        bail!(ServiceError::Invalid(format!(
+            "comment is longer than {MAX_COMMENT_LEN} characters"
+        )));
+    }
+    Ok(body.to_string())
+}
+
+/// Orders comments depth-first so every reply follows its parent, oldest
+/// first at each level.
+fn thread(comments: Vec<Comment>) -> Vec<ThreadedComment> {
+    let mut children: HashMap<Option<i64>, Vec<Comment>> = HashMap::new();
+    for c in comments {
+        children.entry(c.parent_id).or_default().push(c);
+    }
+
+    let mut out = Vec::new();
+    let mut stack: Vec<(Comment, usize)> = children
+        .remove(&None)
+        .unwrap_or_default()
+        .into_iter()
+        .rev()
+        .map(|c| (c, 0))
+        .collect();
+    while let Some((comment, depth)) = stack.pop() {
+        if let Some(replies) = children.remove(&Some(comment.id)) {
+            stack.extend(replies.into_iter().rev().map(|c| (c, depth + 1)));
+        }
+        out.push(ThreadedComment { comment, depth });
+    }
+    out
+}
+
+pub async fn get_comments(state: &WebState, sample_id: i64) -> Result<Vec<ThreadedComment>> {
+    let comments = sqlx::query_as!(
+        Comment,
+        r#"
+            SELECT
+                c.id          AS "id!",
+                c.sample_id   AS "sample_id!",
+                c.parent_id   AS "parent_id?",
+                c.author_id   AS "author_id!",
+                u.email       AS "author_email!",
+                CASE WHEN c.deleted_at IS NULL THEN c.body ELSE '' END AS "body!: String",
+                c.created_at  AS "created_at!",
+                c.updated_at  AS "updated_at?",
+                c.deleted_at  AS "deleted_at?"
+            FROM sample_comments c
+            JOIN users u ON u.id = c.author_id
+            WHERE c.sample_id = ?
+            ORDER BY c.id
+        "#,
+        sample_id
+    )
+    .fetch_all(&state.db)
+    .await?;
+    Ok(thread(comments))
+}
+
+pub async fn get_comment(state: &WebState, sample_id: i64, id: i64) -> Result<Comment> {
+    let mut conn = state.db.acquire().await?;
+    fetch_comment(&mut conn, sample_id, id).await
+}
+
+async fn fetch_comment(conn: &mut SqliteConnection, sample_id: i64, id: i64) -> Result<Comment> {
+    let comment = sqlx::query_as!(
+        Comment,
+        r#"
+            SELECT
+                c.id          AS "id!",
+                c.sample_id   AS "sample_id!",
+                c.parent_id   AS "parent_id?",
+                c.author_id   AS "author_id!",
+                u.email       AS "author_email!",
+                CASE WHEN c.deleted_at IS NULL THEN c.body ELSE '' END AS "body!: String",
+                c.created_at  AS "created_at!",
+                c.updated_at  AS "updated_at?",
+                c.deleted_at  AS "deleted_at?"
+            FROM sample_comments c
+            JOIN users u ON u.id = c.author_id
+            WHERE c.id = ? AND c.sample_id = ?
+        "#,
+        id,
+        sample_id
+    )
+    .fetch_optional(conn)
+    .await?;
+    comment.ok_or_else(|| ServiceError::NotFound("comment", id).into())
+}
+
+pub async fn add_comment(
+    state: &WebState,
+    sample_id: i64,
+    input: CommentInput,
+    author_id: i64,
+) -> Result<Comment> {
+    authorize(state, sample_id, author_id, SampleRole::Viewer).await?;
+    let body = validate_body(&input.body)?;
+    if let Some(parent_id) = input.parent_id {
+        let parent = get_comment(state, sample_id, parent_id).await?;
+        if parent.deleted_at.is_some() {
+            bail!(ServiceError::Invalid(
+                "cannot reply to a deleted comment".into()
+            ));
+        }
+    }
+
+    let mut tx = state.db.begin().await?;
+    let id = sqlx::query_scalar!(
+        r#"
+            INSERT INTO sample_comments (sample_id, parent_id, author_id, body)
+            VALUES (?, ?, ?, ?)
+            RETURNING id AS "id!"
+        "#,
+        sample_id,
+        input.parent_id,
+        author_id,
+        body
+    )
+    .fetch_one(&mut *tx)
+    .await?;
+    let comment = fetch_comment(&mut tx, sample_id, id).await?;
+    audit::record(
+        &mut tx,
+        Some(author_id),
+        "comment.added",
+        Some(sample_id),
+        None,
+        snapshot(&comment),
+    )
+    .await?;
+    tx.commit().await?;
+
+    state.events.comment_added(comment.clone()).await.ok();
+    Ok(comment)
+}
+
+/// Loads a live comment and checks `user_id` wrote it and can still see the
+/// sample.
+async fn own_comment(state: &WebState, sample_id: i64, id: i64, user_id: i64) -> Result<Comment> {
+    authorize(state, sample_id, user_id, SampleRole::Viewer).await?;
+    let comment = get_comment(state, sample_id, id).await?;
+    if comment.deleted_at.is_some() {
+        bail!(ServiceError::NotFound("comment", id));
+    }
+    if comment.author_id != user_id {
+        bail!(ServiceError::Forbidden(
+            "only the author can change a comment"
+        ));
+    }
+    Ok(comment)
+}
+
+pub async fn update_comment(
+    state: &WebState,
+    sample_id: i64,
+    id: i64,
+    input: CommentUpdate,
+    user_id: i64,
+) -> Result<Comment> {
+    let before = own_comment(state, sample_id, id, user_id).await?;
+    let body = validate_body(&input.body)?;
+    let mut tx = state.db.begin().await?;
+    sqlx::query!(
+        "UPDATE sample_comments SET body = ?, updated_at = datetime('now') WHERE id = ?",
+        body,
+        id
+    )
+    .execute(&mut *tx)
+    .await?;
+    let comment = fetch_comment(&mut tx, sample_id, id).await?;
+    audit::record(
+        &mut tx,
+        Some(user_id),
+        "comment.updated",
+        Some(sample_id),
+        snapshot(&before),
+        snapshot(&comment),
+    )
+    .await?;
+    tx.commit().await?;
+    Ok(comment)
+}
+
+/// Blanks the comment but keeps its row, so any replies stay threaded under it.
+pub async fn delete_comment(state: &WebState, sample_id: i64, id: i64, user_id: i64) -> Result<()> {
+    let before = own_comment(state, sample_id, id, user_id).await?;
+    let mut tx = state.db.begin().await?;
+    sqlx::query!(
+        "UPDATE sample_comments SET body = '', deleted_at = datetime('now') WHERE id = ?",
+        id
+    )
+    .execute(&mut *tx)
+    .await?;
+    audit::record(
+        &mut tx,
+        Some(user_id),
+        "comment.deleted",
+        Some(sample_id),
+        snapshot(&before),
+        None,
+    )
+    .await?;
+    tx.commit().await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{models::share::SampleRole, services::error::status_code, testing};
+    use axum::http::StatusCode;
+
+    fn say(body: &str, parent_id: Option<i64>) -> CommentInput {
+        CommentInput {
+            body: body.to_string(),
+            parent_id,
+        }
+    }
+
+    /// Bodies and depths in thread order.
+    async fn outline(state: &WebState, sample_id: i64) -> Vec<(String, usize)> {
+        get_comments(state, sample_id)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|t| (t.comment.body, t.depth))
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn replies_follow_their_parent() {
+        let state = testing::state().await;
+        let uid = testing::user(&state.db, "ann@example.com").await;
+        let s = testing::sample(&state, uid, "Threaded").await;
+
+        let first = add_comment(&state, s.id, say("first", None), uid)
+            .await
+            .unwrap();
+        let second = add_comment(&state, s.id, say("second", None), uid)
+            .await
+            .unwrap();
+        let reply = add_comment(&state, s.id, say("reply", Some(first.id)), uid)
+            .await
+            .unwrap();
+        add_comment(&state, s.id, say("nested", Some(reply.id)), uid)
+            .await
+            .unwrap();
+        add_comment(&state, s.id, say("  late reply  ", Some(second.id)), uid)
+            .await
+            .unwrap();
+
+        assert_eq!(
+            outline(&state, s.id).await,
+            [
+                ("first".to_string(), 0),
+                ("reply".to_string(), 1),
+                ("nested".to_string(), 2),
+                ("second".to_string(), 0),
+                ("late reply".to_string(), 1),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn bodies_and_parents_are_checked() {
+        let state = testing::state().await;
+        let uid = testing::user(&state.db, "ann@example.com").await;
+        let s = testing::sample(&state, uid, "One").await;
+        let other = testing::sample(&state, uid, "Two").await;
+        let elsewhere = add_comment(&state, other.id, say("hi", None), uid)
+            .await
+            .unwrap();
+
+        let blank = add_comment(&state, s.id, say("   ", None), uid)
+            .await
+            .unwrap_err();
+        assert_eq!(status_code(&blank), StatusCode::UNPROCESSABLE_ENTITY);
+        let long = "x".repeat(MAX_COMMENT_LEN + 1);
+        let long = add_comment(&state, s.id, say(&long, None), uid)
+            .await
+            .unwrap_err();
+        assert_eq!(status_code(&long), StatusCode::UNPROCESSABLE_ENTITY);
+        let cross = add_comment(&state, s.id, say("hi", Some(elsewhere.id)), uid)
+            .await
+            .unwrap_err();
+        assert_eq!(status_code(&cross), StatusCode::NOT_FOUND);
+        let missing = add_comment(&state, 9999, say("hi", None), uid)
+            .await
+            .unwrap_err();
+        assert_eq!(status_code(&missing), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn only_the_author_edits_or_deletes() {
+        let state = testing::state().await;
+        let ann = testing::user(&state.db, "ann@example.com").await;
+        let bob = testing::user(&state.db, "bob@example.com").await;
+        let s = testing::sample(&state, ann, "Shared").await;
+        testing::share(&state, s.id, ann, "bob@example.com", SampleRole::Editor).await;
+        let c = add_comment(&state, s.id, say("draft", None), ann)
+            .await
+            .unwrap();
+
+        let edit = CommentUpdate {
+            body: "hijacked".into(),
+        };
+        let err = update_comment(&state, s.id, c.id, edit, bob)
+            .await
+            .unwrap_err();
+        assert_eq!(status_code(&err), StatusCode::FORBIDDEN);
+        let err = delete_comment(&state, s.id, c.id, bob).await.unwrap_err();
+        assert_eq!(status_code(&err), StatusCode::FORBIDDEN);
+
+        let edit = CommentUpdate {
+            body: "final".into(),
+        };
+        let edited = update_comment(&state, s.id, c.id, edit, ann).await.unwrap();
+        assert_eq!(edited.body, "final");
+        assert!(edited.updated_at.is_some());
+    }
+
+    #[tokio::test]
+    async fn deleted_comments_keep_their_replies() {
+        let state = testing::state().await;
+        let uid = testing::user(&state.db, "ann@example.com").await;
+        let s = testing::sample(&state, uid, "Threaded").await;
+        let parent = add_comment(&state, s.id, say("secret", None), uid)
+            .await
+            .unwrap();
+        add_comment(&state, s.id, say("reply", Some(parent.id)), uid)
+            .await
+            .unwrap();
+
+        delete_comment(&state, s.id, parent.id, uid).await.unwrap();
+        assert_eq!(
+            outline(&state, s.id).await,
+            [(String::new(), 0), ("reply".to_string(), 1)]
+        );
+
+        let err = add_comment(&state, s.id, say("late", Some(parent.id)), uid)
+            .await
+            .unwrap_err();
+        assert_eq!(status_code(&err), StatusCode::UNPROCESSABLE_ENTITY);
+        let err = delete_comment(&state, s.id, parent.id, uid)
+            .await
+            .unwrap_err();
+        assert_eq!(status_code(&err), StatusCode::NOT_FOUND);
+    }
+}
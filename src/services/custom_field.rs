@@ -0,0 +1,490 @@
+use anyhow::{bail, Result};
+use chrono::NaiveDate;
+use regex::Regex;
+use serde_json::{Map, Value};
+use sqlx::{types::Json, SqliteConnection};
+
+use crate::models::{
+    custom_field::{CustomField, CustomFieldInput, CustomFieldUpdate, FieldType},
+    state::WebState,
+};
+use crate::services::{
+    audit::{self, snapshot},
+    error::ServiceError,
+};
+
+pub const MAX_KEY_LEN: usize = 50;
+pub const MAX_TEXT_VALUE_LEN: usize = 1000;
+
+pub(crate) async fn load_fields(conn: &mut SqliteConnection) -> Result<Vec<CustomField>> {
+    let fields = sqlx::query_as!(
+        CustomField,
+        r#"
+            SELECT
+                id         AS "id!",
+                key        AS "key!",
+                label      AS "label!",
+                field_type AS "field_type!: FieldType",
+                required   AS "required!: bool",
+                options    AS "options!: Json<Vec<String>>",
+                pattern    AS "pattern?",
+                position   AS "position!",
+                created_at AS "created_at!"
+            FROM custom_fields
+            ORDER BY position, id
+        "#
+    )
+    .fetch_all(conn)
+    .await?;
+    Ok(fields)
+}
+
+pub async fn get_fields(state: &WebState) -> Result<Vec<CustomField>> {
+    let mut conn = state.db.acquire().await?;
+    load_fields(&mut conn).await
+}
+
+async fn fetch_field(conn: &mut SqliteConnection, id: i64) -> Result<CustomField> {
+    load_fields(conn)
+        .await?
+        .into_iter()
+        .find(|f| f.id == id)
+        .ok_or_else(|| ServiceError::NotFound("custom field", id).into())
+}
+
+async fn get_field(state: &WebState, id: i64) -> Result<CustomField> {
+    let mut conn = state.db.acquire().await?;
+    fetch_field(&mut conn, id).await
+}
+
+fn invalid(msg: String) -> anyhow::Error {
+    ServiceError::Invalid(msg).into()
+}
+
+/// Options only make sense on enum fields and patterns on text fields.
+fn validate_definition(
+    field_type: FieldType,
+    label: &str,
+    options: &[String],
+    pattern: Option<&str>,
+) -> Result<()> {
+    if label.trim().is_empty() {
+        return Err(invalid("label is required".into()));
+    }
+    match field_type {
+        FieldType::Enum if options.iter().all(|o| o.trim().is_empty()) => {
+            return Err(invalid("enum fields need at least one option".into()));
+        }
+        FieldType::Enum => {}
+        _ if !options.is_empty() => {
+            return Err(invalid("only enum fields take options".into()));
+        }
+        _ => {}
+    }
+    if let Some(pattern) = pattern {
+        if field_type != FieldType::Text {
+            return Err(invalid("only text fields take a pattern".into()));
+        }
+        Regex::new(pattern).map_err(|e| invalid(format!("invalid pattern: {e}")))?;
+    }
+    Ok(())
+}
+
+fn clean_options(options: Vec<String>) -> Vec<String> {
+    let mut out: Vec<String> = Vec::new();
+    for option in options {
+        let option = option.trim().to_string();
+        if !option.is_empty() && !out.contains(&option) {
+            out.push(option);
+        }
+    }
+    out
+}
+
+pub async fn create_field(
+    state: &WebState,
+    input: CustomFieldInput,
+    actor_id: i64,
+) -> Result<CustomField> {
+    let key = input.key.trim();
+    let valid_key = key.len() <= MAX_KEY_LEN
+        && key.starts_with(|c: char| c.is_ascii_lowercase())
+        && key
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_');
+    if !valid_key {
+        bail!(ServiceError::Invalid(format!(
+            "key must be lowercase letters, digits and underscores, starting with a letter, at most {MAX_KEY_LEN} long"
+        )));
+    }
+    let pattern = input.pattern.filter(|p| !p.is_empty());
+    let options = clean_options(input.options);
+    validate_definition(input.field_type, &input.label, &options, pattern.as_deref())?;
+
+    let exists = sqlx::query_scalar!("SELECT COUNT(1) FROM custom_fields WHERE key = ?", key)
+        .fetch_one(&state.db)
+        .await?;
+    if exists > 0 {
+        bail!(ServiceError::Invalid(format!(
+            "field `{key}` already exists"
+        )));
+    }
+
+    let label = input.label.trim();
+    let options = Json(options);
+    let mut tx = state.db.begin().await?;
+    let id = sqlx::query_scalar!(
+        r#"
+            INSERT INTO custom_fields (key, label, field_type, required, options, pattern, position)
+            VALUES (?, ?, ?, ?, ?, ?, ?)
+            RETURNING id AS "id!"
+        "#,
+        key,
+        label,
+        input.field_type,
+        input.required,
+        options,
+        pattern,
+        input.position
+    )
+    .fetch_one(&mut *tx)
+    .await?;
+    let field = fetch_field(&mut tx, id).await?;
+    audit::record(
+        &mut tx,
+        Some(actor_id),
+        "custom_field.created",
+        None,
+        None,
+        snapshot(&field),
+    )
+    .await?;
+    tx.commit().await?;
+    Ok(field)
+}
+
+pub async fn update_field(
+    state: &WebState,
+    id: i64,
+    input: CustomFieldUpdate,
+    actor_id: i64,
+) -> Result<CustomField> {
+    let field = get_field(state, id).await?;
+    let pattern = input.pattern.filter(|p| !p.is_empty());
+    let options = clean_options(input.options);
+    validate_definition(field.field_type, &input.label, &options, pattern.as_deref())?;
+
+    let label = input.label.trim();
+    let options = Json(options);
+    let mut tx = state.db.begin().await?;
+    sqlx::query!(
+        r#"UPDATE custom_fields
+SET label = ?, required = ?, options = ?, pattern = ?, position = ?
+WHERE id = ?"#,
+        label,
+        input.required,
+        options,
+        pattern,
+        input.position,
+        id
+    )
+    .execute(&mut *tx)
+    .await?;
+    let updated = fetch_field(&mut tx, id).await?;
+    audit::record(
+        &mut tx,
+        Some(actor_id),
+        "custom_field.updated",
+        None,
+        snapshot(&field),
+        snapshot(&updated),
+    )
+    .await?;
+    tx.commit().await?;
+    Ok(updated)
+}
+
+/// Removes the definition and its values from every sample.
+pub async fn delete_field(state: &WebState, id: i64, actor_id: i64) -> Result<()> {
+    let field = get_field(state, id).await?;
+    let path = format!("$.{}", field.key);
+    let mut tx = state.db.begin().await?;
+    sqlx::query!(
+        "UPDATE samples SET custom_fields = json_remove(custom_fields, ?) WHERE json_extract(custom_fields, ?) IS NOT NULL",
+        path,
+        path
+    )
+    .execute(&mut *tx)
+    .await?;
+    sqlx::query!("DELETE FROM custom_fields WHERE id = ?", id)
+        .execute(&mut *tx)
+        .await?;
+    audit::record(
+        &mut tx,
+        Some(actor_id),
+        "custom_field.deleted",
+        None,
+        snapshot(&field),
+        None,
+    )
+    .await?;
+    tx.commit().await?;
+    Ok(())
+}
+
+/// Converts one value to the field's type. Strings are accepted for every
+/// type so HTML form posts, where everything is a string, validate the same
+/// way as JSON.
+fn coerce(field: &CustomField, value: &Value) -> Result<Value, String> {
+    let as_text = |v: &Value| match v {
+        Value::String(s) => Some(s.trim().to_string()),
+        _ => None,
+    };
+    match field.field_type {
+        FieldType::Text => {
+            let text = as_text(value).ok_or("expected text")?;
+            if text.chars().count() > MAX_TEXT_VALUE_LEN {
+                return Err(format!("longer than {MAX_TEXT_VALUE_LEN} characters"));
+            }
+            if let Some(pattern) = &field.pattern {
+                let re = Regex::new(&format!("^(?:{pattern})$")).map_err(|e| e.to_string())?;
+                if !re.is_match(&text) {
+                    return Err(format!("does not match `{pattern}`"));
+                }
+            }
+            Ok(Value::String(text))
+        }
+        FieldType::Number => match value {
+            Value::Number(_) => Ok(value.clone()),
+            _ => as_text(value)
+                .and_then(|s| s.parse::<f64>().ok())
+                .and_then(serde_json::Number::from_f64)
+                .map(Value::Number)
+                .ok_or_else(|| "expected a number".into()),
+        },
+        FieldType::Integer => match value {
+            Value::Number(n) if n.is_i64() => Ok(value.clone()),
+            _ => as_text(value)
+                .and_then(|s| s.parse::<i64>().ok())
+                .map(Value::from)
+                .ok_or_else(|| "expected a whole number".into()),
+        },
+        FieldType::Boolean => match value {
+            Value::Bool(_) => Ok(value.clone()),
+            _ => match as_text(value).as_deref() {
+                Some("true" | "on" | "yes" | "1") => Ok(Value::Bool(true)),
+                Some("false" | "off" | "no" | "0") => Ok(Value::Bool(false)),
+                _ => Err("expected true or false".into()),
+            },
+        },
+        FieldType::Date => as_text(value)
+            .filter(|s| NaiveDate::parse_from_str(s, "%Y-%m-%d").is_ok())
+            .map(Value::String)
+            .ok_or_else(|| "expected a date as YYYY-MM-DD".into()),
+        FieldType::Enum => as_text(value)
+            .filter(|s| field.options.contains(s))
+            .map(Value::String)
+            .ok_or_else(|| format!("expected one of {}", field.options.join(", "))),
+    }
+}
+
+/// Checks a sample's custom values against the field definitions and returns
+/// them normalised. Nulls and empty strings count as missing.
+pub fn validate_values(
+    fields: &[CustomField],
+    values: &Map<String, Value>,
+) -> Result<Map<String, Value>> {
+    if let Some(key) = values.keys().find(|k| !fields.iter().any(|f| &f.key == *k)) {
+        bail!(ServiceError::Invalid(format!(
+            "unknown custom field `{key}`"
+        )));
+    }
+
+    let mut out = Map::new();
+    let mut errors = Vec::new();
+    for field in fields {
+        let value = values
+            .get(&field.key)
+            .filter(|v| !v.is_null() && v.as_str().is_none_or(|s| !s.trim().is_empty()));
+        match value {
+            Some(value) => match coerce(field, value) {
+                Ok(value) => {
+                    out.insert(field.key.clone(), value);
+                }
+                Err(e) => errors.push(format!("{}: {e}", field.label)),
+            },
+            None if field.required => errors.push(format!("{} is required", field.label)),
+            None => {}
+        }
+    }
+    if !errors.is_empty() {
+        bail!(ServiceError::Invalid(errors.join("; ")));
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        models::share::SampleRole,
+        services::{error::status_code, sample::authorize},
+        testing,
+    };
+    use axum::http::StatusCode;
+    use serde_json::json;
+
+    fn definition(key: &str, field_type: FieldType) -> CustomFieldInput {
+        CustomFieldInput {
+            key: key.into(),
+            label: key.to_uppercase(),
+            field_type,
+            required: false,
+            options: vec![],
+            pattern: None,
+            position: 0,
+        }
+    }
+
+    fn field(key: &str, field_type: FieldType) -> CustomField {
+        CustomField {
+            id: 0,
+            key: key.into(),
+            label: key.into(),
+            field_type,
+            required: false,
+            options: Json(vec![]),
+            pattern: None,
+            position: 0,
+            created_at: String::new(),
+        }
+    }
+
+    fn values(value: Value) -> Map<String, Value> {
+        value.as_object().unwrap().clone()
+    }
+
+    #[tokio::test]
+    async fn bad_definitions_are_rejected() {
+        let state = testing::state().await;
+        let uid = testing::user(&state.db, "admin@example.com").await;
+        create_field(&state, definition("batch", FieldType::Text), uid)
+            .await
+            .unwrap();
+
+        let mut no_options = definition("grade", FieldType::Enum);
+        no_options.options = vec![" ".into()];
+        let mut stray_options = definition("weight", FieldType::Number);
+        stray_options.options = vec!["1".into()];
+        let mut stray_pattern = definition("count", FieldType::Integer);
+        stray_pattern.pattern = Some("[0-9]+".into());
+        let mut bad_pattern = definition("code", FieldType::Text);
+        bad_pattern.pattern = Some("(".into());
+        for input in [
+            definition("Batch", FieldType::Text),
+            definition("1st", FieldType::Text),
+            definition("batch", FieldType::Text),
+            no_options,
+            stray_options,
+            stray_pattern,
+            bad_pattern,
+        ] {
+            let key = input.key.clone();
+            let err = create_field(&state, input, uid).await.unwrap_err();
+            assert_eq!(status_code(&err), StatusCode::UNPROCESSABLE_ENTITY, "{key}");
+        }
+        assert_eq!(get_fields(&state).await.unwrap().len(), 1);
+    }
+
+    #[test]
+    fn form_strings_are_coerced_to_the_field_type() {
+        let mut grade = field("grade", FieldType::Enum);
+        grade.options = Json(vec!["A".into(), "B".into()]);
+        let fields = [
+            field("weight", FieldType::Number),
+            field("count", FieldType::Integer),
+            field("sterile", FieldType::Boolean),
+            field("expires", FieldType::Date),
+            grade,
+            field("note", FieldType::Text),
+        ];
+        let form = values(json!({
+            "weight": "2.5",
+            "count": " 3 ",
+            "sterile": "on",
+            "expires": "2026-01-31",
+            "grade": "B",
+            "note": "",
+        }));
+        assert_eq!(
+            Value::Object(validate_values(&fields, &form).unwrap()),
+            json!({
+                "weight": 2.5,
+                "count": 3,
+                "sterile": true,
+                "expires": "2026-01-31",
+                "grade": "B",
+            })
+        );
+
+        let bad = values(json!({
+            "weight": "heavy",
+            "count": "1.5",
+            "sterile": "maybe",
+            "expires": "31/01/2026",
+            "grade": "C",
+        }));
+        let err = validate_values(&fields, &bad).unwrap_err();
+        assert_eq!(err.to_string().matches(';').count(), 4, "{err}");
+    }
+
+    #[test]
+    fn required_unknown_and_patterned_values_are_checked() {
+        let mut lot = field("lot", FieldType::Text);
+        lot.required = true;
+        lot.pattern = Some("[A-Z][0-9]+".into());
+        let fields = [lot];
+
+        for bad in [
+            json!({}),
+            json!({ "lot": null }),
+            json!({ "lot": "a1" }),
+            json!({ "lot": "A1x" }),
+            json!({ "lot": "A1", "extra": "x" }),
+        ] {
+            assert!(
+                validate_values(&fields, &values(bad.clone())).is_err(),
+                "{bad}"
+            );
+        }
+        let ok = validate_values(&fields, &values(json!({ "lot": " A12 " }))).unwrap();
+        assert_eq!(ok["lot"], "A12");
+    }
+
+    #[tokio::test]
+    async fn deleting_a_field_strips_its_values() {
+        let state = testing::state().await;
+        let uid = testing::user(&state.db, "admin@example.com").await;
+        let batch = create_field(&state, definition("batch", FieldType::Text), uid)
+            .await
+            .unwrap();
+        create_field(&state, definition("lot", FieldType::Text), uid)
+            .await
+            .unwrap();
+        let mut input = testing::input("Blue dye");
+        input.custom_fields = Some(values(json!({ "batch": "A1", "lot": "L7" })));
+        let sample = crate::services::sample::create_sample(&state, input, uid)
+            .await
+            .unwrap();
+
+        delete_field(&state, batch.id, uid).await.unwrap();
+        let sample = authorize(&state, sample.id, uid, SampleRole::Viewer)
+            .await
+            .unwrap();
+        assert_eq!(
+            Value::Object(sample.custom_fields.0),
+            json!({ "lot": "L7" })
+        );
+        assert_eq!(get_fields(&state).await.unwrap().len(), 1);
+    }
+}
@@ -0,0 +1,49 @@
+use http::StatusCode;
+use thiserror::Error;
+
+use crate::models::sample::SampleStatus;
+
+/// Errors the services layer raises on purpose. They travel inside
+/// `anyhow::Error` so callers can downcast to tell a rejected request apart
+/// from an unexpected failure.
+#[derive(Debug, Error)]
+pub enum ServiceError {
+    #[error("{0} {1} not found")]
+    NotFound(&'static str, i64),
+    #[error("{0}")]
+    Forbidden(&'static str),
+    #[error("cannot change status from {from} to {to}")]
+    InvalidTransition {
+        from: SampleStatus,
+        to: SampleStatus,
+    },
+    #[error("sample was changed by someone else (version {current}, expected {expected})")]
+    VersionConflict { expected: i64, current: i64 },
+    #[error("{0}")]
+    Invalid(String),
+    #[error("file is larger than the {limit} byte limit")]
+    TooLarge { limit: usize },
+    #[error("{0} files are not accepted")]
+    UnsupportedType(String),
+}
+
+impl ServiceError {
+    pub fn status_code(&self) -> StatusCode {
+        match self {
+            ServiceError::NotFound(..) => StatusCode::NOT_FOUND,
+            ServiceError::Forbidden(_) => StatusCode::FORBIDDEN,
+            ServiceError::InvalidTransition { .. } => StatusCode::CONFLICT,
+            ServiceError::VersionConflict { .. } => StatusCode::PRECONDITION_FAILED,
+            ServiceError::Invalid(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            ServiceError::TooLarge { .. } => StatusCode::PAYLOAD_TOO_LARGE,
+            ServiceError::UnsupportedType(_) => StatusCode::UNSUPPORTED_MEDIA_TYPE,
+        }
+    }
+}
+
+/// HTTP status for any error coming out of a service call.
+pub fn status_code(err: &anyhow::Error) -> StatusCode {
+    err.downcast_ref::<ServiceError>()
+        .map(ServiceError::status_code)
+        .unwrap_or(StatusCode::INTERNAL_SERVER_ERROR)
+}
@@ -0,0 +1,328 @@
+use anyhow::Result;
+use futures_util::{Stream, StreamExt};
+use rust_xlsxwriter::Workbook;
+use serde_json::Value;
+use tokio::sync::mpsc;
+
+use crate::models::{
+    custom_field::CustomField,
+    sample::{Sample, SampleQuery, SortOrder},
+    state::WebState,
+};
+use crate::services::{custom_field, import::CUSTOM_FIELD_PREFIX, sample::filtered_samples_query};
+
+pub const EXPORT_COLUMNS: [&str; 9] = [
+    "id",
+    "name",
+    "description",
+    "status",
+    "tags",
+    "created_at",
+    "updated_at",
+    "created_by",
+    "version",
+];
+
+/// Streams every sample `user_id` can see that matches the list filters, in
+/// the list's sort order. Rows are read from a cursor in a background task
+/// and handed over a bounded channel, so memory use stays flat however many
+/// samples match. The filters are checked before this returns so bad input
+/// fails the request rather than the stream.
+pub fn stream_samples(
+    state: &WebState,
+    query: &SampleQuery,
+    user_id: i64,
+) -> Result<impl Stream<Item = Result<Sample>>> {
+    let mut qb = filtered_samples_query(query, user_id)?;
+    let column = query.sort.unwrap_or_default().column();
+    let dir = match query.order.unwrap_or_default() {
+        SortOrder::Asc => "ASC",
+        SortOrder::Desc => "DESC",
+    };
+    qb.push(format!(" ORDER BY {column} {dir}, id {dir}"));
+
+    let db = state.db.clone();
+    let (tx, rx) = mpsc::channel(64);
+    tokio::spawn(async move {
+        let mut rows = qb.build_query_as::<Sample>().fetch(&db);
+        while let Some(row) = rows.next().await {
+            if tx.send(row.map_err(Into::into)).await.is_err() {
+                // client went away
+                break;
+            }
+        }
+    });
+
+    Ok(futures_util::stream::unfold(rx, |mut rx| async move {
+        rx.recv().await.map(|row| (row, rx))
+    }))
+}
+
+/// The header row: the fixed columns, then a `cf.<key>` column per custom
+/// field, which the CSV import reads back.
+fn header(fields: &[CustomField]) -> Vec<String> {
+    EXPORT_COLUMNS
+        .iter()
+        .map(|c| c.to_string())
+        .chain(
+            fields
+                .iter()
+                .map(|f| format!("{CUSTOM_FIELD_PREFIX}{}", f.key)),
+        )
+        .collect()
+}
+
+fn record(sample: &Sample, fields: &[CustomField]) -> Vec<String> {
+    let mut cells = vec![
+        sample.id.to_string(),
+        sample.name.clone(),
+        sample.description.clone().unwrap_or_default(),
+        sample.status.to_string(),
+        sample.tags.join(","),
+        sample.created_at.clone(),
+        sample.updated_at.clone().unwrap_or_default(),
+        sample.created_by.to_string(),
+        sample.version.to_string(),
+    ];
+    cells.extend(
+        fields
+            .iter()
+            .map(|f| match sample.custom_fields.get(&f.key) {
+                None | Some(Value::Null) => String::new(),
+                Some(Value::String(s)) => s.clone(),
+                Some(other) => other.to_string(),
+            }),
+    );
+    cells
+}
+
+/// Spreadsheets run a cell that starts with one of these as a formula.
+pub const FORMULA_TRIGGERS: [char; 6] = ['=', '+', '-', '@', '\t', '\r'];
+
+/// Prefixes a cell a spreadsheet would take for a formula with `'`, so an
+/// opened export can't run anything a user typed into a sample. Plain numbers
+/// such as `-3` are left alone.
+fn escape_formula(cell: String) -> String {
+    if cell.starts_with(FORMULA_TRIGGERS) && cell.parse::<f64>().is_err() {
+        format!("'{cell}")
+    } else {
+        cell
+    }
+}
+
+pub fn csv_header(fields: &[CustomField]) -> Result<Vec<u8>> {
+    let mut w = csv::Writer::from_writer(Vec::new());
+    w.write_record(header(fields))?;
+    Ok(w.into_inner()?)
+}
+
+pub fn csv_row(sample: &Sample, fields: &[CustomField]) -> Result<Vec<u8>> {
+    let mut w = csv::Writer::from_writer(Vec::new());
+    w.write_record(record(sample, fields).into_iter().map(escape_formula))?;
+    Ok(w.into_inner()?)
+}
+
+pub fn ndjson_row(sample: &Sample) -> Result<Vec<u8>> {
+    let mut line = serde_json::to_vec(sample)?;
+    line.push(b'\n');
+    Ok(line)
+}
+
+/// XLSX is a zip archive, so unlike CSV and NDJSON it has to be built in
+/// memory before it can be sent.
+pub async fn xlsx(state: &WebState, query: &SampleQuery, user_id: i64) -> Result<Vec<u8>> {
+    let fields = custom_field::get_fields(state).await?;
+    let mut rows = Box::pin(stream_samples(state, query, user_id)?);
+    let mut samples = Vec::new();
+    while let Some(sample) = rows.next().await {
+        samples.push(sample?);
+    }
+
+    tokio::task::spawn_blocking(move || {
+        let mut workbook = Workbook::new();
+        let sheet = workbook.add_worksheet();
+        sheet.set_name("samples")?;
+        // cells are written as text, which Excel never evaluates
+        for (col, name) in header(&fields).iter().enumerate() {
+            sheet.write_string(0, col as u16, name)?;
+        }
+        for (i, sample) in samples.iter().enumerate() {
+            let row = i as u32 + 1;
+            for (col, value) in record(sample, &fields).iter().enumerate() {
+                sheet.write_string(row, col as u16, value)?;
+            }
+        }
+        Ok(workbook.save_to_buffer()?)
+    })
+    .await?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{
+        custom_field::{CustomFieldInput, FieldType},
+        import::ImportFormat,
+        sample::{SampleSort, SampleStatus},
+    };
+    use crate::services::{import::run_import, sample::delete_sample_by_id};
+    use crate::testing;
+
+    async fn exported(state: &WebState, query: &SampleQuery, uid: i64) -> Vec<Sample> {
+        let rows = stream_samples(state, query, uid).unwrap();
+        rows.map(Result::unwrap).collect().await
+    }
+
+    fn csv(samples: &[Sample], fields: &[CustomField]) -> String {
+        let mut out = csv_header(fields).unwrap();
+        for sample in samples {
+            out.extend(csv_row(sample, fields).unwrap());
+        }
+        String::from_utf8(out).unwrap()
+    }
+
+    #[tokio::test]
+    async fn export_follows_the_list_filters_and_sort() {
+        let state = testing::state().await;
+        let uid = testing::user(&state.db, "owner@example.com").await;
+        testing::sample(&state, uid, "b").await;
+        let trashed = testing::sample(&state, uid, "c").await;
+        testing::sample(&state, uid, "a").await;
+        let mut active = testing::input("d");
+        active.status = SampleStatus::Active;
+        crate::services::sample::create_sample(&state, active, uid)
+            .await
+            .unwrap();
+        delete_sample_by_id(&state, trashed.id, None, uid)
+            .await
+            .unwrap();
+
+        let query = SampleQuery {
+            status: Some(SampleStatus::Draft),
+            sort: Some(SampleSort::Name),
+            order: Some(SortOrder::Asc),
+            limit: Some(1),
+            ..Default::default()
+        };
+        let names: Vec<String> = exported(&state, &query, uid)
+            .await
+            .into_iter()
+            .map(|s| s.name)
+            .collect();
+        assert_eq!(names, ["a", "b"]);
+    }
+
+    #[tokio::test]
+    async fn csv_export_imports_back() {
+        let state = testing::state().await;
+        let uid = testing::user(&state.db, "owner@example.com").await;
+        let mut dye = testing::input("Blue, \"deep\" dye");
+        dye.description = Some("line one\nline two".into());
+        dye.tags = Some(vec!["wool".into(), "blue".into()]);
+        crate::services::sample::create_sample(&state, dye, uid)
+            .await
+            .unwrap();
+        let samples = exported(&state, &SampleQuery::default(), uid).await;
+        let text = csv(&samples, &[]);
+        assert!(text.starts_with(&format!("{}\n", EXPORT_COLUMNS.join(","))));
+
+        let report = run_import(&state, ImportFormat::Csv, text.as_bytes(), false, uid)
+            .await
+            .unwrap();
+        assert!(report.committed, "{:?}", report.errors);
+        let all = exported(
+            &state,
+            &SampleQuery {
+                order: Some(SortOrder::Asc),
+                ..Default::default()
+            },
+            uid,
+        )
+        .await;
+        assert_eq!(all.len(), 2);
+        assert_eq!(all[1].name, all[0].name);
+        assert_eq!(all[1].description, all[0].description);
+        assert_eq!(all[1].tags.0, all[0].tags.0);
+    }
+
+    #[tokio::test]
+    async fn csv_has_custom_field_columns_and_defuses_formulas() {
+        let state = testing::state().await;
+        let uid = testing::user(&state.db, "owner@example.com").await;
+        for (key, field_type) in [("batch", FieldType::Integer), ("note", FieldType::Text)] {
+            let field = CustomFieldInput {
+                key: key.into(),
+                label: key.into(),
+                field_type,
+                required: false,
+                options: vec![],
+                pattern: None,
+                position: 0,
+            };
+            custom_field::create_field(&state, field, uid)
+                .await
+                .unwrap();
+        }
+        let mut dye = testing::input("=HYPERLINK(\"http://evil\")");
+        dye.description = Some("@SUM(A1)".into());
+        dye.custom_fields = Some(
+            serde_json::json!({"batch": "-3", "note": "+A1"})
+                .as_object()
+                .cloned()
+                .unwrap(),
+        );
+        crate::services::sample::create_sample(&state, dye, uid)
+            .await
+            .unwrap();
+        testing::sample(&state, uid, "Plain").await;
+
+        let fields = custom_field::get_fields(&state).await.unwrap();
+        let query = SampleQuery {
+            order: Some(SortOrder::Asc),
+            ..Default::default()
+        };
+        let text = csv(&exported(&state, &query, uid).await, &fields);
+        let mut lines = text.lines();
+        assert!(lines.next().unwrap().ends_with(",version,cf.batch,cf.note"));
+        let first = lines.next().unwrap();
+        assert!(first.contains(r#""'=HYPERLINK(""http://evil"")""#));
+        assert!(first.contains(",'@SUM(A1),"));
+        assert!(first.ends_with(",-3,'+A1"));
+        assert!(lines.next().unwrap().ends_with(",,"));
+
+        run_import(&state, ImportFormat::Csv, text.as_bytes(), false, uid)
+            .await
+            .unwrap();
+        let copy = &exported(&state, &query, uid).await[2];
+        assert_eq!(copy.name, "=HYPERLINK(\"http://evil\")");
+        assert_eq!(copy.description.as_deref(), Some("@SUM(A1)"));
+        assert_eq!(copy.custom_fields["batch"], -3);
+        assert_eq!(copy.custom_fields["note"], "+A1");
+    }
+
+    #[tokio::test]
+    async fn ndjson_and_xlsx_cover_every_row() {
+        let state = testing::state().await;
+        let uid = testing::user(&state.db, "owner@example.com").await;
+        let dye = testing::sample(&state, uid, "Blue dye").await;
+
+        let line = ndjson_row(&dye).unwrap();
+        assert_eq!(line.last(), Some(&b'\n'));
+        let parsed: serde_json::Value = serde_json::from_slice(&line).unwrap();
+        assert_eq!(parsed["name"], "Blue dye");
+
+        let book = xlsx(&state, &SampleQuery::default(), uid).await.unwrap();
+        assert!(book.starts_with(b"PK"));
+    }
+
+    #[tokio::test]
+    async fn bad_filters_fail_before_streaming() {
+        let state = testing::state().await;
+        let uid = testing::user(&state.db, "owner@example.com").await;
+        let query = SampleQuery {
+            created_after: Some("soon".into()),
+            ..Default::default()
+        };
+        assert!(stream_samples(&state, &query, uid).is_err());
+    }
+}
@@ -0,0 +1,118 @@
+use anyhow::{bail, Result};
+use serde::{de::DeserializeOwned, Serialize};
+use sha2::{Digest, Sha256};
+use sqlx::{SqliteConnection, SqlitePool};
+
+use crate::services::error::ServiceError;
+
+pub const MAX_KEY_LEN: usize = 255;
+
+/// How long a key is remembered, from `IDEMPOTENCY_TTL_HOURS`.
+pub fn ttl_hours() -> i64 {
+    std::env::var("IDEMPOTENCY_TTL_HOURS")
+        .ok()
+        .and_then(|h| h.parse().ok())
+        .filter(|h| *h > 0)
+        .unwrap_or(24)
+}
+
+fn cutoff() -> String {
+    format!("-{} hours", ttl_hours())
+}
+
+/// Trims a client-supplied key and checks it is usable.
+pub fn clean_key(key: &str) -> Result<&str> {
+    let key = key.trim();
+    if key.is_empty() || key.len() > MAX_KEY_LEN || !key.chars().all(|c| c.is_ascii_graphic()) {
+        bail!(ServiceError::Invalid(format!(
+            "idempotency key must be 1 to {MAX_KEY_LEN} printable ASCII characters"
+        )));
+    }
+    Ok(key)
+}
+
+/// Fingerprint of a request body, so a key reused for a different request is
+/// caught rather than answered with someone else's result.
+pub fn request_hash<T: Serialize>(request: &T) -> Result<String> {
+    let body = serde_json::to_vec(request)?;
+    Ok(hex::encode(Sha256::digest(body)))
+}
+
+/// The stored result for `key`, if it was used within the TTL. Fails when the
+/// key was used for a different request.
+///
+/// An expired entry is deleted first. Besides making room for `store`, that
+/// write makes SQLite take its write lock before the read, so concurrent
+/// requests with the same key wait their turn instead of failing as locked.
+pub(crate) async fn lookup<T: DeserializeOwned>(
+    conn: &mut SqliteConnection,
+    user_id: i64,
+    key: &str,
+    request_hash: &str,
+) -> Result<Option<T>> {
+    let cutoff = cutoff();
+    sqlx::query!(
+        "DELETE FROM idempotency_keys WHERE user_id = ? AND key = ? AND created_at < datetime('now', ?)",
+        user_id,
+        key,
+        cutoff
+    )
+    .execute(&mut *conn)
+    .await?;
+    let stored = sqlx::query!(
+        r#"
+            SELECT request_hash AS "request_hash!", response_json AS "response_json!"
+            FROM idempotency_keys
+            WHERE user_id = ? AND key = ? AND created_at >= datetime('now', ?)
+        "#,
+        user_id,
+        key,
+        cutoff
+    )
+    .fetch_optional(&mut *conn)
+    .await?;
+    match stored {
+        Some(row) if row.request_hash != request_hash => bail!(ServiceError::Invalid(
+            "idempotency key was already used for a different request".into()
+        )),
+        Some(row) => Ok(Some(serde_json::from_str(&row.response_json)?)),
+        None => Ok(None),
+    }
+}
+
+/// Remembers the result for `key` on the caller's connection. Call `lookup`
+/// on the same transaction first.
+pub(crate) async fn store<T: Serialize>(
+    conn: &mut SqliteConnection,
+    user_id: i64,
+    key: &str,
+    request_hash: &str,
+    response: &T,
+) -> Result<()> {
+    let response_json = serde_json::to_string(response)?;
+    sqlx::query!(
+        r#"
+            INSERT INTO idempotency_keys (user_id, key, request_hash, response_json)
+            VALUES (?, ?, ?, ?)
+        "#,
+        user_id,
+        key,
+        request_hash,
+        response_json
+    )
+    .execute(&mut *conn)
+    .await?;
+    Ok(())
+}
+
+/// Drops keys older than the TTL. Returns how many were removed.
+pub async fn purge_expired(db: &SqlitePool) -> Result<u64> {
+    let cutoff = cutoff();
+    let result = sqlx::query!(
+        "DELETE FROM idempotency_keys WHERE created_at < datetime('now', ?)",
+        cutoff
+    )
+    .execute(db)
+    .await?;
+    Ok(result.rows_affected())
+}
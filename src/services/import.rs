@@ -0,0 +1,334 @@
+use anyhow::{bail, Result};
+use serde::Deserialize;
+use serde_json::{Map, Value};
+
+use crate::models::{
+    import::{ImportFormat, ImportReport, ImportRowError},
+    sample::{SampleInput, SampleStatus},
+    state::WebState,
+};
+use crate::services::{
+    custom_field,
+    error::ServiceError,
+    export::FORMULA_TRIGGERS,
+    sample::{insert_sample, validate_input},
+};
+
+pub const MAX_IMPORT_ROWS: usize = 10_000;
+pub const MAX_IMPORT_BYTES: usize = 10 * 1024 * 1024;
+/// Header prefix for a custom field column, as in `cf.batch_no`.
+pub const CUSTOM_FIELD_PREFIX: &str = "cf.";
+
+/// A CSV row. Columns match `SampleInput`, with tags comma separated in one
+/// cell. Custom fields come from `cf.<key>` columns, see [`parse_csv`].
+#[derive(Debug, Deserialize)]
+struct CsvRow {
+    name: String,
+    #[serde(default)]
+    description: Option<String>,
+    status: String,
+    #[serde(default)]
+    tags: Option<String>,
+}
+
+impl TryFrom<CsvRow> for SampleInput {
+    type Error = String;
+
+    fn try_from(row: CsvRow) -> Result<Self, Self::Error> {
+        Ok(SampleInput {
+            name: row.name,
+            description: row.description.filter(|d| !d.is_empty()),
+            status: row.status.parse::<SampleStatus>()?,
+            tags: row
+                .tags
+                .map(|tags| tags.split(',').map(str::to_string).collect()),
+            version: None,
+            custom_fields: None,
+        })
+    }
+}
+
+type ParsedRow = (u64, Result<SampleInput, String>);
+
+/// Drops the `'` the CSV export puts before cells a spreadsheet would run as
+/// formulas, so an export imports back as it was.
+fn unescape_formula(cell: &str) -> &str {
+    match cell.strip_prefix('\'') {
+        Some(rest) if rest.starts_with(FORMULA_TRIGGERS) && rest.parse::<f64>().is_err() => rest,
+        _ => cell,
+    }
+}
+
+fn parse_csv(data: &[u8]) -> Result<Vec<ParsedRow>> {
+    let mut reader = csv::ReaderBuilder::new()
+        .trim(csv::Trim::All)
+        .from_reader(data);
+    let headers = reader
+        .headers()
+        .map_err(|e| ServiceError::Invalid(format!("invalid CSV header: {e}")))?
+        .clone();
+    let custom_columns: Vec<(usize, String)> = headers
+        .iter()
+        .enumerate()
+        .filter_map(|(i, h)| {
+            h.strip_prefix(CUSTOM_FIELD_PREFIX)
+                .map(|key| (i, key.to_string()))
+        })
+        .collect();
+
+    let mut rows = Vec::new();
+    for record in reader.records() {
+        let row = match record {
+            Ok(record) => {
+                let line = record.position().map(|p| p.line()).unwrap_or_default();
+                let record: csv::StringRecord = record.iter().map(unescape_formula).collect();
+                let input = record
+                    .deserialize::<CsvRow>(Some(&headers))
+                    .map_err(|e| e.to_string())
+                    .and_then(SampleInput::try_from)
+                    .map(|mut input| {
+                        if !custom_columns.is_empty() {
+                            // cells are text; the field definitions coerce them
+                            let values: Map<String, Value> = custom_columns
+                                .iter()
+                                .map(|(i, key)| {
+                                    let cell = record.get(*i).unwrap_or_default();
+                                    (key.clone(), Value::String(cell.to_string()))
+                                })
+                                .collect();
+                            input.custom_fields = Some(values);
+                        }
+                        input
+                    });
+                (line, input)
+            }
+            Err(e) => {
+                let line = e.position().map(|p| p.line()).unwrap_or_default();
+                (line, Err(e.to_string()))
+            }
+        };
+        rows.push(row);
+    }
+    Ok(rows)
+}
+
+fn parse_ndjson(data: &[u8]) -> Result<Vec<ParsedRow>> {
+    let text = std::str::from_utf8(data)
+        .map_err(|_| ServiceError::Invalid("NDJSON must be UTF-8".into()))?;
+    Ok(text
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(i, line)| {
+            let input = serde_json::from_str::<SampleInput>(line).map_err(|e| e.to_string());
+            (i as u64 + 1, input)
+        })
+        .collect())
+}
+
+/// Validates every row and, unless `dry_run` is set or a row failed, inserts
+/// them all as `uid` in one transaction. `SampleCreated` is published for
+/// each row after the commit.
+pub async fn run_import(
+    state: &WebState,
+    format: ImportFormat,
+    data: &[u8],
+    dry_run: bool,
+    uid: i64,
+) -> Result<ImportReport> {
+    let rows = match format {
+        ImportFormat::Csv => parse_csv(data)?,
+        ImportFormat::Ndjson => parse_ndjson(data)?,
+    };
+    if rows.len() > MAX_IMPORT_ROWS {
+        bail!(ServiceError::Invalid(format!(
+            "at most {MAX_IMPORT_ROWS} rows per import"
+        )));
+    }
+
+    let total = rows.len();
+    let mut errors = Vec::new();
+    let mut inputs = Vec::with_capacity(total);
+    let fields = custom_field::get_fields(state).await?;
+    for (line, parsed) in rows {
+        match parsed.and_then(|input| {
+            validate_input(&input)
+                .and_then(|_| {
+                    let values = input.custom_fields.clone().unwrap_or_default();
+                    custom_field::validate_values(&fields, &values)
+                })
+                .map(|_| input)
+                .map_err(|e| e.to_string())
+        }) {
+            Ok(input) => inputs.push(input),
+            Err(error) => errors.push(ImportRowError { line, error }),
+        }
+    }
+
+    let mut report = ImportReport {
+        dry_run,
+        committed: false,
+        total,
+        valid: inputs.len(),
+        errors,
+        created: Vec::new(),
+    };
+    if dry_run || !report.errors.is_empty() {
+        return Ok(report);
+    }
+
+    let mut tx = state.db.begin().await?;
+    let mut created = Vec::with_capacity(inputs.len());
+    for input in inputs {
+        created.push(insert_sample(&mut tx, input, uid).await?);
+    }
+    tx.commit().await?;
+
+    for sample in created {
+        report.created.push(sample.id);
+        if let Err(e) = state.events.sample_created(sample).await {
+            tracing::error!(?e, "failed to publish imported sample");
+        }
+    }
+    report.committed = true;
+
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::custom_field::{CustomFieldInput, FieldType};
+    use crate::services::custom_field;
+    use crate::testing;
+
+    const CSV: &str = "name,description,status,tags\n\
+        Blue dye,,draft,\"wool, blue\"\n\
+        Red dye,Bright,active,\n";
+
+    async fn names(state: &WebState) -> Vec<String> {
+        sqlx::query_scalar!("SELECT name FROM samples ORDER BY id")
+            .fetch_all(&state.db)
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn csv_rows_are_created_together() {
+        let state = testing::state().await;
+        let uid = testing::user(&state.db, "owner@example.com").await;
+
+        let report = run_import(&state, ImportFormat::Csv, CSV.as_bytes(), false, uid)
+            .await
+            .unwrap();
+        assert!(report.committed);
+        assert_eq!((report.total, report.valid), (2, 2));
+        assert_eq!(report.created.len(), 2);
+        assert_eq!(names(&state).await, ["Blue dye", "Red dye"]);
+        let tags = sqlx::query_scalar!(
+            "SELECT t.name FROM sample_tags st JOIN tags t ON t.id = st.tag_id ORDER BY t.name"
+        )
+        .fetch_all(&state.db)
+        .await
+        .unwrap();
+        assert_eq!(tags, ["blue", "wool"]);
+    }
+
+    #[tokio::test]
+    async fn dry_run_validates_without_writing() {
+        let state = testing::state().await;
+        let uid = testing::user(&state.db, "owner@example.com").await;
+
+        let report = run_import(&state, ImportFormat::Csv, CSV.as_bytes(), true, uid)
+            .await
+            .unwrap();
+        assert!(report.dry_run && !report.committed);
+        assert_eq!(report.valid, 2);
+        assert!(report.created.is_empty());
+        assert!(names(&state).await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn one_bad_row_fails_the_import_with_its_line() {
+        let state = testing::state().await;
+        let uid = testing::user(&state.db, "owner@example.com").await;
+        let csv = "name,status\nBlue dye,draft\nRed dye,finished\n ,active\n";
+
+        let report = run_import(&state, ImportFormat::Csv, csv.as_bytes(), false, uid)
+            .await
+            .unwrap();
+        assert!(!report.committed);
+        assert_eq!((report.total, report.valid), (3, 1));
+        let lines: Vec<u64> = report.errors.iter().map(|e| e.line).collect();
+        assert_eq!(lines, [3, 4]);
+        assert!(report.errors[1].error.contains("name is required"));
+        assert!(names(&state).await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn ndjson_skips_blank_lines_and_counts_from_one() {
+        let state = testing::state().await;
+        let uid = testing::user(&state.db, "owner@example.com").await;
+        let ndjson = "{\"name\":\"Blue dye\",\"status\":\"draft\",\"tags\":[\"wool\"]}\n\n{\"name\":\"Red dye\"}\n";
+
+        let report = run_import(&state, ImportFormat::Ndjson, ndjson.as_bytes(), false, uid)
+            .await
+            .unwrap();
+        assert_eq!(report.total, 2);
+        assert_eq!(report.errors.len(), 1);
+        assert_eq!(report.errors[0].line, 3);
+
+        let e = run_import(&state, ImportFormat::Ndjson, b"\xff\n", false, uid)
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            e.downcast_ref::<ServiceError>(),
+            Some(ServiceError::Invalid(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn cf_columns_fill_custom_fields() {
+        let state = testing::state().await;
+        let uid = testing::user(&state.db, "owner@example.com").await;
+        let field = CustomFieldInput {
+            key: "batch".into(),
+            label: "Batch".into(),
+            field_type: FieldType::Integer,
+            required: false,
+            options: vec![],
+            pattern: None,
+            position: 0,
+        };
+        custom_field::create_field(&state, field, uid)
+            .await
+            .unwrap();
+
+        let csv = "name,status,cf.batch\nBlue dye,draft,12\nRed dye,draft,twelve\n";
+        let report = run_import(&state, ImportFormat::Csv, csv.as_bytes(), true, uid)
+            .await
+            .unwrap();
+        assert_eq!(report.valid, 1);
+        assert_eq!(report.errors[0].line, 3);
+
+        let csv = "name,status,cf.batch\nBlue dye,draft,12\n";
+        run_import(&state, ImportFormat::Csv, csv.as_bytes(), false, uid)
+            .await
+            .unwrap();
+        let stored = sqlx::query_scalar!("SELECT custom_fields FROM samples")
+            .fetch_one(&state.db)
+            .await
+            .unwrap();
+        assert_eq!(stored, r#"{"batch":12}"#);
+    }
+
+    #[test]
+    fn format_is_detected_from_type_or_name() {
+        assert_eq!(ImportFormat::detect("text/csv"), Some(ImportFormat::Csv));
+        assert_eq!(
+            ImportFormat::detect("samples.JSONL"),
+            Some(ImportFormat::Ndjson)
+        );
+        assert_eq!(ImportFormat::detect("application/pdf"), None);
+    }
+}
@@ -1 +1,20 @@
+pub mod account;
+pub mod api_token;
+pub mod attachment;
+pub mod audit;
+pub mod bulk;
+pub mod comment;
+pub mod custom_field;
+pub mod error;
+pub mod export;
+pub mod idempotency;
+pub mod import;
+pub mod notification;
+pub mod revision;
 pub mod sample;
+pub mod sample_template;
+pub mod saved_view;
+pub mod schedule;
+pub mod setting;
+pub mod share;
+pub mod stats;
@@ -0,0 +1,330 @@
+use anyhow::{bail, Result};
+use sqlx::{QueryBuilder, Sqlite, SqliteConnection};
+
+use crate::models::{
+    notification::{
+        Notification, NotificationKind, NotificationPage, NotificationQuery, WatchStatus,
+    },
+    share::SampleRole,
+    state::WebState,
+};
+use crate::services::{error::ServiceError, sample::check_access};
+
+pub const DEFAULT_PAGE_SIZE: i64 = 50;
+pub const MAX_PAGE_SIZE: i64 = 500;
+
+pub async fn is_watching(state: &WebState, sample_id: i64, user_id: i64) -> Result<bool> {
+    let watching = sqlx::query_scalar!(
+        r#"SELECT EXISTS (SELECT 1 FROM sample_watches WHERE sample_id = ? AND user_id = ?) AS "watching!: bool""#,
+        sample_id,
+        user_id
+    )
+    .fetch_one(&state.db)
+    .await?;
+    Ok(watching)
+}
+
+pub async fn watch_status(state: &WebState, sample_id: i64, user_id: i64) -> Result<WatchStatus> {
+    let mut conn = state.db.acquire().await?;
+    check_access(&mut conn, sample_id, user_id, SampleRole::Viewer).await?;
+    Ok(WatchStatus {
+        sample_id,
+        watching: is_watching(state, sample_id, user_id).await?,
+    })
+}
+
+/// Adds a watch on the caller's connection. Watching twice is a no-op.
+pub(crate) async fn add_watch(
+    conn: &mut SqliteConnection,
+    sample_id: i64,
+    user_id: i64,
+) -> Result<()> {
+    sqlx::query!(
+        "INSERT OR IGNORE INTO sample_watches (sample_id, user_id) VALUES (?, ?)",
+        sample_id,
+        user_id
+    )
+    .execute(conn)
+    .await?;
+    Ok(())
+}
+
+/// Anyone who can see a sample can watch it.
+pub async fn watch_sample(state: &WebState, sample_id: i64, user_id: i64) -> Result<WatchStatus> {
+    let mut conn = state.db.acquire().await?;
+    check_access(&mut conn, sample_id, user_id, SampleRole::Viewer).await?;
+    add_watch(&mut conn, sample_id, user_id).await?;
+    Ok(WatchStatus {
+        sample_id,
+        watching: true,
+    })
+}
+
+/// Stops watching. Needs no access, so a user who lost theirs can still
+/// clean up.
+pub async fn unwatch_sample(state: &WebState, sample_id: i64, user_id: i64) -> Result<WatchStatus> {
+    sqlx::query!(
+        "DELETE FROM sample_watches WHERE sample_id = ? AND user_id = ?",
+        sample_id,
+        user_id
+    )
+    .execute(&state.db)
+    .await?;
+    Ok(WatchStatus {
+        sample_id,
+        watching: false,
+    })
+}
+
+/// Tells everyone watching the sample, except the actor, that `actor_id`
+/// `verb` it, e.g. "ann@example.com updated “Blue dye”". Watchers who have
+/// since lost access to the sample hear nothing. Runs on the caller's
+/// connection so it commits with the change.
+pub(crate) async fn notify_watchers(
+    conn: &mut SqliteConnection,
+    sample_id: i64,
+    actor_id: i64,
+    kind: NotificationKind,
+    verb: &str,
+    sample_name: &str,
+) -> Result<()> {
+    let actor = sqlx::query_scalar!("SELECT email FROM users WHERE id = ?", actor_id)
+        .fetch_optional(&mut *conn)
+        .await?
+        .unwrap_or_else(|| "someone".to_string());
+    let message = format!("{actor} {verb} “{sample_name}”");
+    sqlx::query!(
+        r#"
+            INSERT INTO notifications (user_id, sample_id, actor_id, kind, message)
+            SELECT w.user_id, w.sample_id, ?, ?, ?
+            FROM sample_watches w
+            JOIN sample_acl acl ON acl.sample_id = w.sample_id AND acl.user_id = w.user_id
+            WHERE w.sample_id = ? AND w.user_id != ?
+        "#,
+        actor_id,
+        kind,
+        message,
+        sample_id,
+        actor_id
+    )
+    .execute(conn)
+    .await?;
+    Ok(())
+}
+
+pub async fn unread_count(state: &WebState, user_id: i64) -> Result<i64> {
+    let count = sqlx::query_scalar!(
+        r#"SELECT COUNT(1) AS "count!: i64" FROM notifications WHERE user_id = ? AND read_at IS NULL"#,
+        user_id
+    )
+    .fetch_one(&state.db)
+    .await?;
+    Ok(count)
+}
+
+pub async fn get_notifications(
+    state: &WebState,
+    query: &NotificationQuery,
+    user_id: i64,
+) -> Result<NotificationPage> {
+    let limit = query
+        .limit
+        .unwrap_or(DEFAULT_PAGE_SIZE)
+        .clamp(1, MAX_PAGE_SIZE);
+
+    let mut qb: QueryBuilder<Sqlite> = QueryBuilder::new(
+        "SELECT id, user_id, sample_id, actor_id, kind, message, read_at, created_at \
+         FROM notifications WHERE user_id = ",
+    );
+    qb.push_bind(user_id);
+    if query.unread == Some(true) {
+        qb.push(" AND read_at IS NULL");
+    }
+    if let Some(before_id) = query.before_id {
+        qb.push(" AND id < ").push_bind(before_id);
+    }
+    // one extra row tells us whether there is another page
+    qb.push(" ORDER BY id DESC LIMIT ").push_bind(limit + 1);
+
+    let mut items: Vec<Notification> = qb.build_query_as().fetch_all(&state.db).await?;
+    let next_before_id = if items.len() as i64 > limit {
+        items.truncate(limit as usize);
+        items.last().map(|n| n.id)
+    } else {
+        None
+    };
+    Ok(NotificationPage {
+        items,
+        unread: unread_count(state, user_id).await?,
+        next_before_id,
+    })
+}
+
+/// Marks one of the user's notifications read. Another user's reads as not
+/// found.
+pub async fn mark_read(state: &WebState, id: i64, user_id: i64) -> Result<Notification> {
+    let notification = sqlx::query_as!(
+        Notification,
+        r#"
+            UPDATE notifications SET read_at = COALESCE(read_at, datetime('now'))
+            WHERE id = ? AND user_id = ?
+            RETURNING
+                id         AS "id!",
+                user_id    AS "user_id!",
+                sample_id  AS "sample_id?",
+                actor_id   AS "actor_id?",
+                kind       AS "kind!: NotificationKind",
+                message    AS "message!",
+                read_at    AS "read_at?",
+                created_at AS "created_at!"
+        "#,
+        id,
+        user_id
+    )
+    .fetch_optional(&state.db)
+    .await?;
+    match notification {
+        Some(notification) => Ok(notification),
+        None => bail!(ServiceError::NotFound("notification", id)),
+    }
+}
+
+/// Marks all the user's notifications read. Returns how many were unread.
+pub async fn mark_all_read(state: &WebState, user_id: i64) -> Result<u64> {
+    let result = sqlx::query!(
+        "UPDATE notifications SET read_at = datetime('now') WHERE user_id = ? AND read_at IS NULL",
+        user_id
+    )
+    .execute(&state.db)
+    .await?;
+    Ok(result.rows_affected())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::{
+        error::status_code,
+        sample::{clone_sample, delete_sample_by_id, restore_sample_by_id, update_sample_by_id},
+        share::unshare_sample,
+    };
+    use crate::testing;
+    use axum::http::StatusCode;
+
+    async fn inbox(state: &WebState, user_id: i64) -> Vec<(NotificationKind, String)> {
+        get_notifications(state, &NotificationQuery::default(), user_id)
+            .await
+            .unwrap()
+            .items
+            .into_iter()
+            .rev()
+            .map(|n| (n.kind, n.message))
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn watchers_hear_about_changes_by_others() {
+        let state = testing::state().await;
+        let ann = testing::user(&state.db, "ann@example.com").await;
+        let bob = testing::user(&state.db, "bob@example.com").await;
+        let dye = testing::sample(&state, ann, "Blue dye").await;
+        testing::share(&state, dye.id, ann, "bob@example.com", SampleRole::Editor).await;
+        assert!(is_watching(&state, dye.id, ann).await.unwrap());
+        assert!(!is_watching(&state, dye.id, bob).await.unwrap());
+
+        watch_sample(&state, dye.id, bob).await.unwrap();
+        update_sample_by_id(&state, testing::input("Red dye"), dye.id, bob)
+            .await
+            .unwrap();
+        clone_sample(&state, dye.id, bob).await.unwrap();
+        delete_sample_by_id(&state, dye.id, None, ann)
+            .await
+            .unwrap();
+        restore_sample_by_id(&state, dye.id, ann).await.unwrap();
+
+        assert_eq!(
+            inbox(&state, ann).await,
+            [
+                (
+                    NotificationKind::Updated,
+                    "bob@example.com updated “Red dye”".to_string()
+                ),
+                (
+                    NotificationKind::Copied,
+                    "bob@example.com copied “Red dye”".to_string()
+                ),
+            ]
+        );
+        assert_eq!(
+            inbox(&state, bob).await,
+            [
+                (
+                    NotificationKind::Deleted,
+                    "ann@example.com deleted “Red dye”".to_string()
+                ),
+                (
+                    NotificationKind::Restored,
+                    "ann@example.com restored “Red dye”".to_string()
+                ),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn watching_needs_access_but_unwatching_does_not() {
+        let state = testing::state().await;
+        let ann = testing::user(&state.db, "ann@example.com").await;
+        let bob = testing::user(&state.db, "bob@example.com").await;
+        let dye = testing::sample(&state, ann, "Blue dye").await;
+
+        let err = watch_sample(&state, dye.id, bob).await.unwrap_err();
+        assert_eq!(status_code(&err), StatusCode::NOT_FOUND);
+        testing::share(&state, dye.id, ann, "bob@example.com", SampleRole::Viewer).await;
+        watch_sample(&state, dye.id, bob).await.unwrap();
+        watch_sample(&state, dye.id, bob).await.unwrap();
+
+        // a watcher who loses access stops hearing about the sample
+        unshare_sample(&state, dye.id, bob, ann).await.unwrap();
+        update_sample_by_id(&state, testing::input("Red dye"), dye.id, ann)
+            .await
+            .unwrap();
+        assert!(inbox(&state, bob).await.is_empty());
+        assert!(!unwatch_sample(&state, dye.id, bob).await.unwrap().watching);
+        assert!(!is_watching(&state, dye.id, bob).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn notifications_are_read_one_by_one_or_all_at_once() {
+        let state = testing::state().await;
+        let ann = testing::user(&state.db, "ann@example.com").await;
+        let bob = testing::user(&state.db, "bob@example.com").await;
+        let dye = testing::sample(&state, ann, "Blue dye").await;
+        testing::share(&state, dye.id, ann, "bob@example.com", SampleRole::Editor).await;
+        for name in ["a", "b", "c"] {
+            update_sample_by_id(&state, testing::input(name), dye.id, bob)
+                .await
+                .unwrap();
+        }
+
+        let page = get_notifications(&state, &NotificationQuery::default(), ann)
+            .await
+            .unwrap();
+        assert_eq!((page.items.len(), page.unread), (3, 3));
+        let newest = page.items[0].id;
+        let err = mark_read(&state, newest, bob).await.unwrap_err();
+        assert_eq!(status_code(&err), StatusCode::NOT_FOUND);
+        assert!(mark_read(&state, newest, ann).await.unwrap().is_read());
+
+        let unread = NotificationQuery {
+            unread: Some(true),
+            limit: Some(1),
+            ..Default::default()
+        };
+        let page = get_notifications(&state, &unread, ann).await.unwrap();
+        assert_eq!(page.unread, 2);
+        assert!(page.items[0].id < newest);
+        assert!(page.next_before_id.is_some());
+        assert_eq!(mark_all_read(&state, ann).await.unwrap(), 2);
+        assert_eq!(unread_count(&state, ann).await.unwrap(), 0);
+    }
+}
@@ -0,0 +1,435 @@
+use anyhow::Result;
+use serde_json::{Map, Value};
+use sqlx::{types::Json, SqliteConnection};
+use std::collections::BTreeSet;
+
+use crate::models::{
+    custom_field::CustomField,
+    revision::{FieldChange, RevisionDiff, SampleRevision},
+    sample::{form_value, Sample, SampleInput, SampleStatus},
+    share::SampleRole,
+    state::WebState,
+};
+use crate::services::{
+    custom_field,
+    error::ServiceError,
+    sample::{apply_update, authorize, UpdateKind},
+};
+
+/// Appends the current state of `sample` as its next revision. Runs on the
+/// caller's connection so it commits or rolls back with the write itself.
+pub(crate) async fn record_revision(
+    conn: &mut SqliteConnection,
+    sample: &Sample,
+    actor_id: i64,
+    restored_from: Option<i64>,
+) -> Result<()> {
+    sqlx::query!(
+        r#"
+            INSERT INTO sample_revisions
+                (sample_id, revision, name, description, status, tags, custom_fields,
+                 actor_id, restored_from)
+            SELECT ?, COALESCE(MAX(revision), 0) + 1, ?, ?, ?, ?, ?, ?, ?
+            FROM sample_revisions WHERE sample_id = ?
+        "#,
+        sample.id,
+        sample.name,
+        sample.description,
+        sample.status,
+        sample.tags,
+        sample.custom_fields,
+        actor_id,
+        restored_from,
+        sample.id
+    )
+    .execute(conn)
+    .await?;
+    Ok(())
+}
+
+pub async fn get_revisions(state: &WebState, sample_id: i64) -> Result<Vec<SampleRevision>> {
+    let revisions = sqlx::query_as!(
+        SampleRevision,
+        r#"
+            SELECT
+                r.id            AS "id!",
+                r.sample_id     AS "sample_id!",
+                r.revision      AS "revision!",
+                r.name          AS "name!",
+                r.description   AS "description?",
+                r.status        AS "status!: SampleStatus",
+                r.tags          AS "tags?: Json<Vec<String>>",
+                r.custom_fields AS "custom_fields?: Json<Map<String, Value>>",
+                r.actor_id      AS "actor_id!",
+                u.email         AS "actor_email!",
+                r.restored_from AS "restored_from?",
+                r.created_at    AS "created_at!"
+            FROM sample_revisions r
+            JOIN users u ON u.id = r.actor_id
+            WHERE r.sample_id = ?
+            ORDER BY r.revision DESC
+        "#,
+        sample_id
+    )
+    .fetch_all(&state.db)
+    .await?;
+    Ok(revisions)
+}
+
+pub async fn get_revision(
+    state: &WebState,
+    sample_id: i64,
+    revision: i64,
+) -> Result<Option<SampleRevision>> {
+    let revision = sqlx::query_as!(
+        SampleRevision,
+        r#"
+            SELECT
+                r.id            AS "id!",
+                r.sample_id     AS "sample_id!",
+                r.revision      AS "revision!",
+                r.name          AS "name!",
+                r.description   AS "description?",
+                r.status        AS "status!: SampleStatus",
+                r.tags          AS "tags?: Json<Vec<String>>",
+                r.custom_fields AS "custom_fields?: Json<Map<String, Value>>",
+                r.actor_id      AS "actor_id!",
+                u.email         AS "actor_email!",
+                r.restored_from AS "restored_from?",
+                r.created_at    AS "created_at!"
+            FROM sample_revisions r
+            JOIN users u ON u.id = r.actor_id
+            WHERE r.sample_id = ? AND r.revision = ?
+        "#,
+        sample_id,
+        revision
+    )
+    .fetch_optional(&state.db)
+    .await?;
+    Ok(revision)
+}
+
+fn field_changes(from: &SampleRevision, to: &SampleRevision) -> Vec<FieldChange> {
+    let fields = [
+        ("name", Some(from.name.clone()), Some(to.name.clone())),
+        (
+            "description",
+            from.description.clone(),
+            to.description.clone(),
+        ),
+        (
+            "status",
+            Some(from.status.to_string()),
+            Some(to.status.to_string()),
+        ),
+    ];
+    let mut changes: Vec<FieldChange> = fields
+        .into_iter()
+        .filter(|(_, a, b)| a != b)
+        .map(|(field, from, to)| FieldChange {
+            field: field.to_string(),
+            from,
+            to,
+        })
+        .collect();
+    // only comparable when both revisions recorded their tags
+    if let (Some(a), Some(b)) = (&from.tags, &to.tags) {
+        if a.0 != b.0 {
+            changes.push(FieldChange {
+                field: "tags".to_string(),
+                from: Some(a.join(", ")),
+                to: Some(b.join(", ")),
+            });
+        }
+    }
+    if let (Some(a), Some(b)) = (&from.custom_fields, &to.custom_fields) {
+        let keys: BTreeSet<&String> = a.keys().chain(b.keys()).collect();
+        for key in keys {
+            let (from, to) = (a.get(key).map(form_value), b.get(key).map(form_value));
+            if from != to {
+                changes.push(FieldChange {
+                    field: format!("cf.{key}"),
+                    from,
+                    to,
+                });
+            }
+        }
+    }
+    changes
+}
+
+/// The custom values to restore from `old`. Values of fields deleted since
+/// are dropped, and a required field the old revision had no value for keeps
+/// its current one, so the restore passes today's validation.
+fn restored_custom_fields(
+    fields: &[CustomField],
+    old: &Map<String, Value>,
+    current: &Map<String, Value>,
+) -> Map<String, Value> {
+    fields
+        .iter()
+        .filter_map(|field| {
+            let value = old
+                .get(&field.key)
+                .or_else(|| current.get(&field.key).filter(|_| field.required))?;
+            Some((field.key.clone(), value.clone()))
+        })
+        .collect()
+}
+
+pub async fn diff_revisions(
+    state: &WebState,
+    sample_id: i64,
+    from: i64,
+    to: i64,
+) -> Result<Option<RevisionDiff>> {
+    let from = get_revision(state, sample_id, from).await?;
+    let to = get_revision(state, sample_id, to).await?;
+    Ok(from.zip(to).map(|(from, to)| RevisionDiff {
+        changes: field_changes(&from, &to),
+        from,
+        to,
+    }))
+}
+
+/// Restores an old revision by applying it as a regular update, so it gets a
+/// new revision of its own and publishes `SampleUpdated`. Revisions from
+/// before tags or custom values were recorded leave the current ones alone.
+pub async fn restore_revision(
+    state: &WebState,
+    sample_id: i64,
+    revision: i64,
+    actor_id: i64,
+) -> Result<Sample> {
+    let current = authorize(state, sample_id, actor_id, SampleRole::Editor).await?;
+    let old = get_revision(state, sample_id, revision)
+        .await?
+        .ok_or(ServiceError::NotFound("revision", revision))?;
+    let custom_fields = match old.custom_fields {
+        Some(values) => {
+            let fields = custom_field::get_fields(state).await?;
+            Some(restored_custom_fields(
+                &fields,
+                &values,
+                &current.custom_fields,
+            ))
+        }
+        None => None,
+    };
+    let input = SampleInput {
+        name: old.name,
+        description: old.description,
+        status: old.status,
+        tags: old.tags.map(|tags| tags.0),
+        version: None,
+        custom_fields,
+    };
+    apply_update(
+        state,
+        input,
+        sample_id,
+        actor_id,
+        UpdateKind::Restore(revision),
+    )
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::custom_field::{CustomFieldInput, FieldType};
+    use crate::services::sample::{create_sample, update_sample_by_id};
+    use crate::testing;
+
+    #[tokio::test]
+    async fn every_write_is_a_revision() {
+        let state = testing::state().await;
+        let alice = testing::user(&state.db, "alice@example.com").await;
+        let bob = testing::user(&state.db, "bob@example.com").await;
+        let sample = testing::sample(&state, alice, "Blue dye").await;
+        testing::share(
+            &state,
+            sample.id,
+            alice,
+            "bob@example.com",
+            SampleRole::Editor,
+        )
+        .await;
+        update_sample_by_id(&state, testing::input("Red dye"), sample.id, bob)
+            .await
+            .unwrap();
+
+        let revisions = get_revisions(&state, sample.id).await.unwrap();
+        let summary: Vec<_> = revisions
+            .iter()
+            .map(|r| (r.revision, r.name.as_str(), r.actor_email.as_str()))
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                (2, "Red dye", "bob@example.com"),
+                (1, "Blue dye", "alice@example.com")
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn diff_lists_only_changed_fields() {
+        let state = testing::state().await;
+        let uid = testing::user(&state.db, "owner@example.com").await;
+        let sample = testing::sample(&state, uid, "Blue dye").await;
+        let mut edit = testing::input("Blue dye");
+        edit.status = SampleStatus::Active;
+        update_sample_by_id(&state, edit, sample.id, uid)
+            .await
+            .unwrap();
+
+        let diff = diff_revisions(&state, sample.id, 1, 2)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(diff.changes.len(), 1);
+        assert_eq!(diff.changes[0].field, "status");
+        assert_eq!(diff.changes[0].from.as_deref(), Some("draft"));
+        assert_eq!(diff.changes[0].to.as_deref(), Some("active"));
+
+        assert!(diff_revisions(&state, sample.id, 1, 9)
+            .await
+            .unwrap()
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn restore_adds_a_revision_with_the_old_values() {
+        let state = testing::state().await;
+        let uid = testing::user(&state.db, "owner@example.com").await;
+        let sample = testing::sample(&state, uid, "Blue dye").await;
+        update_sample_by_id(&state, testing::input("Red dye"), sample.id, uid)
+            .await
+            .unwrap();
+
+        let restored = restore_revision(&state, sample.id, 1, uid).await.unwrap();
+        assert_eq!(restored.name, "Blue dye");
+        let latest = get_revision(&state, sample.id, 3).await.unwrap().unwrap();
+        assert_eq!(latest.name, "Blue dye");
+        assert_eq!(latest.restored_from, Some(1));
+
+        assert!(restore_revision(&state, sample.id, 9, uid).await.is_err());
+        assert_eq!(get_revisions(&state, sample.id).await.unwrap().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn revisions_carry_tags_into_diffs_and_restores() {
+        let state = testing::state().await;
+        let uid = testing::user(&state.db, "owner@example.com").await;
+        let mut tagged = testing::input("Blue dye");
+        tagged.tags = Some(vec!["wool".into(), "blue".into()]);
+        let sample = create_sample(&state, tagged, uid).await.unwrap();
+        let mut retagged = testing::input("Blue dye");
+        retagged.tags = Some(vec!["cotton".into()]);
+        update_sample_by_id(&state, retagged, sample.id, uid)
+            .await
+            .unwrap();
+
+        let diff = diff_revisions(&state, sample.id, 1, 2)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(diff.changes.len(), 1);
+        assert_eq!(diff.changes[0].field, "tags");
+        assert_eq!(diff.changes[0].from.as_deref(), Some("blue, wool"));
+        assert_eq!(diff.changes[0].to.as_deref(), Some("cotton"));
+
+        let restored = restore_revision(&state, sample.id, 1, uid).await.unwrap();
+        assert_eq!(restored.tags.0, ["blue", "wool"]);
+    }
+
+    #[tokio::test]
+    async fn revisions_without_tags_leave_them_alone() {
+        let state = testing::state().await;
+        let uid = testing::user(&state.db, "owner@example.com").await;
+        let sample = testing::sample(&state, uid, "Blue dye").await;
+        // as if recorded before tags were
+        sqlx::query!("UPDATE sample_revisions SET tags = NULL")
+            .execute(&state.db)
+            .await
+            .unwrap();
+        let mut tagged = testing::input("Red dye");
+        tagged.tags = Some(vec!["wool".into()]);
+        update_sample_by_id(&state, tagged, sample.id, uid)
+            .await
+            .unwrap();
+
+        let diff = diff_revisions(&state, sample.id, 1, 2)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(diff.changes.iter().all(|c| c.field != "tags"));
+        let restored = restore_revision(&state, sample.id, 1, uid).await.unwrap();
+        assert_eq!(restored.name, "Blue dye");
+        assert_eq!(restored.tags.0, ["wool"]);
+    }
+
+    #[tokio::test]
+    async fn revisions_carry_custom_values_into_diffs_and_restores() {
+        let state = testing::state().await;
+        let uid = testing::user(&state.db, "owner@example.com").await;
+        for key in ["batch", "retired"] {
+            let field = CustomFieldInput {
+                key: key.into(),
+                label: key.into(),
+                field_type: FieldType::Text,
+                required: false,
+                options: vec![],
+                pattern: None,
+                position: 0,
+            };
+            custom_field::create_field(&state, field, uid)
+                .await
+                .unwrap();
+        }
+        let mut first = testing::input("Blue dye");
+        first.custom_fields = Some(Map::from_iter([
+            ("batch".to_string(), Value::from("A1")),
+            ("retired".to_string(), Value::from("old")),
+        ]));
+        let sample = create_sample(&state, first, uid).await.unwrap();
+        let mut second = testing::input("Blue dye");
+        second.custom_fields = Some(Map::from_iter([("batch".to_string(), Value::from("B2"))]));
+        update_sample_by_id(&state, second, sample.id, uid)
+            .await
+            .unwrap();
+
+        let diff = diff_revisions(&state, sample.id, 1, 2)
+            .await
+            .unwrap()
+            .unwrap();
+        let changes: Vec<_> = diff
+            .changes
+            .iter()
+            .map(|c| (c.field.as_str(), c.from.as_deref(), c.to.as_deref()))
+            .collect();
+        assert_eq!(
+            changes,
+            [
+                ("cf.batch", Some("A1"), Some("B2")),
+                ("cf.retired", Some("old"), None)
+            ]
+        );
+
+        let retired = custom_field::get_fields(&state)
+            .await
+            .unwrap()
+            .into_iter()
+            .find(|f| f.key == "retired")
+            .unwrap();
+        custom_field::delete_field(&state, retired.id, uid)
+            .await
+            .unwrap();
+        let restored = restore_revision(&state, sample.id, 1, uid).await.unwrap();
+        assert_eq!(
+            restored.custom_fields.0,
+            Map::from_iter([("batch".to_string(), Value::from("A1"))])
+        );
+    }
+}
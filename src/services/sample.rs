@@ -1,67 +1,643 @@
-use anyhow::Result;
+use anyhow::{bail, Result};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine as _};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use sqlx::{types::Json, QueryBuilder, Sqlite, SqliteConnection, SqlitePool};
 use tracing::error;
 
 use crate::models::{
-    sample::{Sample, SampleInput},
+    notification::NotificationKind,
+    sample::{
+        Sample, SampleInput, SamplePage, SampleQuery, SampleSearchHit, SampleSearchQuery,
+        SampleSort, SampleStatus, SortOrder,
+    },
+    share::SampleRole,
     state::WebState,
 };
+use crate::services::{
+    audit::{self, snapshot},
+    custom_field,
+    error::ServiceError,
+    idempotency,
+    notification::{add_watch, notify_watchers},
+    revision,
+};
 
-pub async fn create_sample(state: &WebState, input: SampleInput, uid: i64) -> Result<Sample> {
+/// Checks the parts of an input the schema doesn't. Status is already typed.
+pub fn validate_input(input: &SampleInput) -> Result<()> {
+    let name = input.name.trim();
+    if name.is_empty() {
+        bail!(ServiceError::Invalid("name is required".into()));
+    }
+    if name.chars().count() > MAX_NAME_LEN {
+        bail!(ServiceError::Invalid(format!(
+            "name is longer than {MAX_NAME_LEN} characters"
+        )));
+    }
+    Ok(())
+}
+
+/// Tags are trimmed, de-duplicated case-insensitively and capped in length.
+pub(crate) fn normalize_tags(tags: &[String]) -> Vec<String> {
+    let mut out: Vec<String> = Vec::new();
+    for tag in tags {
+        let tag: String = tag.trim().chars().take(MAX_TAG_LEN).collect();
+        if !tag.is_empty() && !out.iter().any(|t| t.eq_ignore_ascii_case(&tag)) {
+            out.push(tag);
+        }
+    }
+    out
+}
+
+/// Replaces the tags on a sample, creating any tag names that don't exist yet.
+async fn set_tags(conn: &mut SqliteConnection, sample_id: i64, tags: &[String]) -> Result<()> {
+    sqlx::query!("DELETE FROM sample_tags WHERE sample_id = ?", sample_id)
+        .execute(&mut *conn)
+        .await?;
+
+    for tag in normalize_tags(tags) {
+        sqlx::query!(
+            "INSERT INTO tags (name) VALUES (?) ON CONFLICT(name) DO NOTHING",
+            tag
+        )
+        .execute(&mut *conn)
+        .await?;
+        sqlx::query!(
+            "INSERT INTO sample_tags (sample_id, tag_id) SELECT ?, id FROM tags WHERE name = ?",
+            sample_id,
+            tag
+        )
+        .execute(&mut *conn)
+        .await?;
+    }
+    Ok(())
+}
+
+/// Loads a sample with its tags, including trashed ones. Used after writes so
+/// the returned and published sample always has the same shape as a read.
+async fn fetch_sample(conn: &mut SqliteConnection, id: i64) -> Result<Sample> {
     let sample = sqlx::query_as!(
         Sample,
         r#"
-            INSERT INTO samples (name, description, status, created_by)
-            VALUES (?, ?, ?, ?)
-            RETURNING
-                id          AS "id!",
-                name        AS "name!",
-                description AS "description?",   -- nullable
-                status      AS "status!",
-                created_at  AS "created_at!",
-                updated_at  AS "updated_at!",
-                created_by  AS "created_by!"
-            "#,
-        input.name,
-        input.description,
-        input.status,
-        uid
+            SELECT
+                s.id          AS "id!",
+                s.name        AS "name!",
+                s.description AS "description?",
+                s.status      AS "status!: SampleStatus",
+                s.created_at  AS "created_at!",
+                s.updated_at  AS "updated_at?",
+                s.created_by  AS "created_by!",
+                s.deleted_at  AS "deleted_at?",
+                s.version     AS "version!",
+                (SELECT json_group_array(t.name ORDER BY t.name)
+                 FROM sample_tags st JOIN tags t ON t.id = st.tag_id
+                 WHERE st.sample_id = s.id) AS "tags!: Json<Vec<String>>",
+                s.custom_fields AS "custom_fields!: Json<Map<String, Value>>"
+            FROM samples s
+            WHERE s.id = ?
+        "#,
+        id
     )
-    .fetch_one(&state.db)
-    .await
-    .unwrap();
+    .fetch_one(conn)
+    .await?;
+    Ok(sample)
+}
+
+pub async fn create_sample(state: &WebState, input: SampleInput, uid: i64) -> Result<Sample> {
+    let mut tx = state.db.begin().await?;
+    let sample = insert_sample(&mut tx, input, uid).await?;
+    tx.commit().await?;
 
     state.events.sample_created(sample.clone()).await.ok();
 
     Ok(sample)
 }
 
-pub async fn get_samples(state: &WebState) -> Vec<Sample> {
-    sqlx::query_as!(Sample, r#"SELECT * FROM samples ORDER BY id DESC"#)
-        .fetch_all(&state.db)
-        .await
-        .unwrap_or(vec![])
+/// Creates a sample at most once per idempotency `key`. A repeat of the same
+/// request within the TTL returns the sample as it was first created, with
+/// `true` to say it was a replay, and publishes nothing.
+pub async fn create_sample_once(
+    state: &WebState,
+    input: SampleInput,
+    uid: i64,
+    key: &str,
+) -> Result<(Sample, bool)> {
+    let key = idempotency::clean_key(key)?;
+    let hash = idempotency::request_hash(&input)?;
+    let (sample, replayed) = match try_create_once(state, input, uid, key, &hash).await {
+        Ok(created) => created,
+        Err(e) => {
+            // a concurrent request with the same key may have got there first
+            let mut conn = state.db.acquire().await?;
+            match idempotency::lookup(&mut conn, uid, key, &hash).await? {
+                Some(sample) => (sample, true),
+                None => return Err(e),
+            }
+        }
+    };
+    if !replayed {
+        state.events.sample_created(sample.clone()).await.ok();
+    }
+    Ok((sample, replayed))
 }
 
-pub async fn get_sample_by_id(state: &WebState, sample_id: &i64) -> Option<Sample> {
-    let s = sqlx::query_as!(Sample, r#"SELECT * FROM samples WHERE id = ?"#, sample_id)
-        .fetch_one(&state.db)
-        .await;
-    s.ok()
+async fn try_create_once(
+    state: &WebState,
+    input: SampleInput,
+    uid: i64,
+    key: &str,
+    hash: &str,
+) -> Result<(Sample, bool)> {
+    let mut tx = state.db.begin().await?;
+    if let Some(sample) = idempotency::lookup(&mut tx, uid, key, hash).await? {
+        return Ok((sample, true));
+    }
+    let sample = insert_sample(&mut tx, input, uid).await?;
+    idempotency::store(&mut tx, uid, key, hash, &sample).await?;
+    tx.commit().await?;
+    Ok((sample, false))
 }
 
-pub async fn update_sample_by_id(state: &WebState, input: SampleInput, uid: i64) -> Result<Sample> {
-    let sample = sqlx::query_as!(
-        Sample,
-        r#"UPDATE samples SET name = ?, description = ?, status = ?, updated_at = datetime('now')
-WHERE id = ? RETURNING *"#,
+/// Copies a sample the caller can see into a new draft they own. Attachments,
+/// comments and sharing stay with the original, whose watchers are told.
+pub async fn clone_sample(state: &WebState, id: i64, uid: i64) -> Result<Sample> {
+    let source = authorize(state, id, uid, SampleRole::Viewer).await?;
+    let mut name: String = source.name.chars().take(MAX_NAME_LEN - 7).collect();
+    name.push_str(" (copy)");
+    let input = SampleInput {
+        name,
+        description: source.description,
+        status: SampleStatus::Draft,
+        tags: Some(source.tags.0),
+        version: None,
+        custom_fields: Some(source.custom_fields.0),
+    };
+    let mut tx = state.db.begin().await?;
+    let sample = insert_sample(&mut tx, input, uid).await?;
+    notify_watchers(
+        &mut tx,
+        id,
+        uid,
+        NotificationKind::Copied,
+        "copied",
+        &source.name,
+    )
+    .await?;
+    tx.commit().await?;
+
+    state.events.sample_created(sample.clone()).await.ok();
+
+    Ok(sample)
+}
+
+/// Inserts a sample with its tags and first revision on the caller's
+/// connection, with the creator watching it. Publishing `SampleCreated` is
+/// left to the caller.
+pub(crate) async fn insert_sample(
+    conn: &mut SqliteConnection,
+    input: SampleInput,
+    uid: i64,
+) -> Result<Sample> {
+    validate_input(&input)?;
+    let fields = custom_field::load_fields(conn).await?;
+    let custom_fields = Json(custom_field::validate_values(
+        &fields,
+        input.custom_fields.as_ref().unwrap_or(&Map::new()),
+    )?);
+    let id = sqlx::query_scalar!(
+        r#"
+            INSERT INTO samples (name, description, status, created_by, custom_fields)
+            VALUES (?, ?, ?, ?, ?)
+            RETURNING id AS "id!"
+            "#,
         input.name,
         input.description,
         input.status,
+        uid,
+        custom_fields
+    )
+    .fetch_one(&mut *conn)
+    .await?;
+
+    sqlx::query!(
+        "INSERT INTO sample_acl (sample_id, user_id, role, granted_by) VALUES (?, ?, 'owner', ?)",
+        id,
+        uid,
         uid
     )
-    .fetch_one(&state.db)
-    .await
-    .unwrap();
+    .execute(&mut *conn)
+    .await?;
+
+    if let Some(tags) = &input.tags {
+        set_tags(conn, id, tags).await?;
+    }
+    add_watch(conn, id, uid).await?;
+    let sample = fetch_sample(conn, id).await?;
+    revision::record_revision(conn, &sample, uid, None).await?;
+    audit::record(
+        conn,
+        Some(uid),
+        "sample.created",
+        Some(id),
+        None,
+        snapshot(&sample),
+    )
+    .await?;
+    Ok(sample)
+}
+
+pub const DEFAULT_PAGE_SIZE: i64 = 50;
+pub const MAX_PAGE_SIZE: i64 = 500;
+pub const MAX_TAG_LEN: usize = 50;
+pub const MAX_NAME_LEN: usize = 200;
+
+// Aggregates a sample's tags into the JSON array decoded into `Sample::tags`.
+const TAGS_COLUMN: &str = "(SELECT json_group_array(t.name ORDER BY t.name) \
+    FROM sample_tags st JOIN tags t ON t.id = st.tag_id \
+    WHERE st.sample_id = samples.id) AS tags";
+
+/// Position of the last row on a page. Encoded into the opaque `cursor` handed
+/// back to clients so the next page can carry on with a keyset query.
+#[derive(Debug, Serialize, Deserialize)]
+struct SampleCursor {
+    sort: SampleSort,
+    order: SortOrder,
+    value: Option<String>,
+    id: i64,
+}
+
+impl SampleCursor {
+    fn from_sample(sample: &Sample, sort: SampleSort, order: SortOrder) -> Self {
+        let value = match sort {
+            SampleSort::Id => None,
+            SampleSort::Name => Some(sample.name.clone()),
+            SampleSort::Status => Some(sample.status.to_string()),
+            SampleSort::CreatedAt => Some(sample.created_at.clone()),
+            SampleSort::UpdatedAt => Some(sample.updated_at.clone().unwrap_or_default()),
+        };
+        Self {
+            sort,
+            order,
+            value,
+            id: sample.id,
+        }
+    }
+
+    fn encode(&self) -> Result<String> {
+        Ok(URL_SAFE_NO_PAD.encode(serde_json::to_vec(self)?))
+    }
+
+    fn decode(raw: &str) -> Result<Self> {
+        let bytes = URL_SAFE_NO_PAD
+            .decode(raw)
+            .map_err(|_| ServiceError::Invalid("invalid cursor".into()))?;
+        serde_json::from_slice(&bytes)
+            .map_err(|_| ServiceError::Invalid("invalid cursor".into()).into())
+    }
+}
+
+/// Accepts `YYYY-MM-DD`, `YYYY-MM-DD HH:MM:SS`, `YYYY-MM-DDTHH:MM` (what
+/// `datetime-local` inputs send) or RFC 3339 and returns the format SQLite's
+/// `datetime('now')` writes, so plain string comparison works.
+pub(crate) fn parse_datetime(raw: &str) -> Result<String> {
+    const FMT: &str = "%Y-%m-%d %H:%M:%S";
+    if let Ok(dt) = chrono::DateTime::parse_from_rfc3339(raw) {
+        return Ok(dt.naive_utc().format(FMT).to_string());
+    }
+    if let Ok(dt) = chrono::NaiveDateTime::parse_from_str(raw, FMT) {
+        return Ok(dt.format(FMT).to_string());
+    }
+    if let Ok(dt) = chrono::NaiveDateTime::parse_from_str(raw, "%Y-%m-%dT%H:%M") {
+        return Ok(dt.format(FMT).to_string());
+    }
+    if let Ok(d) = chrono::NaiveDate::parse_from_str(raw, "%Y-%m-%d") {
+        return Ok(d.and_time(chrono::NaiveTime::MIN).format(FMT).to_string());
+    }
+    bail!(ServiceError::Invalid(format!("invalid date: {raw}")))
+}
+
+// Limits a query over `samples` to those the bound user has any role on.
+pub(crate) const VISIBLE_TO_USER: &str =
+    "EXISTS (SELECT 1 FROM sample_acl acl WHERE acl.sample_id = samples.id AND acl.user_id = ";
+
+/// `SELECT` over the live samples `user_id` can see, with every filter from
+/// `query` applied, ready for the caller to add ordering and paging.
+pub(crate) fn filtered_samples_query(
+    query: &SampleQuery,
+    user_id: i64,
+) -> Result<QueryBuilder<'static, Sqlite>> {
+    let mut qb = QueryBuilder::<Sqlite>::new(format!(
+        "SELECT samples.*, {TAGS_COLUMN} FROM samples WHERE deleted_at IS NULL AND {VISIBLE_TO_USER}"
+    ));
+    qb.push_bind(user_id).push(")");
+
+    if let Some(status) = &query.status {
+        qb.push(" AND status = ").push_bind(*status);
+    }
+    if let Some(created_by) = query.created_by {
+        qb.push(" AND created_by = ").push_bind(created_by);
+    }
+    if let Some(tag) = &query.tag {
+        qb.push(
+            " AND EXISTS (SELECT 1 FROM sample_tags st JOIN tags t ON t.id = st.tag_id \
+            WHERE st.sample_id = samples.id AND t.name = ",
+        )
+        .push_bind(tag.clone())
+        .push(")");
+    }
+    let ranges = [
+        ("created_at >= ", &query.created_after),
+        ("created_at < ", &query.created_before),
+        ("updated_at >= ", &query.updated_after),
+        ("updated_at < ", &query.updated_before),
+    ];
+    for (clause, raw) in ranges {
+        if let Some(raw) = raw {
+            qb.push(" AND ")
+                .push(clause)
+                .push_bind(parse_datetime(raw)?);
+        }
+    }
+
+    Ok(qb)
+}
+
+pub async fn get_samples(
+    state: &WebState,
+    query: &SampleQuery,
+    user_id: i64,
+) -> Result<SamplePage> {
+    let sort = query.sort.unwrap_or_default();
+    let order = query.order.unwrap_or_default();
+    let limit = query
+        .limit
+        .unwrap_or(DEFAULT_PAGE_SIZE)
+        .clamp(1, MAX_PAGE_SIZE);
+
+    let cursor = query
+        .cursor
+        .as_deref()
+        .map(SampleCursor::decode)
+        .transpose()?;
+    if let Some(c) = &cursor {
+        if c.sort != sort || c.order != order {
+            bail!(ServiceError::Invalid(
+                "cursor does not match the requested sort".into()
+            ));
+        }
+    }
+
+    let mut qb = filtered_samples_query(query, user_id)?;
+
+    let column = sort.column();
+    let (cmp, dir) = match order {
+        SortOrder::Asc => (">", "ASC"),
+        SortOrder::Desc => ("<", "DESC"),
+    };
+    if let Some(c) = cursor {
+        match c.value {
+            Some(value) => {
+                qb.push(format!(" AND ({column}, id) {cmp} ("))
+                    .push_bind(value)
+                    .push(", ")
+                    .push_bind(c.id)
+                    .push(")");
+            }
+            None => {
+                qb.push(format!(" AND id {cmp} ")).push_bind(c.id);
+            }
+        }
+    }
+
+    qb.push(format!(" ORDER BY {column} {dir}, id {dir} LIMIT "))
+        .push_bind(limit + 1);
+
+    let mut items: Vec<Sample> = qb.build_query_as().fetch_all(&state.db).await?;
+
+    let next_cursor = if items.len() as i64 > limit {
+        items.truncate(limit as usize);
+        items
+            .last()
+            .map(|last| SampleCursor::from_sample(last, sort, order).encode())
+            .transpose()?
+    } else {
+        None
+    };
+
+    Ok(SamplePage { items, next_cursor })
+}
+
+// FTS5 highlight markers, swapped for <mark> tags once the text is escaped.
+const MATCH_START: &str = "\u{2}";
+const MATCH_END: &str = "\u{3}";
+
+/// Turns free text into an FTS5 query: every term is quoted so user input can't
+/// inject query syntax, and the last term is a prefix match for search-as-you-type.
+fn fts_query(raw: &str) -> Option<String> {
+    let terms: Vec<String> = raw
+        .split_whitespace()
+        .map(|t| format!("\"{}\"", t.replace('"', "\"\"")))
+        .collect();
+    if terms.is_empty() {
+        return None;
+    }
+    Some(format!("{}*", terms.join(" ")))
+}
+
+fn highlight_html(raw: &str) -> String {
+    let mut out = String::with_capacity(raw.len());
+    for c in raw.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#x27;"),
+            _ => out.push(c),
+        }
+    }
+    out.replace(MATCH_START, "<mark>")
+        .replace(MATCH_END, "</mark>")
+}
+
+pub async fn search_samples(
+    state: &WebState,
+    query: &SampleSearchQuery,
+    user_id: i64,
+) -> Result<Vec<SampleSearchHit>> {
+    let Some(fts) = fts_query(&query.q) else {
+        return Ok(vec![]);
+    };
+    let limit = query
+        .limit
+        .unwrap_or(DEFAULT_PAGE_SIZE)
+        .clamp(1, MAX_PAGE_SIZE);
+
+    let sql = format!(
+        r#"
+            SELECT
+                samples.*,
+                {TAGS_COLUMN},
+                samples_fts.rank AS rank,
+                highlight(samples_fts, 0, ?1, ?2) AS name_highlight,
+                snippet(samples_fts, 1, ?1, ?2, '…', 16) AS description_snippet
+            FROM samples_fts
+            JOIN samples ON samples.id = samples_fts.rowid
+            WHERE samples_fts MATCH ?3 AND samples.deleted_at IS NULL
+              AND {VISIBLE_TO_USER}?5)
+            ORDER BY samples_fts.rank
+            LIMIT ?4
+        "#
+    );
+    let mut hits: Vec<SampleSearchHit> = sqlx::query_as(&sql)
+        .bind(MATCH_START)
+        .bind(MATCH_END)
+        .bind(fts)
+        .bind(limit)
+        .bind(user_id)
+        .fetch_all(&state.db)
+        .await?;
+
+    for hit in hits.iter_mut() {
+        hit.name_highlight = highlight_html(&hit.name_highlight);
+        hit.description_snippet = hit
+            .description_snippet
+            .as_deref()
+            .filter(|s| !s.is_empty())
+            .map(highlight_html);
+    }
+
+    Ok(hits)
+}
+
+/// The access check every sample operation goes through, from the web, the
+/// API and Kafka commands alike. Returns the user's role when it is at least
+/// `needed`. Users with no role get `NotFound`, so samples they can't see
+/// don't reveal that they exist.
+pub(crate) async fn check_access(
+    conn: &mut SqliteConnection,
+    sample_id: i64,
+    user_id: i64,
+    needed: SampleRole,
+) -> Result<SampleRole> {
+    let role = sqlx::query_scalar!(
+        r#"SELECT role AS "role: SampleRole" FROM sample_acl WHERE sample_id = ? AND user_id = ?"#,
+        sample_id,
+        user_id
+    )
+    .fetch_optional(&mut *conn)
+    .await?;
+
+    match role {
+        None => bail!(ServiceError::NotFound("sample", sample_id)),
+        Some(role) if role < needed => bail!(ServiceError::Forbidden(match needed {
+            SampleRole::Viewer => "you don't have access to this sample",
+            SampleRole::Editor => "you need editor access to change this sample",
+            SampleRole::Owner => "only an owner of this sample can do that",
+        })),
+        Some(role) => Ok(role),
+    }
+}
+
+/// Loads a live sample after checking `user_id` holds at least `needed` on it.
+pub async fn authorize(
+    state: &WebState,
+    sample_id: i64,
+    user_id: i64,
+    needed: SampleRole,
+) -> Result<Sample> {
+    let mut conn = state.db.acquire().await?;
+    check_access(&mut conn, sample_id, user_id, needed).await?;
+    let sample = fetch_sample(&mut conn, sample_id).await?;
+    if sample.deleted_at.is_some() {
+        bail!(ServiceError::NotFound("sample", sample_id));
+    }
+    Ok(sample)
+}
+
+/// `user_id`'s role on a sample, or `NotFound` when they have none.
+pub async fn role_for(state: &WebState, sample_id: i64, user_id: i64) -> Result<SampleRole> {
+    let mut conn = state.db.acquire().await?;
+    check_access(&mut conn, sample_id, user_id, SampleRole::Viewer).await
+}
+
+pub async fn update_sample_by_id(
+    state: &WebState,
+    input: SampleInput,
+    id: i64,
+    actor_id: i64,
+) -> Result<Sample> {
+    apply_update(state, input, id, actor_id, UpdateKind::Edit).await
+}
+
+/// Moves an archived sample back to draft, the only way out of archived.
+pub async fn reopen_sample_by_id(state: &WebState, id: i64, actor_id: i64) -> Result<Sample> {
+    let current = authorize(state, id, actor_id, SampleRole::Editor).await?;
+    let input = SampleInput {
+        name: current.name,
+        description: current.description,
+        status: SampleStatus::Draft,
+        tags: None,
+        version: None,
+        custom_fields: None,
+    };
+    apply_update(state, input, id, actor_id, UpdateKind::Reopen).await
+}
+
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum UpdateKind {
+    Edit,
+    /// Restores the given revision number.
+    Restore(i64),
+    Reopen,
+}
+
+impl UpdateKind {
+    fn audit_action(&self) -> &'static str {
+        match self {
+            UpdateKind::Edit => "sample.updated",
+            UpdateKind::Restore(_) => "sample.revision_restored",
+            UpdateKind::Reopen => "sample.reopened",
+        }
+    }
+
+    /// How watchers are told about it, e.g. "… reopened “Blue dye”".
+    fn verb(&self) -> String {
+        match self {
+            UpdateKind::Edit => "updated".to_string(),
+            UpdateKind::Restore(revision) => format!("restored revision {revision} of"),
+            UpdateKind::Reopen => "reopened".to_string(),
+        }
+    }
+
+    fn allows(&self, from: SampleStatus, to: SampleStatus) -> bool {
+        match self {
+            UpdateKind::Edit | UpdateKind::Restore(_) => from.can_transition_to(to),
+            UpdateKind::Reopen => from == SampleStatus::Archived && to == SampleStatus::Draft,
+        }
+    }
+}
+
+fn check_version(expected: Option<i64>, current: i64) -> Result<()> {
+    match expected {
+        Some(expected) if expected != current => {
+            bail!(ServiceError::VersionConflict { expected, current })
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Writes the update in one transaction, then publishes `SampleUpdated`.
+pub(crate) async fn apply_update(
+    state: &WebState,
+    input: SampleInput,
+    id: i64,
+    actor_id: i64,
+    kind: UpdateKind,
+) -> Result<Sample> {
+    let mut tx = state.db.begin().await?;
+    let sample = write_update(&mut tx, input, id, actor_id, kind).await?;
+    tx.commit().await?;
 
     state
         .events
@@ -75,12 +651,796 @@ WHERE id = ? RETURNING *"#,
     Ok(sample)
 }
 
-pub async fn delete_sample_by_id(state: &WebState, id: i64) -> Result<()> {
-    sqlx::query!("DELETE FROM samples WHERE id = ?", id)
-        .execute(&state.db)
+/// Checks the version and status transition, then writes the update and its
+/// revision on the caller's connection.
+pub(crate) async fn write_update(
+    conn: &mut SqliteConnection,
+    input: SampleInput,
+    id: i64,
+    actor_id: i64,
+    kind: UpdateKind,
+) -> Result<Sample> {
+    validate_input(&input)?;
+    check_access(conn, id, actor_id, SampleRole::Editor).await?;
+    let current = sqlx::query!(
+        r#"SELECT status AS "status: SampleStatus", version FROM samples WHERE id = ? AND deleted_at IS NULL"#,
+        id
+    )
+    .fetch_optional(&mut *conn)
+    .await?
+    .ok_or(ServiceError::NotFound("sample", id))?;
+
+    check_version(input.version, current.version)?;
+    if !kind.allows(current.status, input.status) {
+        bail!(ServiceError::InvalidTransition {
+            from: current.status,
+            to: input.status,
+        });
+    }
+
+    let before = fetch_sample(conn, id).await?;
+    sqlx::query!(
+        r#"UPDATE samples
+SET name = ?, description = ?, status = ?, version = version + 1, updated_at = datetime('now')
+WHERE id = ?"#,
+        input.name,
+        input.description,
+        input.status,
+        id
+    )
+    .execute(&mut *conn)
+    .await?;
+
+    if let Some(tags) = &input.tags {
+        set_tags(conn, id, tags).await?;
+    }
+    if let Some(values) = &input.custom_fields {
+        let fields = custom_field::load_fields(conn).await?;
+        let values = Json(custom_field::validate_values(&fields, values)?);
+        sqlx::query!(
+            "UPDATE samples SET custom_fields = ? WHERE id = ?",
+            values,
+            id
+        )
+        .execute(&mut *conn)
         .await?;
+    }
+    let sample = fetch_sample(conn, id).await?;
+    let restored_from = match kind {
+        UpdateKind::Restore(revision) => Some(revision),
+        _ => None,
+    };
+    revision::record_revision(conn, &sample, actor_id, restored_from).await?;
+    audit::record(
+        conn,
+        Some(actor_id),
+        kind.audit_action(),
+        Some(id),
+        snapshot(&before),
+        snapshot(&sample),
+    )
+    .await?;
+    notify_watchers(
+        conn,
+        id,
+        actor_id,
+        NotificationKind::Updated,
+        &kind.verb(),
+        &sample.name,
+    )
+    .await?;
+    Ok(sample)
+}
+
+/// Moves a sample to the trash. It stays restorable until the purge task
+/// removes it for good, see [`purge_trashed_samples`]. When `expected_version`
+/// is set the delete only goes through if nobody has saved the sample since.
+pub async fn delete_sample_by_id(
+    state: &WebState,
+    id: i64,
+    expected_version: Option<i64>,
+    actor_id: i64,
+) -> Result<()> {
+    let mut tx = state.db.begin().await?;
+    trash_sample(&mut tx, id, expected_version, actor_id).await?;
+    tx.commit().await?;
 
     state.events.sample_deleted(id).await?;
 
     Ok(())
 }
+
+pub(crate) async fn trash_sample(
+    conn: &mut SqliteConnection,
+    id: i64,
+    expected_version: Option<i64>,
+    actor_id: i64,
+) -> Result<()> {
+    check_access(conn, id, actor_id, SampleRole::Owner).await?;
+    let current = sqlx::query_scalar!(
+        "SELECT version FROM samples WHERE id = ? AND deleted_at IS NULL",
+        id
+    )
+    .fetch_optional(&mut *conn)
+    .await?
+    .ok_or(ServiceError::NotFound("sample", id))?;
+    check_version(expected_version, current)?;
+
+    let before = fetch_sample(conn, id).await?;
+    sqlx::query!(
+        "UPDATE samples SET deleted_at = datetime('now'), version = version + 1 WHERE id = ?",
+        id
+    )
+    .execute(&mut *conn)
+    .await?;
+    let after = fetch_sample(conn, id).await?;
+    audit::record(
+        conn,
+        Some(actor_id),
+        "sample.deleted",
+        Some(id),
+        snapshot(&before),
+        snapshot(&after),
+    )
+    .await?;
+    notify_watchers(
+        conn,
+        id,
+        actor_id,
+        NotificationKind::Deleted,
+        "deleted",
+        &before.name,
+    )
+    .await?;
+    Ok(())
+}
+
+/// Trashed samples `user_id` owns, the ones they could restore.
+pub async fn get_trashed_samples(state: &WebState, user_id: i64) -> Result<Vec<Sample>> {
+    let samples = sqlx::query_as!(
+        Sample,
+        r#"
+            SELECT
+                s.id          AS "id!",
+                s.name        AS "name!",
+                s.description AS "description?",
+                s.status      AS "status!: SampleStatus",
+                s.created_at  AS "created_at!",
+                s.updated_at  AS "updated_at?",
+                s.created_by  AS "created_by!",
+                s.deleted_at  AS "deleted_at?",
+                s.version     AS "version!",
+                (SELECT json_group_array(t.name ORDER BY t.name)
+                 FROM sample_tags st JOIN tags t ON t.id = st.tag_id
+                 WHERE st.sample_id = s.id) AS "tags!: Json<Vec<String>>",
+                s.custom_fields AS "custom_fields!: Json<Map<String, Value>>"
+            FROM samples s
+            JOIN sample_acl acl ON acl.sample_id = s.id AND acl.user_id = ? AND acl.role = 'owner'
+            WHERE s.deleted_at IS NOT NULL
+            ORDER BY s.deleted_at DESC, s.id DESC
+        "#,
+        user_id
+    )
+    .fetch_all(&state.db)
+    .await?;
+    Ok(samples)
+}
+
+pub async fn restore_sample_by_id(state: &WebState, id: i64, actor_id: i64) -> Result<Sample> {
+    let mut tx = state.db.begin().await?;
+    check_access(&mut tx, id, actor_id, SampleRole::Owner).await?;
+    let before = fetch_sample(&mut tx, id).await?;
+    sqlx::query_scalar!(
+        r#"UPDATE samples SET deleted_at = NULL, version = version + 1, updated_at = datetime('now')
+WHERE id = ? AND deleted_at IS NOT NULL RETURNING id"#,
+        id
+    )
+    .fetch_optional(&mut *tx)
+    .await?
+    .ok_or(ServiceError::NotFound("trashed sample", id))?;
+    let sample = fetch_sample(&mut tx, id).await?;
+    audit::record(
+        &mut tx,
+        Some(actor_id),
+        "sample.restored",
+        Some(id),
+        snapshot(&before),
+        snapshot(&sample),
+    )
+    .await?;
+    notify_watchers(
+        &mut tx,
+        id,
+        actor_id,
+        NotificationKind::Restored,
+        "restored",
+        &sample.name,
+    )
+    .await?;
+    tx.commit().await?;
+
+    state.events.sample_restored(sample.clone()).await?;
+
+    Ok(sample)
+}
+
+/// Permanently deletes samples that have been in the trash for longer than
+/// `retention_days`. Returns the number of rows removed.
+pub async fn purge_trashed_samples(db: &SqlitePool, retention_days: i64) -> Result<u64> {
+    let cutoff = format!("-{retention_days} days");
+    let mut tx = db.begin().await?;
+    let purged = sqlx::query_scalar!(
+        r#"DELETE FROM samples WHERE deleted_at IS NOT NULL AND deleted_at < datetime('now', ?)
+RETURNING id AS "id!""#,
+        cutoff
+    )
+    .fetch_all(&mut *tx)
+    .await?;
+    for id in &purged {
+        audit::record(&mut tx, None, "sample.purged", Some(*id), None, None).await?;
+    }
+    tx.commit().await?;
+    Ok(purged.len() as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+
+    fn ids(page: &SamplePage) -> Vec<i64> {
+        page.items.iter().map(|s| s.id).collect()
+    }
+
+    #[tokio::test]
+    async fn cursor_pages_cover_every_sample_once() {
+        let state = testing::state().await;
+        let uid = testing::user(&state.db, "owner@example.com").await;
+        for name in ["e", "b", "d", "a", "c"] {
+            testing::sample(&state, uid, name).await;
+        }
+
+        let mut query = SampleQuery {
+            limit: Some(2),
+            sort: Some(SampleSort::Name),
+            order: Some(SortOrder::Asc),
+            ..Default::default()
+        };
+        let mut names = Vec::new();
+        loop {
+            let page = get_samples(&state, &query, uid).await.unwrap();
+            assert!(page.items.len() <= 2);
+            names.extend(page.items.iter().map(|s| s.name.clone()));
+            match page.next_cursor {
+                Some(cursor) => query.cursor = Some(cursor),
+                None => break,
+            }
+        }
+        assert_eq!(names, ["a", "b", "c", "d", "e"]);
+    }
+
+    #[tokio::test]
+    async fn default_listing_is_newest_first() {
+        let state = testing::state().await;
+        let uid = testing::user(&state.db, "owner@example.com").await;
+        let first = testing::sample(&state, uid, "first").await;
+        let second = testing::sample(&state, uid, "second").await;
+
+        let page = get_samples(&state, &SampleQuery::default(), uid)
+            .await
+            .unwrap();
+        assert_eq!(ids(&page), [second.id, first.id]);
+        assert!(page.next_cursor.is_none());
+    }
+
+    #[tokio::test]
+    async fn filters_narrow_the_listing() {
+        let state = testing::state().await;
+        let alice = testing::user(&state.db, "alice@example.com").await;
+        let bob = testing::user(&state.db, "bob@example.com").await;
+        let draft = testing::sample(&state, alice, "draft").await;
+        let mut active = testing::input("active");
+        active.status = SampleStatus::Active;
+        let active = create_sample(&state, active, alice).await.unwrap();
+        let bobs = testing::sample(&state, bob, "bob's").await;
+        testing::share(
+            &state,
+            bobs.id,
+            bob,
+            "alice@example.com",
+            SampleRole::Viewer,
+        )
+        .await;
+
+        let by_status = SampleQuery {
+            status: Some(SampleStatus::Active),
+            ..Default::default()
+        };
+        let page = get_samples(&state, &by_status, alice).await.unwrap();
+        assert_eq!(ids(&page), [active.id]);
+
+        let by_creator = SampleQuery {
+            created_by: Some(bob),
+            ..Default::default()
+        };
+        let page = get_samples(&state, &by_creator, alice).await.unwrap();
+        assert_eq!(ids(&page), [bobs.id]);
+
+        let future = SampleQuery {
+            created_after: Some("2999-01-01".into()),
+            ..Default::default()
+        };
+        assert!(get_samples(&state, &future, alice)
+            .await
+            .unwrap()
+            .items
+            .is_empty());
+        let past = SampleQuery {
+            created_after: Some("2000-01-01T00:00:00Z".into()),
+            ..Default::default()
+        };
+        assert_eq!(
+            ids(&get_samples(&state, &past, alice).await.unwrap()),
+            [bobs.id, active.id, draft.id]
+        );
+    }
+
+    #[tokio::test]
+    async fn bad_cursors_and_dates_are_rejected() {
+        let state = testing::state().await;
+        let uid = testing::user(&state.db, "owner@example.com").await;
+        testing::sample(&state, uid, "a").await;
+        testing::sample(&state, uid, "b").await;
+        let page = get_samples(
+            &state,
+            &SampleQuery {
+                limit: Some(1),
+                ..Default::default()
+            },
+            uid,
+        )
+        .await
+        .unwrap();
+
+        let other_sort = SampleQuery {
+            cursor: page.next_cursor,
+            sort: Some(SampleSort::Name),
+            ..Default::default()
+        };
+        assert!(get_samples(&state, &other_sort, uid).await.is_err());
+        let garbage = SampleQuery {
+            cursor: Some("not a cursor".into()),
+            ..Default::default()
+        };
+        assert!(get_samples(&state, &garbage, uid).await.is_err());
+        let bad_date = SampleQuery {
+            updated_before: Some("yesterday".into()),
+            ..Default::default()
+        };
+        assert!(get_samples(&state, &bad_date, uid).await.is_err());
+    }
+
+    async fn search(state: &WebState, uid: i64, q: &str) -> Vec<SampleSearchHit> {
+        let query = SampleSearchQuery {
+            q: q.to_string(),
+            limit: None,
+        };
+        search_samples(state, &query, uid).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn search_matches_prefixes_and_marks_them() {
+        let state = testing::state().await;
+        let uid = testing::user(&state.db, "owner@example.com").await;
+        let mut dye = testing::input("Blue dye");
+        dye.description = Some("A stable pigment <for> wool".into());
+        let dye = create_sample(&state, dye, uid).await.unwrap();
+        testing::sample(&state, uid, "Red paint").await;
+
+        let hits = search(&state, uid, "pigm").await;
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].sample.id, dye.id);
+        assert_eq!(
+            hits[0].description_snippet.as_deref(),
+            Some("A stable <mark>pigment</mark> &lt;for&gt; wool")
+        );
+
+        let hits = search(&state, uid, "blue").await;
+        assert_eq!(hits[0].name_highlight, "<mark>Blue</mark> dye");
+        assert!(hits[0].description_snippet.is_some());
+    }
+
+    #[tokio::test]
+    async fn search_treats_input_as_plain_terms() {
+        let state = testing::state().await;
+        let uid = testing::user(&state.db, "owner@example.com").await;
+        testing::sample(&state, uid, "Blue dye").await;
+
+        assert!(search(&state, uid, "   ").await.is_empty());
+        // OR is a term to match, not an operator
+        assert!(search(&state, uid, "blue OR red").await.is_empty());
+        // a stray quote doesn't break the query
+        assert_eq!(search(&state, uid, "\"blue").await.len(), 1);
+        assert_eq!(search(&state, uid, "dye blue").await.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn search_follows_edits_and_deletes() {
+        let state = testing::state().await;
+        let uid = testing::user(&state.db, "owner@example.com").await;
+        let dye = testing::sample(&state, uid, "Blue dye").await;
+
+        sqlx::query!("UPDATE samples SET name = 'Green dye' WHERE id = ?", dye.id)
+            .execute(&state.db)
+            .await
+            .unwrap();
+        assert!(search(&state, uid, "blue").await.is_empty());
+        assert_eq!(search(&state, uid, "green").await.len(), 1);
+
+        delete_sample_by_id(&state, dye.id, None, uid)
+            .await
+            .unwrap();
+        assert!(search(&state, uid, "green").await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn trashed_samples_leave_the_listing_until_restored() {
+        let state = testing::state().await;
+        let uid = testing::user(&state.db, "owner@example.com").await;
+        let dye = testing::sample(&state, uid, "Blue dye").await;
+
+        delete_sample_by_id(&state, dye.id, None, uid)
+            .await
+            .unwrap();
+        let listed = get_samples(&state, &SampleQuery::default(), uid)
+            .await
+            .unwrap();
+        assert!(listed.items.is_empty());
+        assert!(authorize(&state, dye.id, uid, SampleRole::Viewer)
+            .await
+            .is_err());
+        let trash = get_trashed_samples(&state, uid).await.unwrap();
+        assert_eq!(trash.len(), 1);
+        assert!(delete_sample_by_id(&state, dye.id, None, uid)
+            .await
+            .is_err());
+
+        let restored = restore_sample_by_id(&state, dye.id, uid).await.unwrap();
+        assert!(restored.deleted_at.is_none());
+        let listed = get_samples(&state, &SampleQuery::default(), uid)
+            .await
+            .unwrap();
+        assert_eq!(ids(&listed), [dye.id]);
+        assert!(restore_sample_by_id(&state, dye.id, uid).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn purge_only_removes_samples_past_retention() {
+        let state = testing::state().await;
+        let uid = testing::user(&state.db, "owner@example.com").await;
+        let old = testing::sample(&state, uid, "old").await;
+        let recent = testing::sample(&state, uid, "recent").await;
+        let kept = testing::sample(&state, uid, "kept").await;
+        delete_sample_by_id(&state, old.id, None, uid)
+            .await
+            .unwrap();
+        delete_sample_by_id(&state, recent.id, None, uid)
+            .await
+            .unwrap();
+        sqlx::query!(
+            "UPDATE samples SET deleted_at = datetime('now', '-31 days') WHERE id = ?",
+            old.id
+        )
+        .execute(&state.db)
+        .await
+        .unwrap();
+
+        assert_eq!(purge_trashed_samples(&state.db, 30).await.unwrap(), 1);
+        let trash = get_trashed_samples(&state, uid).await.unwrap();
+        assert_eq!(trash.iter().map(|s| s.id).collect::<Vec<_>>(), [recent.id]);
+        assert!(authorize(&state, kept.id, uid, SampleRole::Viewer)
+            .await
+            .is_ok());
+    }
+
+    #[tokio::test]
+    async fn tags_are_cleaned_and_filterable() {
+        let state = testing::state().await;
+        let uid = testing::user(&state.db, "owner@example.com").await;
+        let mut wool = testing::input("Blue dye");
+        wool.tags = Some(vec![
+            " wool ".into(),
+            "Wool".into(),
+            "".into(),
+            "blue".into(),
+        ]);
+        let wool = create_sample(&state, wool, uid).await.unwrap();
+        testing::sample(&state, uid, "Untagged").await;
+        assert_eq!(wool.tags.0, ["blue", "wool"]);
+
+        let by_tag = SampleQuery {
+            tag: Some("WOOL".into()),
+            ..Default::default()
+        };
+        assert_eq!(
+            ids(&get_samples(&state, &by_tag, uid).await.unwrap()),
+            [wool.id]
+        );
+
+        // an update without tags keeps them, an empty list clears them
+        let kept = update_sample_by_id(&state, testing::input("Blue dye"), wool.id, uid)
+            .await
+            .unwrap();
+        assert_eq!(kept.tags.0, ["blue", "wool"]);
+        let mut cleared = testing::input("Blue dye");
+        cleared.tags = Some(vec![]);
+        let cleared = update_sample_by_id(&state, cleared, wool.id, uid)
+            .await
+            .unwrap();
+        assert!(cleared.tags.0.is_empty());
+        assert!(get_samples(&state, &by_tag, uid)
+            .await
+            .unwrap()
+            .items
+            .is_empty());
+    }
+
+    #[test]
+    fn tags_deserialize_from_a_list_or_a_comma_string() {
+        let list: SampleInput =
+            serde_json::from_str(r#"{"name":"a","status":"draft","tags":["x","y"]}"#).unwrap();
+        assert_eq!(list.tags.unwrap(), ["x", "y"]);
+        let text: SampleInput =
+            serde_json::from_str(r#"{"name":"a","status":"draft","tags":"x,y"}"#).unwrap();
+        assert_eq!(text.tags.unwrap(), ["x", "y"]);
+        let none: SampleInput = serde_json::from_str(r#"{"name":"a","status":"draft"}"#).unwrap();
+        assert!(none.tags.is_none());
+    }
+
+    fn with_status(name: &str, status: SampleStatus) -> SampleInput {
+        SampleInput {
+            status,
+            ..testing::input(name)
+        }
+    }
+
+    #[tokio::test]
+    async fn archived_samples_only_leave_by_reopening() {
+        let state = testing::state().await;
+        let uid = testing::user(&state.db, "owner@example.com").await;
+        let dye = testing::sample(&state, uid, "Blue dye").await;
+        update_sample_by_id(
+            &state,
+            with_status("Blue dye", SampleStatus::Archived),
+            dye.id,
+            uid,
+        )
+        .await
+        .unwrap();
+
+        let e = update_sample_by_id(
+            &state,
+            with_status("Blue dye", SampleStatus::Active),
+            dye.id,
+            uid,
+        )
+        .await
+        .unwrap_err();
+        assert!(matches!(
+            e.downcast_ref::<ServiceError>(),
+            Some(ServiceError::InvalidTransition {
+                from: SampleStatus::Archived,
+                to: SampleStatus::Active
+            })
+        ));
+        // renaming while staying archived is fine
+        update_sample_by_id(
+            &state,
+            with_status("Old dye", SampleStatus::Archived),
+            dye.id,
+            uid,
+        )
+        .await
+        .unwrap();
+
+        let reopened = reopen_sample_by_id(&state, dye.id, uid).await.unwrap();
+        assert_eq!(reopened.status, SampleStatus::Draft);
+        assert_eq!(reopened.name, "Old dye");
+        assert!(reopen_sample_by_id(&state, dye.id, uid).await.is_err());
+    }
+
+    #[test]
+    fn unknown_statuses_are_rejected() {
+        let input = serde_json::from_str::<SampleInput>(r#"{"name":"a","status":"active"}"#);
+        assert_eq!(input.unwrap().status, SampleStatus::Active);
+        let input = serde_json::from_str::<SampleInput>(r#"{"name":"a","status":"done"}"#);
+        assert!(input.is_err());
+    }
+
+    fn is_conflict(e: &anyhow::Error, expected: i64, current: i64) -> bool {
+        matches!(
+            e.downcast_ref::<ServiceError>(),
+            Some(ServiceError::VersionConflict { expected: e, current: c })
+                if *e == expected && *c == current
+        )
+    }
+
+    #[tokio::test]
+    async fn stale_updates_are_rejected() {
+        let state = testing::state().await;
+        let uid = testing::user(&state.db, "owner@example.com").await;
+        let dye = testing::sample(&state, uid, "Blue dye").await;
+        let seen = dye.version;
+
+        let mut edit = testing::input("Red dye");
+        edit.version = Some(seen);
+        let updated = update_sample_by_id(&state, edit, dye.id, uid)
+            .await
+            .unwrap();
+        assert_eq!(updated.version, seen + 1);
+
+        let mut stale = testing::input("Green dye");
+        stale.version = Some(seen);
+        let e = update_sample_by_id(&state, stale, dye.id, uid)
+            .await
+            .unwrap_err();
+        assert!(is_conflict(&e, seen, seen + 1), "{e:?}");
+        let current = authorize(&state, dye.id, uid, SampleRole::Viewer)
+            .await
+            .unwrap();
+        assert_eq!(
+            (current.name.as_str(), current.version),
+            ("Red dye", seen + 1)
+        );
+
+        // no version means last write wins
+        let blind = update_sample_by_id(&state, testing::input("Green dye"), dye.id, uid)
+            .await
+            .unwrap();
+        assert_eq!(blind.version, seen + 2);
+    }
+
+    #[tokio::test]
+    async fn stale_deletes_are_rejected() {
+        let state = testing::state().await;
+        let uid = testing::user(&state.db, "owner@example.com").await;
+        let dye = testing::sample(&state, uid, "Blue dye").await;
+        update_sample_by_id(&state, testing::input("Red dye"), dye.id, uid)
+            .await
+            .unwrap();
+
+        let e = delete_sample_by_id(&state, dye.id, Some(dye.version), uid)
+            .await
+            .unwrap_err();
+        assert!(is_conflict(&e, dye.version, dye.version + 1), "{e:?}");
+        assert!(authorize(&state, dye.id, uid, SampleRole::Viewer)
+            .await
+            .is_ok());
+
+        delete_sample_by_id(&state, dye.id, Some(dye.version + 1), uid)
+            .await
+            .unwrap();
+        assert!(authorize(&state, dye.id, uid, SampleRole::Viewer)
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn clones_are_private_drafts_of_the_original() {
+        let state = testing::state().await;
+        let ann = testing::user(&state.db, "ann@example.com").await;
+        let bob = testing::user(&state.db, "bob@example.com").await;
+        let cat = testing::user(&state.db, "cat@example.com").await;
+        let mut input = with_status(&"x".repeat(MAX_NAME_LEN), SampleStatus::Active);
+        input.tags = Some(vec!["wool".into()]);
+        let original = create_sample(&state, input, ann).await.unwrap();
+        testing::share(
+            &state,
+            original.id,
+            ann,
+            "bob@example.com",
+            SampleRole::Viewer,
+        )
+        .await;
+
+        assert!(clone_sample(&state, original.id, cat).await.is_err());
+        let copy = clone_sample(&state, original.id, bob).await.unwrap();
+        assert_ne!(copy.id, original.id);
+        assert_eq!(copy.status, SampleStatus::Draft);
+        assert_eq!(copy.created_by, bob);
+        assert_eq!(copy.tags.0, ["wool"]);
+        assert_eq!(copy.name.chars().count(), MAX_NAME_LEN);
+        assert!(copy.name.ends_with(" (copy)"));
+        assert_eq!(
+            role_for(&state, copy.id, bob).await.unwrap(),
+            SampleRole::Owner
+        );
+        assert!(role_for(&state, copy.id, ann).await.is_err());
+    }
+
+    async fn sample_count(state: &WebState) -> i64 {
+        sqlx::query_scalar!(r#"SELECT COUNT(1) AS "count!: i64" FROM samples"#)
+            .fetch_one(&state.db)
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn repeated_key_replays_the_first_create() {
+        let state = testing::state().await;
+        let uid = testing::user(&state.db, "owner@example.com").await;
+
+        let (first, replayed) = create_sample_once(&state, testing::input("Blue dye"), uid, "k1")
+            .await
+            .unwrap();
+        assert!(!replayed);
+        let (second, replayed) =
+            create_sample_once(&state, testing::input("Blue dye"), uid, " k1 ")
+                .await
+                .unwrap();
+        assert!(replayed);
+        assert_eq!(second.id, first.id);
+        assert_eq!(sample_count(&state).await, 1);
+    }
+
+    #[tokio::test]
+    async fn repeated_key_with_a_different_body_is_rejected() {
+        let state = testing::state().await;
+        let uid = testing::user(&state.db, "owner@example.com").await;
+        create_sample_once(&state, testing::input("Blue dye"), uid, "k1")
+            .await
+            .unwrap();
+
+        let e = create_sample_once(&state, testing::input("Red dye"), uid, "k1")
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            e.downcast_ref::<ServiceError>(),
+            Some(ServiceError::Invalid(_))
+        ));
+        assert_eq!(sample_count(&state).await, 1);
+    }
+
+    #[tokio::test]
+    async fn keys_are_per_user_and_expire() {
+        let state = testing::state().await;
+        let alice = testing::user(&state.db, "alice@example.com").await;
+        let bob = testing::user(&state.db, "bob@example.com").await;
+        let (first, _) = create_sample_once(&state, testing::input("Blue dye"), alice, "k1")
+            .await
+            .unwrap();
+
+        let (other, replayed) = create_sample_once(&state, testing::input("Blue dye"), bob, "k1")
+            .await
+            .unwrap();
+        assert!(!replayed);
+        assert_ne!(other.id, first.id);
+
+        sqlx::query!("UPDATE idempotency_keys SET created_at = datetime('now', '-25 hours')")
+            .execute(&state.db)
+            .await
+            .unwrap();
+        let (again, replayed) = create_sample_once(&state, testing::input("Blue dye"), alice, "k1")
+            .await
+            .unwrap();
+        assert!(!replayed);
+        assert_ne!(again.id, first.id);
+        assert_eq!(sample_count(&state).await, 3);
+    }
+
+    #[tokio::test]
+    async fn concurrent_creates_with_one_key_make_one_sample() {
+        let state = testing::state().await;
+        let uid = testing::user(&state.db, "owner@example.com").await;
+
+        let (a, b) = tokio::join!(
+            create_sample_once(&state, testing::input("Blue dye"), uid, "k1"),
+            create_sample_once(&state, testing::input("Blue dye"), uid, "k1"),
+        );
+        let (a, a_replayed) = a.unwrap();
+        let (b, b_replayed) = b.unwrap();
+        assert_eq!(a.id, b.id);
+        assert!(a_replayed != b_replayed);
+        assert_eq!(sample_count(&state).await, 1);
+    }
+}
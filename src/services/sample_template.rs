@@ -0,0 +1,296 @@
+use anyhow::{bail, Result};
+use serde_json::{Map, Value};
+use sqlx::{types::Json, SqliteConnection};
+
+use crate::models::{
+    sample_template::{SampleTemplate, SampleTemplateInput},
+    share::SampleRole,
+    state::WebState,
+};
+use crate::services::{
+    audit::{self, snapshot},
+    custom_field,
+    error::ServiceError,
+    sample::{authorize, normalize_tags, MAX_NAME_LEN},
+};
+
+pub async fn get_templates(state: &WebState) -> Result<Vec<SampleTemplate>> {
+    let templates = sqlx::query_as!(
+        SampleTemplate,
+        r#"
+            SELECT
+                id            AS "id!",
+                name          AS "name!",
+                description   AS "description?",
+                tags          AS "tags!: Json<Vec<String>>",
+                custom_fields AS "custom_fields!: Json<Map<String, Value>>",
+                created_by    AS "created_by!",
+                created_at    AS "created_at!"
+            FROM sample_templates
+            ORDER BY name COLLATE NOCASE
+        "#
+    )
+    .fetch_all(&state.db)
+    .await?;
+    Ok(templates)
+}
+
+pub async fn get_template(state: &WebState, id: i64) -> Result<SampleTemplate> {
+    let mut conn = state.db.acquire().await?;
+    fetch_template(&mut conn, id).await
+}
+
+async fn fetch_template(conn: &mut SqliteConnection, id: i64) -> Result<SampleTemplate> {
+    let template = sqlx::query_as!(
+        SampleTemplate,
+        r#"
+            SELECT
+                id            AS "id!",
+                name          AS "name!",
+                description   AS "description?",
+                tags          AS "tags!: Json<Vec<String>>",
+                custom_fields AS "custom_fields!: Json<Map<String, Value>>",
+                created_by    AS "created_by!",
+                created_at    AS "created_at!"
+            FROM sample_templates
+            WHERE id = ?
+        "#,
+        id
+    )
+    .fetch_optional(conn)
+    .await?;
+    template.ok_or_else(|| ServiceError::NotFound("template", id).into())
+}
+
+/// Saves a template. Custom values are checked like a sample's, except that
+/// required fields may be left for whoever uses the template to fill in.
+pub async fn create_template(
+    state: &WebState,
+    input: SampleTemplateInput,
+    uid: i64,
+) -> Result<SampleTemplate> {
+    let name = input.name.trim();
+    if name.is_empty() {
+        bail!(ServiceError::Invalid("template name is required".into()));
+    }
+    if name.chars().count() > MAX_NAME_LEN {
+        bail!(ServiceError::Invalid(format!(
+            "template name is longer than {MAX_NAME_LEN} characters"
+        )));
+    }
+
+    let mut tx = state.db.begin().await?;
+    let fields: Vec<_> = custom_field::load_fields(&mut tx)
+        .await?
+        .into_iter()
+        .map(|mut f| {
+            f.required = false;
+            f
+        })
+        .collect();
+    let custom_fields = Json(custom_field::validate_values(
+        &fields,
+        &input.custom_fields,
+    )?);
+    let tags = Json(normalize_tags(&input.tags));
+    let description = input.description.filter(|d| !d.trim().is_empty());
+
+    let exists = sqlx::query_scalar!("SELECT COUNT(1) FROM sample_templates WHERE name = ?", name)
+        .fetch_one(&mut *tx)
+        .await?;
+    if exists > 0 {
+        bail!(ServiceError::Invalid(format!(
+            "template `{name}` already exists"
+        )));
+    }
+
+    let id = sqlx::query_scalar!(
+        r#"
+            INSERT INTO sample_templates (name, description, tags, custom_fields, created_by)
+            VALUES (?, ?, ?, ?, ?)
+            RETURNING id AS "id!"
+        "#,
+        name,
+        description,
+        tags,
+        custom_fields,
+        uid
+    )
+    .fetch_one(&mut *tx)
+    .await?;
+    let template = fetch_template(&mut tx, id).await?;
+    audit::record(
+        &mut tx,
+        Some(uid),
+        "template.created",
+        None,
+        None,
+        snapshot(&template),
+    )
+    .await?;
+    tx.commit().await?;
+    Ok(template)
+}
+
+/// Saves the description, tags and custom values of a sample the caller can
+/// see as a new template.
+pub async fn template_from_sample(
+    state: &WebState,
+    sample_id: i64,
+    name: String,
+    uid: i64,
+) -> Result<SampleTemplate> {
+    let sample = authorize(state, sample_id, uid, SampleRole::Viewer).await?;
+    let input = SampleTemplateInput {
+        name,
+        description: sample.description,
+        tags: sample.tags.0,
+        custom_fields: sample.custom_fields.0,
+    };
+    create_template(state, input, uid).await
+}
+
+/// Templates are shared by everyone, so only their creator or an admin may
+/// delete one.
+pub async fn delete_template(state: &WebState, id: i64, uid: i64) -> Result<()> {
+    let template = get_template(state, id).await?;
+    if template.created_by != uid {
+        let is_admin = sqlx::query_scalar!(
+            r#"SELECT is_admin AS "is_admin!: bool" FROM users WHERE id = ?"#,
+            uid
+        )
+        .fetch_one(&state.db)
+        .await?;
+        if !is_admin {
+            bail!(ServiceError::Forbidden(
+                "only the creator or an admin can delete a template"
+            ));
+        }
+    }
+    let mut tx = state.db.begin().await?;
+    sqlx::query!("DELETE FROM sample_templates WHERE id = ?", id)
+        .execute(&mut *tx)
+        .await?;
+    audit::record(
+        &mut tx,
+        Some(uid),
+        "template.deleted",
+        None,
+        snapshot(&template),
+        None,
+    )
+    .await?;
+    tx.commit().await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::custom_field::{CustomFieldInput, FieldType};
+    use crate::services::error::status_code;
+    use crate::testing;
+    use axum::http::StatusCode;
+    use serde_json::json;
+
+    fn template(name: &str, custom_fields: Value) -> SampleTemplateInput {
+        SampleTemplateInput {
+            name: name.to_string(),
+            description: Some(" ".into()),
+            tags: vec!["Wool".into(), "wool".into()],
+            custom_fields: custom_fields.as_object().unwrap().clone(),
+        }
+    }
+
+    #[tokio::test]
+    async fn templates_may_leave_required_values_out() {
+        let state = testing::state().await;
+        let uid = testing::user(&state.db, "ann@example.com").await;
+        let count = CustomFieldInput {
+            key: "count".into(),
+            label: "Count".into(),
+            field_type: FieldType::Integer,
+            required: true,
+            options: vec![],
+            pattern: None,
+            position: 0,
+        };
+        custom_field::create_field(&state, count, uid)
+            .await
+            .unwrap();
+
+        let empty = create_template(&state, template("Dye", json!({})), uid)
+            .await
+            .unwrap();
+        assert_eq!(empty.tags.0, ["Wool"]);
+        assert!(empty.description.is_none());
+        let filled = create_template(&state, template("Paint", json!({ "count": "4" })), uid)
+            .await
+            .unwrap();
+        assert_eq!(filled.custom_fields.0["count"], 4);
+
+        for bad in [
+            template("Dye", json!({})),
+            template("  ", json!({})),
+            template("Ink", json!({ "count": "four" })),
+            template("Ink", json!({ "colour": "red" })),
+        ] {
+            let err = create_template(&state, bad, uid).await.unwrap_err();
+            assert_eq!(status_code(&err), StatusCode::UNPROCESSABLE_ENTITY);
+        }
+        let names: Vec<_> = get_templates(&state)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|t| t.name)
+            .collect();
+        assert_eq!(names, ["Dye", "Paint"]);
+    }
+
+    #[tokio::test]
+    async fn saving_a_sample_as_a_template_needs_access() {
+        let state = testing::state().await;
+        let ann = testing::user(&state.db, "ann@example.com").await;
+        let bob = testing::user(&state.db, "bob@example.com").await;
+        let mut input = testing::input("Blue dye");
+        input.description = Some("Deep blue".into());
+        input.tags = Some(vec!["wool".into()]);
+        let sample = crate::services::sample::create_sample(&state, input, ann)
+            .await
+            .unwrap();
+
+        let err = template_from_sample(&state, sample.id, "Blue".into(), bob)
+            .await
+            .unwrap_err();
+        assert_eq!(status_code(&err), StatusCode::NOT_FOUND);
+        let saved = template_from_sample(&state, sample.id, "Blue".into(), ann)
+            .await
+            .unwrap();
+        assert_eq!(saved.description.as_deref(), Some("Deep blue"));
+        assert_eq!(saved.tags.0, ["wool"]);
+    }
+
+    #[tokio::test]
+    async fn only_the_creator_or_an_admin_deletes() {
+        let state = testing::state().await;
+        let ann = testing::user(&state.db, "ann@example.com").await;
+        let bob = testing::user(&state.db, "bob@example.com").await;
+        let admin = testing::user(&state.db, "admin@example.com").await;
+        sqlx::query!("UPDATE users SET is_admin = 1 WHERE id = ?", admin)
+            .execute(&state.db)
+            .await
+            .unwrap();
+        let first = create_template(&state, template("First", json!({})), ann)
+            .await
+            .unwrap();
+        let second = create_template(&state, template("Second", json!({})), ann)
+            .await
+            .unwrap();
+
+        let err = delete_template(&state, first.id, bob).await.unwrap_err();
+        assert_eq!(status_code(&err), StatusCode::FORBIDDEN);
+        delete_template(&state, first.id, ann).await.unwrap();
+        delete_template(&state, second.id, admin).await.unwrap();
+        assert!(get_templates(&state).await.unwrap().is_empty());
+    }
+}
@@ -0,0 +1,340 @@
+use anyhow::{bail, Result};
+use sqlx::{types::Json, SqliteConnection};
+
+use crate::models::{
+    sample::SampleQuery,
+    saved_view::{SampleColumn, SavedView, SavedViewInput},
+    state::WebState,
+};
+use crate::services::{error::ServiceError, sample::filtered_samples_query};
+
+pub const MAX_VIEW_NAME_LEN: usize = 100;
+
+pub async fn get_views(state: &WebState, user_id: i64) -> Result<Vec<SavedView>> {
+    let views = sqlx::query_as!(
+        SavedView,
+        r#"
+            SELECT
+                id         AS "id!",
+                user_id    AS "user_id!",
+                name       AS "name!",
+                filters    AS "filters!: Json<SampleQuery>",
+                columns    AS "columns!: Json<Vec<SampleColumn>>",
+                is_default AS "is_default!: bool",
+                created_at AS "created_at!",
+                updated_at AS "updated_at!"
+            FROM saved_views
+            WHERE user_id = ?
+            ORDER BY name COLLATE NOCASE
+        "#,
+        user_id
+    )
+    .fetch_all(&state.db)
+    .await?;
+    Ok(views)
+}
+
+async fn fetch_view(conn: &mut SqliteConnection, id: i64, user_id: i64) -> Result<SavedView> {
+    let view = sqlx::query_as!(
+        SavedView,
+        r#"
+            SELECT
+                id         AS "id!",
+                user_id    AS "user_id!",
+                name       AS "name!",
+                filters    AS "filters!: Json<SampleQuery>",
+                columns    AS "columns!: Json<Vec<SampleColumn>>",
+                is_default AS "is_default!: bool",
+                created_at AS "created_at!",
+                updated_at AS "updated_at!"
+            FROM saved_views
+            WHERE id = ? AND user_id = ?
+        "#,
+        id,
+        user_id
+    )
+    .fetch_optional(conn)
+    .await?;
+    view.ok_or_else(|| ServiceError::NotFound("saved view", id).into())
+}
+
+/// Views are private, so another user's view reads as not found.
+pub async fn get_view(state: &WebState, id: i64, user_id: i64) -> Result<SavedView> {
+    let mut conn = state.db.acquire().await?;
+    fetch_view(&mut conn, id, user_id).await
+}
+
+pub async fn get_default_view(state: &WebState, user_id: i64) -> Result<Option<SavedView>> {
+    let id = sqlx::query_scalar!(
+        r#"SELECT id AS "id!" FROM saved_views WHERE user_id = ? AND is_default = 1"#,
+        user_id
+    )
+    .fetch_optional(&state.db)
+    .await?;
+    match id {
+        Some(id) => get_view(state, id, user_id).await.map(Some),
+        None => Ok(None),
+    }
+}
+
+/// Checks the name and filters and tidies the input for storing. Paging
+/// cursors are dropped since a view always opens on its first page.
+fn clean_input(mut input: SavedViewInput, user_id: i64) -> Result<SavedViewInput> {
+    let name = input.name.trim();
+    if name.is_empty() {
+        bail!(ServiceError::Invalid("view name is required".into()));
+    }
+    if name.chars().count() > MAX_VIEW_NAME_LEN {
+        bail!(ServiceError::Invalid(format!(
+            "view name is longer than {MAX_VIEW_NAME_LEN} characters"
+        )));
+    }
+    input.name = name.to_string();
+    input.filters.cursor = None;
+    filtered_samples_query(&input.filters, user_id)?;
+    input.columns = SampleColumn::normalize(&input.columns);
+    Ok(input)
+}
+
+async fn clear_default(conn: &mut SqliteConnection, user_id: i64) -> Result<()> {
+    sqlx::query!(
+        "UPDATE saved_views SET is_default = 0 WHERE user_id = ? AND is_default = 1",
+        user_id
+    )
+    .execute(&mut *conn)
+    .await?;
+    Ok(())
+}
+
+/// Saves a view under `input.name`, replacing the user's view of that name if
+/// there is one.
+pub async fn save_view(state: &WebState, input: SavedViewInput, user_id: i64) -> Result<SavedView> {
+    let input = clean_input(input, user_id)?;
+    let filters = Json(&input.filters);
+    let columns = Json(&input.columns);
+
+    let mut tx = state.db.begin().await?;
+    if input.is_default {
+        clear_default(&mut tx, user_id).await?;
+    }
+    let id = sqlx::query_scalar!(
+        r#"
+            INSERT INTO saved_views (user_id, name, filters, columns, is_default)
+            VALUES (?, ?, ?, ?, ?)
+            ON CONFLICT (user_id, name) DO UPDATE SET
+                filters = excluded.filters,
+                columns = excluded.columns,
+                is_default = excluded.is_default,
+                updated_at = datetime('now')
+            RETURNING id AS "id!"
+        "#,
+        user_id,
+        input.name,
+        filters,
+        columns,
+        input.is_default
+    )
+    .fetch_one(&mut *tx)
+    .await?;
+    let view = fetch_view(&mut tx, id, user_id).await?;
+    tx.commit().await?;
+    Ok(view)
+}
+
+pub async fn update_view(
+    state: &WebState,
+    id: i64,
+    input: SavedViewInput,
+    user_id: i64,
+) -> Result<SavedView> {
+    let input = clean_input(input, user_id)?;
+    let filters = Json(&input.filters);
+    let columns = Json(&input.columns);
+
+    let mut tx = state.db.begin().await?;
+    fetch_view(&mut tx, id, user_id).await?;
+    let taken = sqlx::query_scalar!(
+        "SELECT COUNT(1) FROM saved_views WHERE user_id = ? AND name = ? AND id != ?",
+        user_id,
+        input.name,
+        id
+    )
+    .fetch_one(&mut *tx)
+    .await?;
+    if taken > 0 {
+        bail!(ServiceError::Invalid(format!(
+            "you already have a view called `{}`",
+            input.name
+        )));
+    }
+    if input.is_default {
+        clear_default(&mut tx, user_id).await?;
+    }
+    sqlx::query!(
+        r#"
+            UPDATE saved_views
+            SET name = ?, filters = ?, columns = ?, is_default = ?, updated_at = datetime('now')
+            WHERE id = ?
+        "#,
+        input.name,
+        filters,
+        columns,
+        input.is_default,
+        id
+    )
+    .execute(&mut *tx)
+    .await?;
+    let view = fetch_view(&mut tx, id, user_id).await?;
+    tx.commit().await?;
+    Ok(view)
+}
+
+/// Pins the view as the user's default, or unpins it.
+pub async fn set_default(
+    state: &WebState,
+    id: i64,
+    pinned: bool,
+    user_id: i64,
+) -> Result<SavedView> {
+    let mut tx = state.db.begin().await?;
+    fetch_view(&mut tx, id, user_id).await?;
+    if pinned {
+        clear_default(&mut tx, user_id).await?;
+    }
+    sqlx::query!(
+        "UPDATE saved_views SET is_default = ?, updated_at = datetime('now') WHERE id = ?",
+        pinned,
+        id
+    )
+    .execute(&mut *tx)
+    .await?;
+    let view = fetch_view(&mut tx, id, user_id).await?;
+    tx.commit().await?;
+    Ok(view)
+}
+
+pub async fn delete_view(state: &WebState, id: i64, user_id: i64) -> Result<()> {
+    let result = sqlx::query!(
+        "DELETE FROM saved_views WHERE id = ? AND user_id = ?",
+        id,
+        user_id
+    )
+    .execute(&state.db)
+    .await?;
+    if result.rows_affected() == 0 {
+        bail!(ServiceError::NotFound("saved view", id));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::sample::{SampleSort, SampleStatus};
+    use crate::services::error::status_code;
+    use crate::testing;
+    use axum::http::StatusCode;
+
+    fn view(name: &str, is_default: bool) -> SavedViewInput {
+        SavedViewInput {
+            name: name.to_string(),
+            filters: SampleQuery {
+                status: Some(SampleStatus::Active),
+                cursor: Some("stale".into()),
+                ..Default::default()
+            },
+            columns: vec![SampleColumn::Status, SampleColumn::Id, SampleColumn::Status],
+            is_default,
+        }
+    }
+
+    #[tokio::test]
+    async fn saving_a_name_again_replaces_the_view() {
+        let state = testing::state().await;
+        let uid = testing::user(&state.db, "ann@example.com").await;
+        let first = save_view(&state, view(" Active ", false), uid)
+            .await
+            .unwrap();
+        assert_eq!(first.name, "Active");
+        assert!(first.filters.cursor.is_none());
+        assert_eq!(first.columns.0, [SampleColumn::Id, SampleColumn::Status]);
+
+        let mut by_name = view("Active", false);
+        by_name.filters.sort = Some(SampleSort::Name);
+        by_name.columns = vec![];
+        let second = save_view(&state, by_name, uid).await.unwrap();
+        assert_eq!(second.id, first.id);
+        assert_eq!(second.filters.sort, Some(SampleSort::Name));
+        assert_eq!(second.columns.0, SampleColumn::DEFAULT);
+        assert_eq!(get_views(&state, uid).await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn only_one_view_is_the_default() {
+        let state = testing::state().await;
+        let uid = testing::user(&state.db, "ann@example.com").await;
+        let a = save_view(&state, view("A", true), uid).await.unwrap();
+        let b = save_view(&state, view("B", true), uid).await.unwrap();
+        let default = get_default_view(&state, uid).await.unwrap().unwrap();
+        assert_eq!(default.id, b.id);
+
+        set_default(&state, a.id, true, uid).await.unwrap();
+        let defaults: Vec<_> = get_views(&state, uid)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|v| (v.name, v.is_default))
+            .collect();
+        assert_eq!(defaults, [("A".into(), true), ("B".into(), false)]);
+        set_default(&state, a.id, false, uid).await.unwrap();
+        assert!(get_default_view(&state, uid).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn views_are_private_to_their_owner() {
+        let state = testing::state().await;
+        let ann = testing::user(&state.db, "ann@example.com").await;
+        let bob = testing::user(&state.db, "bob@example.com").await;
+        let mine = save_view(&state, view("Mine", true), ann).await.unwrap();
+
+        assert!(get_views(&state, bob).await.unwrap().is_empty());
+        assert!(get_default_view(&state, bob).await.unwrap().is_none());
+        for err in [
+            get_view(&state, mine.id, bob).await.unwrap_err(),
+            update_view(&state, mine.id, view("Taken", false), bob)
+                .await
+                .unwrap_err(),
+            set_default(&state, mine.id, false, bob).await.unwrap_err(),
+            delete_view(&state, mine.id, bob).await.unwrap_err(),
+        ] {
+            assert_eq!(status_code(&err), StatusCode::NOT_FOUND);
+        }
+        // the same name is free for someone else
+        save_view(&state, view("Mine", false), bob).await.unwrap();
+        delete_view(&state, mine.id, ann).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn names_and_filters_are_checked() {
+        let state = testing::state().await;
+        let uid = testing::user(&state.db, "ann@example.com").await;
+        save_view(&state, view("A", false), uid).await.unwrap();
+        let b = save_view(&state, view("B", false), uid).await.unwrap();
+
+        let mut bad_date = view("C", false);
+        bad_date.filters.created_after = Some("soon".into());
+        let long = view(&"x".repeat(MAX_VIEW_NAME_LEN + 1), false);
+        for input in [view(" ", false), long, bad_date] {
+            let err = save_view(&state, input, uid).await.unwrap_err();
+            assert_eq!(status_code(&err), StatusCode::UNPROCESSABLE_ENTITY);
+        }
+        let err = update_view(&state, b.id, view("A", false), uid)
+            .await
+            .unwrap_err();
+        assert_eq!(status_code(&err), StatusCode::UNPROCESSABLE_ENTITY);
+        let renamed = update_view(&state, b.id, view("Z", false), uid)
+            .await
+            .unwrap();
+        assert_eq!(renamed.name, "Z");
+    }
+}
@@ -0,0 +1,488 @@
+use anyhow::{bail, Result};
+use chrono::{DateTime, Duration, Utc};
+use sqlx::{SqliteConnection, SqlitePool};
+
+use crate::models::{
+    audit::Channel,
+    sample::{SampleInput, SampleStatus},
+    schedule::{ActionState, ScheduleInput, ScheduledAction},
+    share::SampleRole,
+    state::WebState,
+};
+use crate::services::{
+    audit::{self, snapshot, with_context, AuditContext},
+    error::ServiceError,
+    sample::{authorize, parse_datetime, update_sample_by_id},
+};
+
+pub const MAX_AFTER_DAYS: i64 = 3650;
+
+/// How many due actions one scheduler tick applies. The rest wait for the
+/// next tick.
+const BATCH_SIZE: i64 = 100;
+
+/// Formats a time the way SQLite's `datetime('now')` does.
+fn sqlite_time(at: DateTime<Utc>) -> String {
+    at.naive_utc().format("%Y-%m-%d %H:%M:%S").to_string()
+}
+
+pub async fn get_actions(
+    state: &WebState,
+    sample_id: i64,
+    uid: i64,
+) -> Result<Vec<ScheduledAction>> {
+    authorize(state, sample_id, uid, SampleRole::Viewer).await?;
+    let actions = sqlx::query_as!(
+        ScheduledAction,
+        r#"
+            SELECT
+                id          AS "id!",
+                sample_id   AS "sample_id!",
+                to_status   AS "to_status!: SampleStatus",
+                run_at      AS "run_at!",
+                state       AS "state!: ActionState",
+                error       AS "error?",
+                created_by  AS "created_by!",
+                created_at  AS "created_at!",
+                finished_at AS "finished_at?"
+            FROM scheduled_actions
+            WHERE sample_id = ?
+            ORDER BY run_at, id
+        "#,
+        sample_id
+    )
+    .fetch_all(&state.db)
+    .await?;
+    Ok(actions)
+}
+
+async fn get_action(db: &SqlitePool, id: i64) -> Result<ScheduledAction> {
+    let mut conn = db.acquire().await?;
+    fetch_action(&mut conn, id).await
+}
+
+async fn fetch_action(conn: &mut SqliteConnection, id: i64) -> Result<ScheduledAction> {
+    let action = sqlx::query_as!(
+        ScheduledAction,
+        r#"
+            SELECT
+                id          AS "id!",
+                sample_id   AS "sample_id!",
+                to_status   AS "to_status!: SampleStatus",
+                run_at      AS "run_at!",
+                state       AS "state!: ActionState",
+                error       AS "error?",
+                created_by  AS "created_by!",
+                created_at  AS "created_at!",
+                finished_at AS "finished_at?"
+            FROM scheduled_actions
+            WHERE id = ?
+        "#,
+        id
+    )
+    .fetch_optional(conn)
+    .await?;
+    action.ok_or_else(|| ServiceError::NotFound("scheduled action", id).into())
+}
+
+/// Works out when an action should run: at `run_at`, or `after_days` from now.
+fn run_at_for(input: &ScheduleInput) -> Result<String> {
+    let run_at = input
+        .run_at
+        .as_deref()
+        .map(str::trim)
+        .filter(|r| !r.is_empty());
+    match (run_at, input.after_days) {
+        (Some(raw), None) => {
+            let run_at = parse_datetime(raw)?;
+            if run_at <= sqlite_time(Utc::now()) {
+                bail!(ServiceError::Invalid("run_at must be in the future".into()));
+            }
+            Ok(run_at)
+        }
+        (None, Some(days)) if (1..=MAX_AFTER_DAYS).contains(&days) => {
+            Ok(sqlite_time(Utc::now() + Duration::days(days)))
+        }
+        (None, Some(_)) => bail!(ServiceError::Invalid(format!(
+            "after_days must be between 1 and {MAX_AFTER_DAYS}"
+        ))),
+        _ => bail!(ServiceError::Invalid(
+            "give either run_at or after_days".into()
+        )),
+    }
+}
+
+/// Schedules a status change. It is applied as `uid`, so it only goes through
+/// if they can still edit the sample when it comes due.
+pub async fn schedule_action(
+    state: &WebState,
+    sample_id: i64,
+    input: ScheduleInput,
+    uid: i64,
+) -> Result<ScheduledAction> {
+    authorize(state, sample_id, uid, SampleRole::Editor).await?;
+    let run_at = run_at_for(&input)?;
+    let mut tx = state.db.begin().await?;
+    let id = sqlx::query_scalar!(
+        r#"
+            INSERT INTO scheduled_actions (sample_id, to_status, run_at, created_by)
+            VALUES (?, ?, ?, ?)
+            RETURNING id AS "id!"
+        "#,
+        sample_id,
+        input.status,
+        run_at,
+        uid
+    )
+    .fetch_one(&mut *tx)
+    .await?;
+    let action = fetch_action(&mut tx, id).await?;
+    audit::record(
+        &mut tx,
+        Some(uid),
+        "schedule.created",
+        Some(sample_id),
+        None,
+        snapshot(&action),
+    )
+    .await?;
+    tx.commit().await?;
+    Ok(action)
+}
+
+pub async fn cancel_action(state: &WebState, sample_id: i64, id: i64, uid: i64) -> Result<()> {
+    authorize(state, sample_id, uid, SampleRole::Editor).await?;
+    let action = get_action(&state.db, id).await?;
+    if action.sample_id != sample_id {
+        bail!(ServiceError::NotFound("scheduled action", id));
+    }
+    let mut tx = state.db.begin().await?;
+    let result = sqlx::query!(
+        "UPDATE scheduled_actions SET state = 'cancelled', finished_at = datetime('now') WHERE id = ? AND state = 'pending'",
+        id
+    )
+    .execute(&mut *tx)
+    .await?;
+    if result.rows_affected() == 0 {
+        bail!(ServiceError::Invalid(format!(
+            "action is already {}",
+            action.state
+        )));
+    }
+    let cancelled = fetch_action(&mut tx, id).await?;
+    audit::record(
+        &mut tx,
+        Some(uid),
+        "schedule.cancelled",
+        Some(sample_id),
+        snapshot(&action),
+        snapshot(&cancelled),
+    )
+    .await?;
+    tx.commit().await?;
+    Ok(())
+}
+
+/// Moves a pending action to running. Only one caller can win this, which is
+/// what keeps an action from being applied twice.
+async fn claim(db: &SqlitePool, id: i64) -> Result<bool> {
+    let result = sqlx::query!(
+        "UPDATE scheduled_actions SET state = 'running' WHERE id = ? AND state = 'pending'",
+        id
+    )
+    .execute(db)
+    .await?;
+    Ok(result.rows_affected() == 1)
+}
+
+async fn finish(db: &SqlitePool, id: i64, state: ActionState, error: Option<String>) -> Result<()> {
+    sqlx::query!(
+        "UPDATE scheduled_actions SET state = ?, error = ?, finished_at = datetime('now') WHERE id = ?",
+        state,
+        error,
+        id
+    )
+    .execute(db)
+    .await?;
+    Ok(())
+}
+
+async fn current_status(db: &SqlitePool, sample_id: i64) -> Result<Option<SampleStatus>> {
+    let status = sqlx::query_scalar!(
+        r#"SELECT status AS "status!: SampleStatus" FROM samples WHERE id = ? AND deleted_at IS NULL"#,
+        sample_id
+    )
+    .fetch_optional(db)
+    .await?;
+    Ok(status)
+}
+
+/// Applies one claimed action through the normal update path, so it gets the
+/// same checks, revision and event as an edit by its creator.
+async fn apply(state: &WebState, action: &ScheduledAction) -> Result<()> {
+    let sample = match authorize(
+        state,
+        action.sample_id,
+        action.created_by,
+        SampleRole::Editor,
+    )
+    .await
+    {
+        Ok(sample) => sample,
+        Err(e) => {
+            return finish(
+                &state.db,
+                action.id,
+                ActionState::Failed,
+                Some(e.to_string()),
+            )
+            .await
+        }
+    };
+    if sample.status == action.to_status {
+        return finish(&state.db, action.id, ActionState::Done, None).await;
+    }
+
+    let input = SampleInput {
+        name: sample.name,
+        description: sample.description,
+        status: action.to_status,
+        tags: None,
+        version: Some(sample.version),
+        custom_fields: None,
+    };
+    match update_sample_by_id(state, input, action.sample_id, action.created_by).await {
+        Ok(_) => finish(&state.db, action.id, ActionState::Done, None).await,
+        Err(e)
+            if matches!(
+                e.downcast_ref::<ServiceError>(),
+                Some(ServiceError::VersionConflict { .. })
+            ) =>
+        {
+            // edited while we were applying: try again on the next tick
+            sqlx::query!(
+                "UPDATE scheduled_actions SET state = 'pending' WHERE id = ?",
+                action.id
+            )
+            .execute(&state.db)
+            .await?;
+            Ok(())
+        }
+        Err(e) => {
+            // the update may have committed with only the event failing
+            let state_after =
+                if current_status(&state.db, action.sample_id).await? == Some(action.to_status) {
+                    ActionState::Done
+                } else {
+                    ActionState::Failed
+                };
+            tracing::warn!(id = action.id, ?e, "scheduled action did not apply cleanly");
+            finish(&state.db, action.id, state_after, Some(e.to_string())).await
+        }
+    }
+}
+
+/// Claims and applies every action that has come due. Returns how many were
+/// claimed.
+pub async fn run_due_actions(state: &WebState) -> Result<usize> {
+    let due = sqlx::query_scalar!(
+        r#"SELECT id AS "id!" FROM scheduled_actions WHERE state = 'pending' AND run_at <= datetime('now') ORDER BY run_at, id LIMIT ?"#,
+        BATCH_SIZE
+    )
+    .fetch_all(&state.db)
+    .await?;
+
+    let mut claimed = 0;
+    for id in due {
+        if !claim(&state.db, id).await? {
+            continue;
+        }
+        claimed += 1;
+        let action = get_action(&state.db, id).await?;
+        let ctx = AuditContext {
+            channel: Channel::System,
+            request_id: Some(format!("scheduled-action-{id}")),
+        };
+        if let Err(e) = with_context(ctx, apply(state, &action)).await {
+            tracing::error!(id, ?e, "failed to apply scheduled action");
+            finish(&state.db, id, ActionState::Failed, Some(e.to_string())).await?;
+        }
+    }
+    Ok(claimed)
+}
+
+/// Settles actions left running by a restart. Ones whose change already landed
+/// are marked done; the rest go back to pending to be applied again.
+pub async fn recover_interrupted(db: &SqlitePool) -> Result<u64> {
+    let done = sqlx::query!(
+        r#"
+            UPDATE scheduled_actions SET state = 'done', finished_at = datetime('now')
+            WHERE state = 'running'
+              AND to_status = (SELECT status FROM samples WHERE samples.id = scheduled_actions.sample_id)
+        "#
+    )
+    .execute(db)
+    .await?;
+    let reset =
+        sqlx::query!("UPDATE scheduled_actions SET state = 'pending' WHERE state = 'running'")
+            .execute(db)
+            .await?;
+    Ok(done.rows_affected() + reset.rows_affected())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::{error::status_code, sample::delete_sample_by_id};
+    use crate::testing;
+    use axum::http::StatusCode;
+
+    /// A draft sample with an action to make it `to_status` that is already
+    /// due.
+    async fn due_action(state: &WebState, uid: i64, to_status: SampleStatus) -> ScheduledAction {
+        let sample = testing::sample(state, uid, "Blue dye").await;
+        let schedule = ScheduleInput {
+            status: to_status,
+            run_at: None,
+            after_days: Some(1),
+        };
+        let action = schedule_action(state, sample.id, schedule, uid)
+            .await
+            .unwrap();
+        sqlx::query!(
+            "UPDATE scheduled_actions SET run_at = datetime('now', '-1 minutes') WHERE id = ?",
+            action.id
+        )
+        .execute(&state.db)
+        .await
+        .unwrap();
+        action
+    }
+
+    #[tokio::test]
+    async fn due_action_is_applied_once() {
+        let state = testing::state().await;
+        let uid = testing::user(&state.db, "owner@example.com").await;
+        let action = due_action(&state, uid, SampleStatus::Active).await;
+
+        assert_eq!(run_due_actions(&state).await.unwrap(), 1);
+        let done = get_action(&state.db, action.id).await.unwrap();
+        assert_eq!(done.state, ActionState::Done);
+        assert!(done.finished_at.is_some());
+        assert_eq!(
+            current_status(&state.db, action.sample_id).await.unwrap(),
+            Some(SampleStatus::Active)
+        );
+
+        assert_eq!(run_due_actions(&state).await.unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn only_one_claim_wins() {
+        let state = testing::state().await;
+        let uid = testing::user(&state.db, "owner@example.com").await;
+        let action = due_action(&state, uid, SampleStatus::Active).await;
+
+        let (a, b) = tokio::join!(claim(&state.db, action.id), claim(&state.db, action.id));
+        assert!(a.unwrap() != b.unwrap());
+        // a claimed action is no longer due
+        assert_eq!(run_due_actions(&state).await.unwrap(), 0);
+        assert_eq!(
+            current_status(&state.db, action.sample_id).await.unwrap(),
+            Some(SampleStatus::Draft)
+        );
+    }
+
+    #[tokio::test]
+    async fn cancelled_action_is_not_applied() {
+        let state = testing::state().await;
+        let uid = testing::user(&state.db, "owner@example.com").await;
+        let action = due_action(&state, uid, SampleStatus::Active).await;
+
+        cancel_action(&state, action.sample_id, action.id, uid)
+            .await
+            .unwrap();
+        assert_eq!(run_due_actions(&state).await.unwrap(), 0);
+        assert_eq!(
+            current_status(&state.db, action.sample_id).await.unwrap(),
+            Some(SampleStatus::Draft)
+        );
+    }
+
+    #[tokio::test]
+    async fn interrupted_actions_are_settled() {
+        let state = testing::state().await;
+        let uid = testing::user(&state.db, "owner@example.com").await;
+        let landed = due_action(&state, uid, SampleStatus::Active).await;
+        let lost = due_action(&state, uid, SampleStatus::Archived).await;
+        sqlx::query!("UPDATE scheduled_actions SET state = 'running'")
+            .execute(&state.db)
+            .await
+            .unwrap();
+        // the first change committed before the restart, the second didn't
+        sqlx::query!(
+            "UPDATE samples SET status = 'active' WHERE id = ?",
+            landed.sample_id
+        )
+        .execute(&state.db)
+        .await
+        .unwrap();
+
+        assert_eq!(recover_interrupted(&state.db).await.unwrap(), 2);
+        let landed = get_action(&state.db, landed.id).await.unwrap();
+        assert_eq!(landed.state, ActionState::Done);
+        let lost = get_action(&state.db, lost.id).await.unwrap();
+        assert_eq!(lost.state, ActionState::Pending);
+
+        assert_eq!(run_due_actions(&state).await.unwrap(), 1);
+        assert_eq!(
+            current_status(&state.db, lost.sample_id).await.unwrap(),
+            Some(SampleStatus::Archived)
+        );
+    }
+
+    #[tokio::test]
+    async fn action_on_a_trashed_sample_fails() {
+        let state = testing::state().await;
+        let uid = testing::user(&state.db, "owner@example.com").await;
+        let action = due_action(&state, uid, SampleStatus::Active).await;
+        delete_sample_by_id(&state, action.sample_id, None, uid)
+            .await
+            .unwrap();
+
+        assert_eq!(run_due_actions(&state).await.unwrap(), 1);
+        let failed = get_action(&state.db, action.id).await.unwrap();
+        assert_eq!(failed.state, ActionState::Failed);
+        assert!(failed.error.is_some());
+    }
+
+    #[tokio::test]
+    async fn run_times_must_be_ahead_and_in_range() {
+        let state = testing::state().await;
+        let uid = testing::user(&state.db, "owner@example.com").await;
+        let sample = testing::sample(&state, uid, "Blue dye").await;
+        let at = |run_at: Option<&str>, after_days: Option<i64>| ScheduleInput {
+            status: SampleStatus::Active,
+            run_at: run_at.map(str::to_string),
+            after_days,
+        };
+
+        for bad in [
+            at(None, None),
+            at(Some("2000-01-01T00:00:00Z"), None),
+            at(None, Some(0)),
+            at(None, Some(MAX_AFTER_DAYS + 1)),
+            at(Some("2999-01-01T00:00:00Z"), Some(1)),
+        ] {
+            let err = schedule_action(&state, sample.id, bad, uid)
+                .await
+                .unwrap_err();
+            assert_eq!(status_code(&err), StatusCode::UNPROCESSABLE_ENTITY);
+        }
+        let action = schedule_action(&state, sample.id, at(Some("2999-01-01"), None), uid)
+            .await
+            .unwrap();
+        assert_eq!(action.state, ActionState::Pending);
+        assert!(action.run_at.starts_with("2999-01-01"));
+    }
+}
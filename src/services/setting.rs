@@ -0,0 +1,123 @@
+use anyhow::Result;
+use serde_json::json;
+use sqlx::SqliteConnection;
+
+use crate::models::{
+    setting::{AppSettings, AppSettingsUpdate},
+    state::WebState,
+    user::TokenPurpose,
+};
+use crate::services::audit;
+
+const MAGIC_LINKS_ENABLED: &str = "magic_links_enabled";
+
+async fn get_flag(conn: &mut SqliteConnection, key: &str, default: bool) -> Result<bool> {
+    let value = sqlx::query_scalar!("SELECT value FROM app_settings WHERE key = ?", key)
+        .fetch_optional(conn)
+        .await?;
+    Ok(value.map(|v| v == "true").unwrap_or(default))
+}
+
+async fn set_flag(conn: &mut SqliteConnection, key: &str, value: bool) -> Result<()> {
+    let value = value.to_string();
+    sqlx::query!(
+        r#"
+            INSERT INTO app_settings (key, value) VALUES (?, ?)
+            ON CONFLICT(key) DO UPDATE SET value = excluded.value, updated_at = datetime('now')
+        "#,
+        key,
+        value
+    )
+    .execute(conn)
+    .await?;
+    Ok(())
+}
+
+async fn load(conn: &mut SqliteConnection) -> Result<AppSettings> {
+    Ok(AppSettings {
+        magic_links_enabled: get_flag(conn, MAGIC_LINKS_ENABLED, false).await?,
+    })
+}
+
+pub async fn get_settings(state: &WebState) -> Result<AppSettings> {
+    let mut conn = state.db.acquire().await?;
+    load(&mut conn).await
+}
+
+pub async fn magic_links_enabled(state: &WebState) -> Result<bool> {
+    let mut conn = state.db.acquire().await?;
+    get_flag(&mut conn, MAGIC_LINKS_ENABLED, false).await
+}
+
+/// Applies the update and records it. Turning magic links off also voids any
+/// link already mailed.
+pub async fn update_settings(
+    state: &WebState,
+    update: AppSettingsUpdate,
+    actor_id: i64,
+) -> Result<AppSettings> {
+    let mut tx = state.db.begin().await?;
+    let before = load(&mut tx).await?;
+    if let Some(enabled) = update.magic_links_enabled {
+        set_flag(&mut tx, MAGIC_LINKS_ENABLED, enabled).await?;
+        if !enabled {
+            sqlx::query!(
+                "DELETE FROM user_tokens WHERE purpose = ?",
+                TokenPurpose::MagicLink
+            )
+            .execute(&mut *tx)
+            .await?;
+        }
+    }
+    let after = load(&mut tx).await?;
+    if after != before {
+        audit::record(
+            &mut tx,
+            Some(actor_id),
+            "settings.updated",
+            None,
+            Some(json!(before)),
+            Some(json!(after)),
+        )
+        .await?;
+    }
+    tx.commit().await?;
+    Ok(after)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::audit::AuditQuery;
+    use crate::testing;
+
+    #[tokio::test]
+    async fn only_real_changes_are_recorded() {
+        let state = testing::state().await;
+        let admin = testing::user(&state.db, "admin@example.com").await;
+        assert!(!get_settings(&state).await.unwrap().magic_links_enabled);
+
+        let on = || AppSettingsUpdate {
+            magic_links_enabled: Some(true),
+        };
+        assert!(
+            update_settings(&state, on(), admin)
+                .await
+                .unwrap()
+                .magic_links_enabled
+        );
+        update_settings(&state, on(), admin).await.unwrap();
+        update_settings(&state, AppSettingsUpdate::default(), admin)
+            .await
+            .unwrap();
+        assert!(magic_links_enabled(&state).await.unwrap());
+
+        let query = AuditQuery {
+            action: Some("settings.updated".into()),
+            ..Default::default()
+        };
+        let page = audit::get_entries(&state, &query).await.unwrap();
+        assert_eq!(page.items.len(), 1);
+        assert_eq!(page.items[0].actor_id, Some(admin));
+    }
+}
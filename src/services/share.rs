@@ -0,0 +1,361 @@
+use anyhow::{bail, Result};
+use sqlx::SqliteConnection;
+
+use crate::models::{
+    share::{SampleRole, SampleShare, ShareInput},
+    state::WebState,
+};
+use crate::services::{
+    audit::{self, snapshot},
+    error::ServiceError,
+    sample::{authorize, check_access},
+};
+
+pub async fn get_shares(
+    state: &WebState,
+    sample_id: i64,
+    user_id: i64,
+) -> Result<Vec<SampleShare>> {
+    authorize(state, sample_id, user_id, SampleRole::Viewer).await?;
+    let shares = sqlx::query_as!(
+        SampleShare,
+        r#"
+            SELECT
+                a.sample_id  AS "sample_id!",
+                a.user_id    AS "user_id!",
+                u.email      AS "email!",
+                a.role       AS "role!: SampleRole",
+                a.granted_by AS "granted_by!",
+                a.created_at AS "created_at!"
+            FROM sample_acl a
+            JOIN users u ON u.id = a.user_id
+            WHERE a.sample_id = ?
+            ORDER BY CASE a.role WHEN 'owner' THEN 0 WHEN 'editor' THEN 1 ELSE 2 END, u.email
+        "#,
+        sample_id
+    )
+    .fetch_all(&state.db)
+    .await?;
+    Ok(shares)
+}
+
+async fn fetch_share(
+    conn: &mut SqliteConnection,
+    sample_id: i64,
+    user_id: i64,
+) -> Result<Option<SampleShare>> {
+    let share = sqlx::query_as!(
+        SampleShare,
+        r#"
+            SELECT
+                a.sample_id  AS "sample_id!",
+                a.user_id    AS "user_id!",
+                u.email      AS "email!",
+                a.role       AS "role!: SampleRole",
+                a.granted_by AS "granted_by!",
+                a.created_at AS "created_at!"
+            FROM sample_acl a
+            JOIN users u ON u.id = a.user_id
+            WHERE a.sample_id = ? AND a.user_id = ?
+        "#,
+        sample_id,
+        user_id
+    )
+    .fetch_optional(conn)
+    .await?;
+    Ok(share)
+}
+
+/// Fails when taking `user_id`'s owner role away would leave the sample
+/// without any owner.
+async fn keep_an_owner(conn: &mut SqliteConnection, sample_id: i64, user_id: i64) -> Result<()> {
+    let other_owners = sqlx::query_scalar!(
+        "SELECT COUNT(1) FROM sample_acl WHERE sample_id = ? AND role = 'owner' AND user_id != ?",
+        sample_id,
+        user_id
+    )
+    .fetch_one(&mut *conn)
+    .await?;
+    if other_owners == 0 {
+        bail!(ServiceError::Invalid(
+            "a sample needs at least one owner".into()
+        ));
+    }
+    Ok(())
+}
+
+/// Grants the user with `input.email` a role on the sample, replacing any
+/// role they had. Only owners can share, and only with confirmed accounts.
+/// An unknown email and an unconfirmed one get the same error, so sharing
+/// can't be used to find out who has signed up.
+pub async fn share_sample(
+    state: &WebState,
+    sample_id: i64,
+    input: ShareInput,
+    actor_id: i64,
+) -> Result<SampleShare> {
+    authorize(state, sample_id, actor_id, SampleRole::Owner).await?;
+    let email = input.email.trim();
+    let user_id = sqlx::query_scalar!(
+        r#"
+            SELECT id FROM users
+            WHERE email = ? COLLATE NOCASE AND email_verified_at IS NOT NULL
+        "#,
+        email
+    )
+    .fetch_optional(&state.db)
+    .await?
+    .ok_or_else(|| {
+        ServiceError::Invalid("can't share with that email, it needs a confirmed account".into())
+    })?;
+
+    let mut tx = state.db.begin().await?;
+    if input.role != SampleRole::Owner {
+        keep_an_owner(&mut tx, sample_id, user_id).await?;
+    }
+    let before = fetch_share(&mut tx, sample_id, user_id).await?;
+    sqlx::query!(
+        r#"
+            INSERT INTO sample_acl (sample_id, user_id, role, granted_by)
+            VALUES (?, ?, ?, ?)
+            ON CONFLICT (sample_id, user_id)
+            DO UPDATE SET role = excluded.role, granted_by = excluded.granted_by
+        "#,
+        sample_id,
+        user_id,
+        input.role,
+        actor_id
+    )
+    .execute(&mut *tx)
+    .await?;
+    let share = fetch_share(&mut tx, sample_id, user_id)
+        .await?
+        .ok_or(ServiceError::NotFound("share", user_id))?;
+    audit::record(
+        &mut tx,
+        Some(actor_id),
+        "share.granted",
+        Some(sample_id),
+        before.as_ref().and_then(snapshot),
+        snapshot(&share),
+    )
+    .await?;
+    tx.commit().await?;
+    Ok(share)
+}
+
+/// Removes a user from the ACL. Owners can remove anyone, and anyone can
+/// remove themselves, as long as an owner is left.
+pub async fn unshare_sample(
+    state: &WebState,
+    sample_id: i64,
+    user_id: i64,
+    actor_id: i64,
+) -> Result<()> {
+    let mut tx = state.db.begin().await?;
+    if user_id != actor_id {
+        check_access(&mut tx, sample_id, actor_id, SampleRole::Owner).await?;
+    }
+    keep_an_owner(&mut tx, sample_id, user_id).await?;
+    let share = fetch_share(&mut tx, sample_id, user_id)
+        .await?
+        .ok_or(ServiceError::NotFound("share", user_id))?;
+    sqlx::query!(
+        "DELETE FROM sample_acl WHERE sample_id = ? AND user_id = ?",
+        sample_id,
+        user_id
+    )
+    .execute(&mut *tx)
+    .await?;
+    audit::record(
+        &mut tx,
+        Some(actor_id),
+        "share.revoked",
+        Some(sample_id),
+        snapshot(&share),
+        None,
+    )
+    .await?;
+    tx.commit().await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::sample::SampleQuery;
+    use crate::services::{
+        error::status_code,
+        sample::{delete_sample_by_id, get_samples, update_sample_by_id},
+    };
+    use crate::testing;
+    use axum::http::StatusCode;
+
+    fn invite(email: &str, role: SampleRole) -> ShareInput {
+        ShareInput {
+            email: email.to_string(),
+            role,
+        }
+    }
+
+    #[tokio::test]
+    async fn strangers_can_neither_list_nor_open_a_sample() {
+        let state = testing::state().await;
+        let ann = testing::user(&state.db, "ann@example.com").await;
+        let bob = testing::user(&state.db, "bob@example.com").await;
+        let s = testing::sample(&state, ann, "Private").await;
+
+        let page = get_samples(&state, &SampleQuery::default(), bob)
+            .await
+            .unwrap();
+        assert!(page.items.is_empty());
+        let err = authorize(&state, s.id, bob, SampleRole::Viewer)
+            .await
+            .unwrap_err();
+        assert_eq!(status_code(&err), StatusCode::NOT_FOUND);
+        let err = get_shares(&state, s.id, bob).await.unwrap_err();
+        assert_eq!(status_code(&err), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn only_confirmed_accounts_can_be_shared_with() {
+        let state = testing::state().await;
+        let ann = testing::user(&state.db, "ann@example.com").await;
+        let bob = testing::user(&state.db, "bob@example.com").await;
+        sqlx::query!("INSERT INTO users (email, password_hash) VALUES ('new@example.com', 'x')")
+            .execute(&state.db)
+            .await
+            .unwrap();
+        let s = testing::sample(&state, ann, "Shared").await;
+
+        let mut errors = Vec::new();
+        for email in ["new@example.com", "nobody@example.com"] {
+            let err = share_sample(&state, s.id, invite(email, SampleRole::Viewer), ann)
+                .await
+                .unwrap_err();
+            assert_eq!(status_code(&err), StatusCode::UNPROCESSABLE_ENTITY);
+            errors.push(err.to_string());
+        }
+        assert_eq!(errors[0], errors[1]);
+
+        let share = share_sample(
+            &state,
+            s.id,
+            invite("Bob@Example.com", SampleRole::Viewer),
+            ann,
+        )
+        .await
+        .unwrap();
+        assert_eq!(share.user_id, bob);
+    }
+
+    #[tokio::test]
+    async fn each_role_adds_to_the_one_before() {
+        let state = testing::state().await;
+        let ann = testing::user(&state.db, "ann@example.com").await;
+        let bob = testing::user(&state.db, "bob@example.com").await;
+        let s = testing::sample(&state, ann, "Shared").await;
+
+        share_sample(
+            &state,
+            s.id,
+            invite("bob@example.com", SampleRole::Viewer),
+            ann,
+        )
+        .await
+        .unwrap();
+        assert!(authorize(&state, s.id, bob, SampleRole::Viewer)
+            .await
+            .is_ok());
+        let err = update_sample_by_id(&state, testing::input("Mine"), s.id, bob)
+            .await
+            .unwrap_err();
+        assert_eq!(status_code(&err), StatusCode::FORBIDDEN);
+
+        let share = share_sample(
+            &state,
+            s.id,
+            invite(" bob@example.com ", SampleRole::Editor),
+            ann,
+        )
+        .await
+        .unwrap();
+        assert_eq!((share.user_id, share.role), (bob, SampleRole::Editor));
+        update_sample_by_id(&state, testing::input("Edited"), s.id, bob)
+            .await
+            .unwrap();
+        let err = delete_sample_by_id(&state, s.id, None, bob)
+            .await
+            .unwrap_err();
+        assert_eq!(status_code(&err), StatusCode::FORBIDDEN);
+        let err = share_sample(
+            &state,
+            s.id,
+            invite("ann@example.com", SampleRole::Viewer),
+            bob,
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(status_code(&err), StatusCode::FORBIDDEN);
+        assert_eq!(get_shares(&state, s.id, bob).await.unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn the_last_owner_cannot_leave_or_step_down() {
+        let state = testing::state().await;
+        let ann = testing::user(&state.db, "ann@example.com").await;
+        let bob = testing::user(&state.db, "bob@example.com").await;
+        let s = testing::sample(&state, ann, "Shared").await;
+
+        let err = share_sample(
+            &state,
+            s.id,
+            invite("ann@example.com", SampleRole::Editor),
+            ann,
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(status_code(&err), StatusCode::UNPROCESSABLE_ENTITY);
+        let err = unshare_sample(&state, s.id, ann, ann).await.unwrap_err();
+        assert_eq!(status_code(&err), StatusCode::UNPROCESSABLE_ENTITY);
+
+        share_sample(
+            &state,
+            s.id,
+            invite("bob@example.com", SampleRole::Owner),
+            ann,
+        )
+        .await
+        .unwrap();
+        unshare_sample(&state, s.id, ann, ann).await.unwrap();
+        let shares = get_shares(&state, s.id, bob).await.unwrap();
+        assert_eq!(
+            shares
+                .iter()
+                .map(|s| (s.user_id, s.role))
+                .collect::<Vec<_>>(),
+            [(bob, SampleRole::Owner)]
+        );
+    }
+
+    #[tokio::test]
+    async fn viewers_can_remove_only_themselves() {
+        let state = testing::state().await;
+        let ann = testing::user(&state.db, "ann@example.com").await;
+        let bob = testing::user(&state.db, "bob@example.com").await;
+        let cat = testing::user(&state.db, "cat@example.com").await;
+        let s = testing::sample(&state, ann, "Shared").await;
+        testing::share(&state, s.id, ann, "bob@example.com", SampleRole::Viewer).await;
+        testing::share(&state, s.id, ann, "cat@example.com", SampleRole::Viewer).await;
+
+        let err = unshare_sample(&state, s.id, cat, bob).await.unwrap_err();
+        assert_eq!(status_code(&err), StatusCode::FORBIDDEN);
+        unshare_sample(&state, s.id, bob, bob).await.unwrap();
+        let err = authorize(&state, s.id, bob, SampleRole::Viewer)
+            .await
+            .unwrap_err();
+        assert_eq!(status_code(&err), StatusCode::NOT_FOUND);
+        let err = unshare_sample(&state, s.id, bob, ann).await.unwrap_err();
+        assert_eq!(status_code(&err), StatusCode::NOT_FOUND);
+    }
+}
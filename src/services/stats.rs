@@ -0,0 +1,367 @@
+use anyhow::{bail, Result};
+use chrono::{Datelike, Days, Months, NaiveDate, Utc};
+use std::collections::HashMap;
+
+use crate::models::{
+    sample::SampleStatus,
+    state::WebState,
+    stats::{Bucket, BucketCount, CreatorCount, SampleStats, StatsQuery, StatusCount},
+};
+use crate::services::{error::ServiceError, sample::VISIBLE_TO_USER};
+
+/// How many of the most prolific creators are listed.
+pub const TOP_CREATORS: i64 = 10;
+pub const MAX_BUCKETS: usize = 400;
+
+fn bucket_start(bucket: Bucket, date: NaiveDate) -> NaiveDate {
+    match bucket {
+        Bucket::Day => date,
+        Bucket::Week => date - Days::new(date.weekday().num_days_from_monday().into()),
+        Bucket::Month => date.with_day(1).unwrap(),
+    }
+}
+
+fn step(bucket: Bucket, date: NaiveDate, n: u32) -> NaiveDate {
+    match bucket {
+        Bucket::Day => date + Days::new(n.into()),
+        Bucket::Week => date + Days::new(u64::from(n) * 7),
+        Bucket::Month => date + Months::new(n),
+    }
+}
+
+fn step_back(bucket: Bucket, date: NaiveDate, n: u32) -> NaiveDate {
+    match bucket {
+        Bucket::Day => date - Days::new(n.into()),
+        Bucket::Week => date - Days::new(u64::from(n) * 7),
+        Bucket::Month => date - Months::new(n),
+    }
+}
+
+/// SQL for the start of the bucket `column` falls in, matching `bucket_start`.
+fn bucket_sql(bucket: Bucket, column: &str) -> String {
+    match bucket {
+        Bucket::Day => format!("date({column})"),
+        Bucket::Week => format!("date({column}, 'weekday 0', '-6 days')"),
+        Bucket::Month => format!("strftime('%Y-%m-01', {column})"),
+    }
+}
+
+fn parse_date(raw: &str) -> Result<NaiveDate> {
+    NaiveDate::parse_from_str(raw.trim(), "%Y-%m-%d").map_err(|_| {
+        ServiceError::Invalid(format!("invalid date: {raw}, expected YYYY-MM-DD")).into()
+    })
+}
+
+/// Every bucket start from `since` through `until`.
+fn bucket_range(bucket: Bucket, since: NaiveDate, until: NaiveDate) -> Result<Vec<NaiveDate>> {
+    if since > until {
+        bail!(ServiceError::Invalid(
+            "since must not be after until".into()
+        ));
+    }
+    let mut buckets = Vec::new();
+    let mut at = since;
+    while at <= until {
+        if buckets.len() == MAX_BUCKETS {
+            bail!(ServiceError::Invalid(format!(
+                "that range has more than {MAX_BUCKETS} {bucket} buckets, use a wider bucket"
+            )));
+        }
+        buckets.push(at);
+        at = step(bucket, at, 1);
+    }
+    Ok(buckets)
+}
+
+/// Counts rows per bucket of `column`, filling empty buckets with 0. `from` is
+/// a `FROM ... WHERE` clause whose only placeholder is the user id.
+async fn histogram(
+    state: &WebState,
+    user_id: i64,
+    bucket: Bucket,
+    buckets: &[NaiveDate],
+    from: &str,
+    column: &str,
+) -> Result<Vec<BucketCount>> {
+    let (first, last) = (buckets[0], buckets[buckets.len() - 1]);
+    let sql = format!(
+        "SELECT {bucket_expr} AS bucket, COUNT(1) AS count {from} \
+         AND {column} >= ? AND {column} < ? GROUP BY 1",
+        bucket_expr = bucket_sql(bucket, column),
+    );
+    let rows: Vec<BucketCount> = sqlx::query_as(&sql)
+        .bind(user_id)
+        .bind(first.to_string())
+        .bind(step(bucket, last, 1).to_string())
+        .fetch_all(&state.db)
+        .await?;
+    let counts: HashMap<String, i64> = rows.into_iter().map(|r| (r.bucket, r.count)).collect();
+    Ok(buckets
+        .iter()
+        .map(|d| {
+            let bucket = d.to_string();
+            let count = counts.get(&bucket).copied().unwrap_or(0);
+            BucketCount { bucket, count }
+        })
+        .collect())
+}
+
+pub async fn get_stats(state: &WebState, query: &StatsQuery, user_id: i64) -> Result<SampleStats> {
+    let bucket = query.bucket.unwrap_or_default();
+    let until = match &query.until {
+        Some(raw) => parse_date(raw)?,
+        None => Utc::now().date_naive(),
+    };
+    let since = match &query.since {
+        Some(raw) => bucket_start(bucket, parse_date(raw)?),
+        None => step_back(
+            bucket,
+            bucket_start(bucket, until),
+            bucket.default_span() - 1,
+        ),
+    };
+    let buckets = bucket_range(bucket, since, until)?;
+
+    let counts = sqlx::query_as!(
+        StatusCount,
+        r#"
+            SELECT status AS "status!: SampleStatus", COUNT(1) AS "count!: i64"
+            FROM samples
+            WHERE deleted_at IS NULL
+              AND EXISTS (SELECT 1 FROM sample_acl acl WHERE acl.sample_id = samples.id AND acl.user_id = ?)
+            GROUP BY status
+        "#,
+        user_id
+    )
+    .fetch_all(&state.db)
+    .await?;
+    let by_status: Vec<StatusCount> = SampleStatus::ALL
+        .into_iter()
+        .map(|status| StatusCount {
+            status,
+            count: counts
+                .iter()
+                .find(|c| c.status == status)
+                .map_or(0, |c| c.count),
+        })
+        .collect();
+    let total = by_status.iter().map(|c| c.count).sum();
+
+    let by_creator = sqlx::query_as!(
+        CreatorCount,
+        r#"
+            SELECT u.id AS "user_id!", u.email AS "email!", COUNT(1) AS "count!: i64"
+            FROM samples
+            JOIN users u ON u.id = samples.created_by
+            WHERE samples.deleted_at IS NULL
+              AND EXISTS (SELECT 1 FROM sample_acl acl WHERE acl.sample_id = samples.id AND acl.user_id = ?)
+            GROUP BY u.id
+            ORDER BY 3 DESC, u.email
+            LIMIT ?
+        "#,
+        user_id,
+        TOP_CREATORS
+    )
+    .fetch_all(&state.db)
+    .await?;
+
+    let live_samples = format!("FROM samples WHERE deleted_at IS NULL AND {VISIBLE_TO_USER}?)");
+    let created = histogram(
+        state,
+        user_id,
+        bucket,
+        &buckets,
+        &live_samples,
+        "samples.created_at",
+    )
+    .await?;
+    // every revision after the first is an update
+    let revisions = format!(
+        "FROM sample_revisions r JOIN samples ON samples.id = r.sample_id \
+         WHERE r.revision > 1 AND samples.deleted_at IS NULL AND {VISIBLE_TO_USER}?)"
+    );
+    let updated = histogram(state, user_id, bucket, &buckets, &revisions, "r.created_at").await?;
+
+    Ok(SampleStats {
+        total,
+        by_status,
+        by_creator,
+        bucket,
+        since: since.to_string(),
+        until: until.to_string(),
+        created,
+        updated,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::share::SampleRole;
+    use crate::services::{
+        error::status_code,
+        sample::{create_sample, delete_sample_by_id, update_sample_by_id},
+    };
+    use crate::testing;
+    use axum::http::StatusCode;
+
+    fn counts(buckets: &[BucketCount]) -> Vec<(&str, i64)> {
+        buckets
+            .iter()
+            .map(|b| (b.bucket.as_str(), b.count))
+            .collect()
+    }
+
+    fn range(bucket: Bucket, since: &str, until: &str) -> StatsQuery {
+        StatsQuery {
+            bucket: Some(bucket),
+            since: Some(since.into()),
+            until: Some(until.into()),
+        }
+    }
+
+    #[tokio::test]
+    async fn totals_count_only_live_samples_the_user_sees() {
+        let state = testing::state().await;
+        let ann = testing::user(&state.db, "ann@example.com").await;
+        let bob = testing::user(&state.db, "bob@example.com").await;
+        testing::sample(&state, ann, "draft").await;
+        let mut active = testing::input("active");
+        active.status = SampleStatus::Active;
+        create_sample(&state, active, ann).await.unwrap();
+        let trashed = testing::sample(&state, ann, "trashed").await;
+        delete_sample_by_id(&state, trashed.id, None, ann)
+            .await
+            .unwrap();
+        testing::sample(&state, bob, "private").await;
+        let shared = testing::sample(&state, bob, "shared").await;
+        testing::share(
+            &state,
+            shared.id,
+            bob,
+            "ann@example.com",
+            SampleRole::Viewer,
+        )
+        .await;
+
+        let stats = get_stats(&state, &StatsQuery::default(), ann)
+            .await
+            .unwrap();
+        assert_eq!(stats.total, 3);
+        let by_status: Vec<_> = stats
+            .by_status
+            .iter()
+            .map(|c| (c.status, c.count))
+            .collect();
+        assert_eq!(
+            by_status,
+            [
+                (SampleStatus::Draft, 2),
+                (SampleStatus::Active, 1),
+                (SampleStatus::Archived, 0)
+            ]
+        );
+        let by_creator: Vec<_> = stats
+            .by_creator
+            .iter()
+            .map(|c| (c.email.as_str(), c.count))
+            .collect();
+        assert_eq!(by_creator, [("ann@example.com", 2), ("bob@example.com", 1)]);
+        assert_eq!(stats.created.len(), Bucket::Day.default_span() as usize);
+    }
+
+    #[tokio::test]
+    async fn histograms_have_a_zero_for_every_empty_bucket() {
+        let state = testing::state().await;
+        let uid = testing::user(&state.db, "ann@example.com").await;
+        let first = testing::sample(&state, uid, "first").await;
+        let second = testing::sample(&state, uid, "second").await;
+        update_sample_by_id(&state, testing::input("renamed"), second.id, uid)
+            .await
+            .unwrap();
+        for (id, at) in [
+            (first.id, "2026-03-02 10:00:00"),
+            (second.id, "2026-03-15 23:59:59"),
+        ] {
+            sqlx::query("UPDATE samples SET created_at = ? WHERE id = ?")
+                .bind(at)
+                .bind(id)
+                .execute(&state.db)
+                .await
+                .unwrap();
+            sqlx::query("UPDATE sample_revisions SET created_at = ? WHERE sample_id = ?")
+                .bind(at)
+                .bind(id)
+                .execute(&state.db)
+                .await
+                .unwrap();
+        }
+
+        let stats = get_stats(
+            &state,
+            &range(Bucket::Week, "2026-03-04", "2026-03-20"),
+            uid,
+        )
+        .await
+        .unwrap();
+        assert_eq!(stats.since, "2026-03-02");
+        assert_eq!(
+            counts(&stats.created),
+            [("2026-03-02", 1), ("2026-03-09", 1), ("2026-03-16", 0)]
+        );
+        assert_eq!(
+            counts(&stats.updated),
+            [("2026-03-02", 0), ("2026-03-09", 1), ("2026-03-16", 0)]
+        );
+
+        let stats = get_stats(
+            &state,
+            &range(Bucket::Month, "2026-02-10", "2026-03-01"),
+            uid,
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            counts(&stats.created),
+            [("2026-02-01", 0), ("2026-03-01", 2)]
+        );
+    }
+
+    #[tokio::test]
+    async fn week_buckets_start_on_monday_in_sql_too() {
+        let state = testing::state().await;
+        // a Monday, a Wednesday and a Sunday of the same week
+        for day in ["2026-10-12", "2026-10-14", "2026-10-18"] {
+            let date = parse_date(day).unwrap();
+            let sql: String =
+                sqlx::query_scalar(&format!("SELECT {}", bucket_sql(Bucket::Week, "?")))
+                    .bind(day)
+                    .fetch_one(&state.db)
+                    .await
+                    .unwrap();
+            assert_eq!(bucket_start(Bucket::Week, date).to_string(), "2026-10-12");
+            assert_eq!(sql, "2026-10-12", "{day}");
+        }
+    }
+
+    #[tokio::test]
+    async fn backwards_and_oversized_ranges_are_rejected() {
+        let state = testing::state().await;
+        let uid = testing::user(&state.db, "ann@example.com").await;
+        for query in [
+            range(Bucket::Day, "2026-03-02", "2026-03-01"),
+            range(Bucket::Day, "2020-01-01", "2026-01-01"),
+            range(Bucket::Day, "March", "2026-03-01"),
+        ] {
+            let err = get_stats(&state, &query, uid).await.unwrap_err();
+            assert_eq!(status_code(&err), StatusCode::UNPROCESSABLE_ENTITY);
+        }
+        assert!(get_stats(
+            &state,
+            &range(Bucket::Month, "2020-01-01", "2026-01-01"),
+            uid
+        )
+        .await
+        .is_ok());
+    }
+}
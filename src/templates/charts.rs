@@ -0,0 +1,113 @@
+// Small server-rendered SVG charts. The output is inserted with `|safe`, so
+// every label goes through `escape`.
+
+use std::fmt::Write;
+
+const BAR_COLOR: &str = "#334155";
+const AXIS_COLOR: &str = "#cbd5e1";
+const TEXT_COLOR: &str = "#64748b";
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
+
+/// A column chart, one bar per `(label, value)` in order. Only every few
+/// labels are printed so they don't overlap; each bar has a hover title.
+pub fn column_chart(points: &[(String, i64)]) -> String {
+    const WIDTH: f64 = 720.0;
+    const HEIGHT: f64 = 200.0;
+    const LEFT: f64 = 36.0;
+    const BOTTOM: f64 = 24.0;
+    const TOP: f64 = 8.0;
+    const MAX_LABELS: usize = 8;
+
+    let plot_w = WIDTH - LEFT;
+    let plot_h = HEIGHT - BOTTOM - TOP;
+    let max = points.iter().map(|(_, v)| *v).max().unwrap_or(0).max(1);
+    let slot = plot_w / points.len().max(1) as f64;
+    let bar_w = (slot * 0.8).max(1.0);
+    let label_every = points.len().div_ceil(MAX_LABELS).max(1);
+
+    let mut svg = format!(
+        r#"<svg viewBox="0 0 {WIDTH} {HEIGHT}" class="w-full" role="img" xmlns="http://www.w3.org/2000/svg">"#
+    );
+    let base = TOP + plot_h;
+    let _ = write!(
+        svg,
+        r#"<line x1="{LEFT}" y1="{base}" x2="{WIDTH}" y2="{base}" stroke="{AXIS_COLOR}"/>"#
+    );
+    let _ = write!(
+        svg,
+        r#"<text x="{x}" y="{y}" font-size="10" fill="{TEXT_COLOR}" text-anchor="end">{max}</text>"#,
+        x = LEFT - 4.0,
+        y = TOP + 8.0,
+    );
+    let _ = write!(
+        svg,
+        r#"<text x="{x}" y="{base}" font-size="10" fill="{TEXT_COLOR}" text-anchor="end">0</text>"#,
+        x = LEFT - 4.0,
+    );
+    for (i, (label, value)) in points.iter().enumerate() {
+        let label = escape(label);
+        let h = plot_h * *value as f64 / max as f64;
+        let x = LEFT + slot * i as f64 + (slot - bar_w) / 2.0;
+        let _ = write!(
+            svg,
+            r#"<rect x="{x:.1}" y="{y:.1}" width="{bar_w:.1}" height="{h:.1}" fill="{BAR_COLOR}"><title>{label}: {value}</title></rect>"#,
+            y = base - h,
+        );
+        if i % label_every == 0 {
+            let _ = write!(
+                svg,
+                r#"<text x="{x:.1}" y="{y}" font-size="10" fill="{TEXT_COLOR}">{label}</text>"#,
+                y = HEIGHT - 6.0,
+            );
+        }
+    }
+    svg.push_str("</svg>");
+    svg
+}
+
+/// Horizontal bars with the label on the left and the value on the right,
+/// for short category lists.
+pub fn bar_list(rows: &[(String, i64)]) -> String {
+    const WIDTH: f64 = 720.0;
+    const ROW: f64 = 24.0;
+    const LABEL_W: f64 = 200.0;
+    const VALUE_W: f64 = 48.0;
+
+    let height = ROW * rows.len().max(1) as f64;
+    let max = rows.iter().map(|(_, v)| *v).max().unwrap_or(0).max(1);
+    let plot_w = WIDTH - LABEL_W - VALUE_W;
+
+    let mut svg = format!(
+        r#"<svg viewBox="0 0 {WIDTH} {height}" class="w-full" role="img" xmlns="http://www.w3.org/2000/svg">"#
+    );
+    for (i, (label, value)) in rows.iter().enumerate() {
+        let label = escape(label);
+        let y = ROW * i as f64;
+        let w = plot_w * *value as f64 / max as f64;
+        let _ = write!(
+            svg,
+            r#"<text x="0" y="{ty}" font-size="12" fill="{TEXT_COLOR}">{label}</text>"#,
+            ty = y + 16.0,
+        );
+        let _ = write!(
+            svg,
+            r#"<rect x="{LABEL_W}" y="{ry}" width="{w:.1}" height="16" fill="{BAR_COLOR}"><title>{label}: {value}</title></rect>"#,
+            ry = y + 4.0,
+        );
+        let _ = write!(
+            svg,
+            r#"<text x="{vx:.1}" y="{ty}" font-size="12" fill="{TEXT_COLOR}">{value}</text>"#,
+            vx = LABEL_W + w + 6.0,
+            ty = y + 16.0,
+        );
+    }
+    svg.push_str("</svg>");
+    svg
+}
@@ -1,6 +1,29 @@
 use askama::Template;
+use std::collections::HashMap;
 
-use crate::{models::sample::Sample, web::auth::SESSION_USER_ID};
+pub mod charts;
+
+use crate::{
+    models::{
+        api_token::{ApiToken, NewApiToken, TokenScope},
+        attachment::Attachment,
+        audit::{AuditEntry, AuditQuery, Channel},
+        comment::ThreadedComment,
+        custom_field::{CustomField, FieldType},
+        import::ImportReport,
+        notification::{Notification, NotificationQuery},
+        revision::{RevisionDiff, SampleRevision},
+        sample::{Sample, SampleInput, SampleQuery, SampleSearchHit, SampleStatus},
+        sample_template::SampleTemplate,
+        saved_view::{list_query, SampleColumn, SavedView},
+        schedule::{ActionState, ScheduledAction},
+        setting::AppSettings,
+        share::{SampleRole, SampleShare},
+        stats::{Bucket, SampleStats, StatsQuery},
+    },
+    services::error::status_code,
+    web::auth::SESSION_USER_ID,
+};
 
 #[derive(Template)]
 #[template(path = "error_500.html")]
@@ -26,6 +49,106 @@ pub struct Error403Tmpl {
 pub struct SamplesListTmpl {
     pub ctx: BaseCtx,
     pub samples: Vec<Sample>,
+    pub next_page: Option<String>,
+    pub query: SampleQuery,
+    pub columns: Vec<SampleColumn>,
+    pub all_columns: Vec<SampleColumn>,
+    pub views: Vec<SavedView>,
+    /// The saved view the list was opened from, if any.
+    pub view_id: Option<i64>,
+}
+
+impl SamplesListTmpl {
+    pub fn column_shown(&self, column: &SampleColumn) -> bool {
+        self.columns.contains(column)
+    }
+
+    pub fn current_view(&self) -> Option<&SavedView> {
+        self.views.iter().find(|v| Some(v.id) == self.view_id)
+    }
+
+    /// The filters and columns on screen, for saving as a view.
+    pub fn list_query(&self) -> String {
+        let filters = SampleQuery {
+            cursor: None,
+            ..self.query.clone()
+        };
+        list_query(&filters, &self.columns)
+    }
+
+    /// The filters on screen as pairs, carried along when picking columns.
+    pub fn filter_pairs(&self) -> Vec<(String, String)> {
+        let filters = SampleQuery {
+            cursor: None,
+            ..self.query.clone()
+        };
+        serde_urlencoded::to_string(&filters)
+            .ok()
+            .and_then(|q| serde_urlencoded::from_str(&q).ok())
+            .unwrap_or_default()
+    }
+
+    /// The full list has no search matches to mark.
+    pub fn highlight(&self, _id: &i64) -> Option<&SearchHighlight> {
+        None
+    }
+}
+
+#[derive(Template)]
+#[template(path = "samples_trash.html")]
+pub struct SamplesTrashTmpl {
+    pub ctx: BaseCtx,
+    pub samples: Vec<Sample>,
+}
+
+/// A search hit's name and description snippet, escaped and with the matched
+/// terms in `<mark>`.
+pub struct SearchHighlight {
+    pub name: String,
+    pub snippet: Option<String>,
+}
+
+#[derive(Template)]
+#[template(path = "samples_rows.html")]
+pub struct SamplesRowsTmpl {
+    pub samples: Vec<Sample>,
+    pub columns: Vec<SampleColumn>,
+    pub highlights: HashMap<i64, SearchHighlight>,
+}
+
+impl SamplesRowsTmpl {
+    pub fn list(samples: Vec<Sample>, columns: Vec<SampleColumn>) -> Self {
+        SamplesRowsTmpl {
+            samples,
+            columns,
+            highlights: HashMap::new(),
+        }
+    }
+
+    /// Rows for search hits, in rank order, with their matches marked.
+    pub fn search(hits: Vec<SampleSearchHit>, columns: Vec<SampleColumn>) -> Self {
+        let mut samples = Vec::with_capacity(hits.len());
+        let mut highlights = HashMap::new();
+        for hit in hits {
+            highlights.insert(
+                hit.sample.id,
+                SearchHighlight {
+                    name: hit.name_highlight,
+                    snippet: hit.description_snippet,
+                },
+            );
+            samples.push(hit.sample);
+        }
+        SamplesRowsTmpl {
+            samples,
+            columns,
+            highlights,
+        }
+    }
+
+    pub fn highlight(&self, id: &i64) -> Option<&SearchHighlight> {
+        self.highlights.get(id)
+    }
 }
 
 #[derive(Template)]
@@ -34,13 +157,323 @@ pub struct SampleFormTmpl {
     pub ctx: BaseCtx,
     pub s: Option<Sample>,
     pub action: String,
+    pub statuses: Vec<SampleStatus>,
+    pub fields: Vec<CustomField>,
+    /// Templates to start a new sample from, and the one picked if any.
+    pub templates: Vec<SampleTemplate>,
+    pub template: Option<SampleTemplate>,
+    pub can_edit: bool,
+    pub attachments: Option<SampleAttachmentsTmpl>,
+    pub comments: Option<SampleCommentsTmpl>,
+    pub schedule: Option<SampleScheduleTmpl>,
+    pub shares: Option<SampleSharesTmpl>,
+    pub watch: Option<SampleWatchTmpl>,
+}
+
+impl SampleFormTmpl {
+    fn custom_value(&self, key: &str) -> String {
+        match (&self.s, &self.template) {
+            (Some(s), _) => s.custom_value(key),
+            (None, Some(t)) => t.custom_value(key),
+            (None, None) => String::new(),
+        }
+    }
+
+    fn template_id(&self) -> Option<i64> {
+        self.template.as_ref().map(|t| t.id)
+    }
+}
+
+#[derive(Template)]
+#[template(path = "sample_attachments.html")]
+pub struct SampleAttachmentsTmpl {
+    pub sample_id: i64,
+    pub attachments: Vec<Attachment>,
+    pub max_mb: usize,
+    pub can_edit: bool,
+    pub error: Option<String>,
+}
+
+#[derive(Template)]
+#[template(path = "sample_comments.html")]
+pub struct SampleCommentsTmpl {
+    pub sample_id: i64,
+    pub comments: Vec<ThreadedComment>,
+    pub user_id: i64,
+    pub error: Option<String>,
+}
+
+#[derive(Template)]
+#[template(path = "sample_schedule.html")]
+pub struct SampleScheduleTmpl {
+    pub sample_id: i64,
+    pub actions: Vec<ScheduledAction>,
+    pub statuses: Vec<SampleStatus>,
+    pub can_edit: bool,
+    pub error: Option<String>,
+}
+
+#[derive(Template)]
+#[template(path = "sample_shares.html")]
+pub struct SampleSharesTmpl {
+    pub sample_id: i64,
+    pub shares: Vec<SampleShare>,
+    pub roles: Vec<SampleRole>,
+    pub user_id: i64,
+    pub is_owner: bool,
+    pub error: Option<String>,
+}
+
+#[derive(Template)]
+#[template(path = "sample_templates.html")]
+pub struct SampleTemplatesTmpl {
+    pub ctx: BaseCtx,
+    pub templates: Vec<SampleTemplate>,
+    pub user_id: i64,
+    pub error: Option<String>,
+}
+
+#[derive(Template)]
+#[template(path = "sample_revisions.html")]
+pub struct SampleRevisionsTmpl {
+    pub ctx: BaseCtx,
+    pub sample: Sample,
+    pub revisions: Vec<SampleRevision>,
+}
+
+#[derive(Template)]
+#[template(path = "revision_diff.html")]
+pub struct RevisionDiffTmpl {
+    pub ctx: BaseCtx,
+    pub sample: Sample,
+    pub diff: RevisionDiff,
+}
+
+#[derive(Template)]
+#[template(path = "sample_conflict.html")]
+pub struct SampleConflictTmpl {
+    pub ctx: BaseCtx,
+    pub action: String,
+    pub mine: SampleInput,
+    pub current: Sample,
+}
+
+#[derive(Template)]
+#[template(path = "samples_import.html")]
+pub struct SamplesImportTmpl {
+    pub ctx: BaseCtx,
+    pub report: Option<ImportReport>,
+    pub error: Option<String>,
+}
+
+#[derive(Template)]
+#[template(path = "admin_custom_fields.html")]
+pub struct AdminCustomFieldsTmpl {
+    pub ctx: BaseCtx,
+    pub fields: Vec<CustomField>,
+    pub field_types: Vec<FieldType>,
+    pub error: Option<String>,
+}
+
+#[derive(Template)]
+#[template(path = "admin_audit.html")]
+pub struct AdminAuditTmpl {
+    pub ctx: BaseCtx,
+    pub entries: Vec<AuditEntry>,
+    pub query: AuditQuery,
+    pub channels: Vec<Channel>,
+    /// Link to the next (older) page, keeping the filters.
+    pub next_page: Option<String>,
+    pub error: Option<String>,
+}
+
+impl AdminAuditTmpl {
+    pub fn channel_selected(&self, channel: &Channel) -> bool {
+        self.query.channel == Some(*channel)
+    }
+}
+
+#[derive(Template)]
+#[template(path = "admin_settings.html")]
+pub struct AdminSettingsTmpl {
+    pub ctx: BaseCtx,
+    pub settings: AppSettings,
+    pub saved: bool,
+    pub error: Option<String>,
+}
+
+#[derive(Template)]
+#[template(path = "dashboard.html")]
+pub struct DashboardTmpl {
+    pub ctx: BaseCtx,
+    pub stats: Option<SampleStats>,
+    pub query: StatsQuery,
+    pub buckets: Vec<Bucket>,
+    pub error: Option<String>,
+}
+
+impl DashboardTmpl {
+    pub fn bucket_selected(&self, bucket: &Bucket) -> bool {
+        self.query.bucket.unwrap_or_default() == *bucket
+    }
+
+    pub fn status_chart(&self, stats: &SampleStats) -> String {
+        let rows: Vec<_> = stats
+            .by_status
+            .iter()
+            .map(|c| (c.status.label().to_string(), c.count))
+            .collect();
+        charts::bar_list(&rows)
+    }
+
+    pub fn creator_chart(&self, stats: &SampleStats) -> String {
+        let rows: Vec<_> = stats
+            .by_creator
+            .iter()
+            .map(|c| (c.email.clone(), c.count))
+            .collect();
+        charts::bar_list(&rows)
+    }
+
+    pub fn created_chart(&self, stats: &SampleStats) -> String {
+        let points: Vec<_> = stats
+            .created
+            .iter()
+            .map(|b| (b.bucket.clone(), b.count))
+            .collect();
+        charts::column_chart(&points)
+    }
+
+    pub fn updated_chart(&self, stats: &SampleStats) -> String {
+        let points: Vec<_> = stats
+            .updated
+            .iter()
+            .map(|b| (b.bucket.clone(), b.count))
+            .collect();
+        charts::column_chart(&points)
+    }
+}
+
+#[derive(Template)]
+#[template(path = "sample_watch.html")]
+pub struct SampleWatchTmpl {
+    pub sample_id: i64,
+    pub watching: bool,
+    pub error: Option<String>,
+}
+
+#[derive(Template)]
+#[template(path = "notifications.html")]
+pub struct NotificationsTmpl {
+    pub ctx: BaseCtx,
+    pub notifications: Vec<Notification>,
+    pub unread: i64,
+    pub query: NotificationQuery,
+    pub next_page: Option<String>,
+}
+
+impl NotificationsTmpl {
+    pub fn unread_only(&self) -> bool {
+        self.query.unread == Some(true)
+    }
+
+    /// Where the mark-read buttons come back to.
+    pub fn current_query(&self) -> String {
+        serde_urlencoded::to_string(&self.query).unwrap_or_default()
+    }
+}
+
+#[derive(Template)]
+#[template(path = "notifications_badge.html")]
+pub struct NotificationsBadgeTmpl {
+    pub unread: i64,
+}
+
+#[derive(Template)]
+#[template(path = "settings_tokens.html")]
+pub struct SettingsTokensTmpl {
+    pub ctx: BaseCtx,
+    pub tokens: Vec<ApiToken>,
+    pub scopes: Vec<TokenScope>,
+    /// The token just made, whose secret is shown this once.
+    pub created: Option<NewApiToken>,
+    pub error: Option<String>,
 }
 
 #[derive(Template)]
 #[template(path = "login.html")]
 pub struct LoginTmpl {
     pub ctx: BaseCtx,
-    pub error: bool,
+    pub error: Option<&'static str>,
+    /// Offer a login link by email, when an admin has turned them on.
+    pub magic_links: bool,
+}
+
+#[derive(Template)]
+#[template(path = "register.html")]
+pub struct RegisterTmpl {
+    pub ctx: BaseCtx,
+    pub email: String,
+    /// Set once the form went through and the email is on its way.
+    pub sent: bool,
+    pub error: Option<String>,
+}
+
+#[derive(Template)]
+#[template(path = "forgot_password.html")]
+pub struct ForgotPasswordTmpl {
+    pub ctx: BaseCtx,
+    pub sent: bool,
+    pub error: Option<String>,
+}
+
+#[derive(Template)]
+#[template(path = "reset_password.html")]
+pub struct ResetPasswordTmpl {
+    pub ctx: BaseCtx,
+    pub token: String,
+    /// Set once the new password is saved.
+    pub done: bool,
+    pub error: Option<String>,
+}
+
+#[derive(Template)]
+#[template(path = "magic_link.html")]
+pub struct MagicLinkTmpl {
+    pub ctx: BaseCtx,
+    pub sent: bool,
+    pub error: Option<String>,
+}
+
+/// Where a login link lands. Logging in takes a button press, so mail
+/// scanners that open links don't use it up.
+#[derive(Template)]
+#[template(path = "magic_link_verify.html")]
+pub struct MagicLinkVerifyTmpl {
+    pub ctx: BaseCtx,
+    pub token: String,
+    pub error: Option<String>,
+}
+
+#[derive(Template)]
+#[template(path = "verify_email.html")]
+pub struct VerifyEmailTmpl {
+    pub ctx: BaseCtx,
+    pub error: Option<String>,
+}
+
+/// The 403, 404 or 500 page matching a failed service call.
+pub fn error_page(ctx: BaseCtx, e: &anyhow::Error) -> String {
+    match status_code(e) {
+        http::StatusCode::FORBIDDEN => Error403Tmpl { ctx }.render().unwrap(),
+        http::StatusCode::NOT_FOUND => Error404Tmpl { ctx }.render().unwrap(),
+        _ => Error500Tmpl {
+            ctx,
+            message: e.to_string(),
+        }
+        .render()
+        .unwrap(),
+    }
 }
 
 #[derive(Clone, Debug, Default)]
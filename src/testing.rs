@@ -0,0 +1,116 @@
+//! Fixtures shared by the unit tests. Each test gets a database of its own,
+//! migrated from scratch in a temp file, an event bus that drops events and a
+//! mailer that keeps what it is sent.
+
+use futures_util::future::BoxFuture;
+use sqlx::SqlitePool;
+use std::sync::{Arc, Mutex};
+
+use crate::{
+    db,
+    kafka::EventBus,
+    mailer::{Email, Mailer},
+    models::{
+        sample::{Sample, SampleInput, SampleStatus},
+        share::{SampleRole, ShareInput},
+        state::WebState,
+    },
+    services,
+};
+
+/// Holds every email sent instead of delivering it.
+#[derive(Default)]
+pub(crate) struct Outbox(Mutex<Vec<Email>>);
+
+impl Outbox {
+    pub(crate) fn sent(&self) -> Vec<Email> {
+        self.0.lock().unwrap().clone()
+    }
+
+    /// Waits for mail sent from a background task until there are `count`.
+    pub(crate) async fn wait_for(&self, count: usize) -> Vec<Email> {
+        for _ in 0..200 {
+            let sent = self.sent();
+            if sent.len() >= count {
+                return sent;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+        panic!("expected {count} emails, got {}", self.sent().len());
+    }
+}
+
+impl Mailer for Outbox {
+    fn send<'a>(&'a self, email: &'a Email) -> BoxFuture<'a, anyhow::Result<()>> {
+        self.0.lock().unwrap().push(email.clone());
+        Box::pin(async { Ok(()) })
+    }
+}
+
+pub(crate) async fn state() -> WebState {
+    state_with_outbox().await.0
+}
+
+pub(crate) async fn state_with_outbox() -> (WebState, Arc<Outbox>) {
+    let path = std::env::temp_dir().join(format!("sample-test-{}.db", uuid::Uuid::new_v4()));
+    let db = db::connect(&format!("sqlite://{}?mode=rwc", path.display()))
+        .await
+        .expect("test database");
+    let outbox = Arc::new(Outbox::default());
+    let state = WebState {
+        db,
+        events: EventBus::disabled(),
+        mailer: outbox.clone(),
+    };
+    (state, outbox)
+}
+
+/// Adds a verified user and returns their id.
+pub(crate) async fn user(db: &SqlitePool, email: &str) -> i64 {
+    sqlx::query_scalar!(
+        r#"
+            INSERT INTO users (email, password_hash, email_verified_at)
+            VALUES (?, 'x', datetime('now'))
+            RETURNING id AS "id!"
+        "#,
+        email
+    )
+    .fetch_one(db)
+    .await
+    .expect("test user")
+}
+
+/// A draft with no description.
+pub(crate) fn input(name: &str) -> SampleInput {
+    SampleInput {
+        name: name.to_string(),
+        description: None,
+        status: SampleStatus::Draft,
+        tags: None,
+        version: None,
+        custom_fields: None,
+    }
+}
+
+pub(crate) async fn sample(state: &WebState, uid: i64, name: &str) -> Sample {
+    services::sample::create_sample(state, input(name), uid)
+        .await
+        .expect("test sample")
+}
+
+/// Gives the user with `email` `role` on a sample `owner` owns.
+pub(crate) async fn share(
+    state: &WebState,
+    sample_id: i64,
+    owner: i64,
+    email: &str,
+    role: SampleRole,
+) {
+    let input = ShareInput {
+        email: email.to_string(),
+        role,
+    };
+    services::share::share_sample(state, sample_id, input, owner)
+        .await
+        .expect("test share");
+}
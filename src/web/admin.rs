@@ -0,0 +1,225 @@
+use crate::middleware::{is_htmx, AdminUser};
+use crate::models::audit::{AuditQuery, Channel};
+use crate::models::custom_field::{CustomFieldInput, FieldType};
+use crate::models::setting::AppSettingsUpdate;
+use crate::models::state::WebState;
+use crate::services;
+use crate::services::error::status_code;
+use crate::templates::{base_ctx, AdminAuditTmpl, AdminCustomFieldsTmpl, AdminSettingsTmpl};
+use askama::Template;
+use axum::response::IntoResponse;
+use axum::{
+    extract::{Path, Query, State},
+    response::{Html, Redirect},
+    routing::{delete, get},
+    Form, Router,
+};
+use http::StatusCode;
+use serde::Deserialize;
+use tower_sessions::Session;
+
+pub fn router() -> Router<WebState> {
+    Router::new()
+        .route(
+            "/admin/custom-fields",
+            get(custom_fields_page).post(create_custom_field),
+        )
+        .route("/admin/custom-fields/{id}", delete(delete_custom_field))
+        .route("/admin/audit", get(audit_page))
+        .route("/admin/settings", get(settings_page).post(update_settings))
+}
+
+/// The new-field form. Options come in as one comma separated value.
+#[derive(Deserialize)]
+struct CustomFieldForm {
+    key: String,
+    label: String,
+    field_type: FieldType,
+    #[serde(default)]
+    required: Option<String>,
+    #[serde(default)]
+    options: String,
+    #[serde(default)]
+    pattern: String,
+    #[serde(default)]
+    position: i64,
+}
+
+impl From<CustomFieldForm> for CustomFieldInput {
+    fn from(form: CustomFieldForm) -> Self {
+        CustomFieldInput {
+            key: form.key,
+            label: form.label,
+            field_type: form.field_type,
+            required: form.required.is_some(),
+            options: form
+                .options
+                .split(',')
+                .map(str::to_string)
+                .filter(|o| !o.trim().is_empty())
+                .collect(),
+            pattern: Some(form.pattern),
+            position: form.position,
+        }
+    }
+}
+
+async fn render_page(
+    state: &WebState,
+    session: &Session,
+    status: StatusCode,
+    error: Option<String>,
+) -> impl IntoResponse {
+    let ctx = base_ctx(session).await;
+    let fields = services::custom_field::get_fields(state)
+        .await
+        .unwrap_or_default();
+    let html = AdminCustomFieldsTmpl {
+        ctx,
+        fields,
+        field_types: FieldType::ALL.to_vec(),
+        error,
+    }
+    .render()
+    .unwrap();
+    (status, Html(html))
+}
+
+async fn custom_fields_page(
+    State(state): State<WebState>,
+    AdminUser(_): AdminUser,
+    session: Session,
+) -> impl IntoResponse {
+    render_page(&state, &session, StatusCode::OK, None).await
+}
+
+async fn create_custom_field(
+    State(state): State<WebState>,
+    AdminUser(admin_id): AdminUser,
+    session: Session,
+    headers: http::HeaderMap,
+    Form(form): Form<CustomFieldForm>,
+) -> axum::response::Response {
+    match services::custom_field::create_field(&state, form.into(), admin_id).await {
+        Ok(field) => {
+            tracing::info!(admin_id, key = %field.key, "custom field created");
+            if is_htmx(&headers) {
+                render_page(&state, &session, StatusCode::OK, None)
+                    .await
+                    .into_response()
+            } else {
+                Redirect::to("/admin/custom-fields").into_response()
+            }
+        }
+        Err(e) => render_page(&state, &session, status_code(&e), Some(e.to_string()))
+            .await
+            .into_response(),
+    }
+}
+
+async fn delete_custom_field(
+    State(state): State<WebState>,
+    AdminUser(admin_id): AdminUser,
+    session: Session,
+    Path(id): Path<i64>,
+) -> impl IntoResponse {
+    match services::custom_field::delete_field(&state, id, admin_id).await {
+        Ok(()) => {
+            tracing::info!(admin_id, id, "custom field deleted");
+            render_page(&state, &session, StatusCode::OK, None).await
+        }
+        Err(e) => render_page(&state, &session, status_code(&e), Some(e.to_string())).await,
+    }
+}
+
+async fn audit_page(
+    State(state): State<WebState>,
+    AdminUser(_): AdminUser,
+    session: Session,
+    Query(query): Query<AuditQuery>,
+) -> impl IntoResponse {
+    let ctx = base_ctx(&session).await;
+    let (entries, next_page, status, error) =
+        match services::audit::get_entries(&state, &query).await {
+            Ok(page) => {
+                let next_page = page
+                    .next_before_id
+                    .map(|id| format!("/admin/audit?{}", query.next_page_query(id)));
+                (page.items, next_page, StatusCode::OK, None)
+            }
+            Err(e) => (Vec::new(), None, status_code(&e), Some(e.to_string())),
+        };
+    let html = AdminAuditTmpl {
+        ctx,
+        entries,
+        query,
+        channels: Channel::ALL.to_vec(),
+        next_page,
+        error,
+    }
+    .render()
+    .unwrap();
+    (status, Html(html))
+}
+
+/// Unchecked boxes aren't sent, so a missing field means off.
+#[derive(Deserialize)]
+struct SettingsForm {
+    #[serde(default)]
+    magic_links_enabled: Option<String>,
+}
+
+impl From<SettingsForm> for AppSettingsUpdate {
+    fn from(form: SettingsForm) -> Self {
+        AppSettingsUpdate {
+            magic_links_enabled: Some(form.magic_links_enabled.is_some()),
+        }
+    }
+}
+
+async fn render_settings(
+    state: &WebState,
+    session: &Session,
+    saved: bool,
+    result: anyhow::Result<()>,
+) -> impl IntoResponse {
+    let ctx = base_ctx(session).await;
+    let (status, error) = match result {
+        Ok(()) => (StatusCode::OK, None),
+        Err(e) => (status_code(&e), Some(e.to_string())),
+    };
+    let settings = services::setting::get_settings(state)
+        .await
+        .unwrap_or_default();
+    let html = AdminSettingsTmpl {
+        ctx,
+        settings,
+        saved,
+        error,
+    }
+    .render()
+    .unwrap();
+    (status, Html(html))
+}
+
+async fn settings_page(
+    State(state): State<WebState>,
+    AdminUser(_): AdminUser,
+    session: Session,
+) -> impl IntoResponse {
+    render_settings(&state, &session, false, Ok(())).await
+}
+
+async fn update_settings(
+    State(state): State<WebState>,
+    AdminUser(admin_id): AdminUser,
+    session: Session,
+    Form(form): Form<SettingsForm>,
+) -> impl IntoResponse {
+    let result = services::setting::update_settings(&state, form.into(), admin_id).await;
+    if let Ok(settings) = &result {
+        tracing::info!(admin_id, ?settings, "settings updated");
+    }
+    let saved = result.is_ok();
+    render_settings(&state, &session, saved, result.map(|_| ())).await
+}
@@ -0,0 +1,90 @@
+use askama::Template;
+use axum::{
+    extract::{Path, State},
+    response::{Html, IntoResponse},
+    routing::{delete, get},
+    Form, Router,
+};
+use http::StatusCode;
+use tower_sessions::Session;
+
+use crate::{
+    middleware::AuthedUser,
+    models::{
+        api_token::{ApiTokenInput, NewApiToken, TokenScope},
+        state::WebState,
+    },
+    services::{self, error::status_code},
+    templates::{base_ctx, SettingsTokensTmpl},
+};
+
+pub fn router() -> Router<WebState> {
+    Router::new()
+        .route("/settings/tokens", get(tokens_page).post(create_token))
+        .route("/settings/tokens/{id}", delete(revoke_token))
+}
+
+async fn render_page(
+    state: &WebState,
+    session: &Session,
+    user_id: i64,
+    created: Option<NewApiToken>,
+    result: anyhow::Result<()>,
+) -> impl IntoResponse {
+    let ctx = base_ctx(session).await;
+    let (status, error) = match result {
+        Ok(()) => (StatusCode::OK, None),
+        Err(e) => (status_code(&e), Some(e.to_string())),
+    };
+    let tokens = services::api_token::get_tokens(state, user_id)
+        .await
+        .unwrap_or_default();
+    let html = SettingsTokensTmpl {
+        ctx,
+        tokens,
+        scopes: TokenScope::ALL.to_vec(),
+        created,
+        error,
+    }
+    .render()
+    .unwrap();
+    (status, Html(html))
+}
+
+async fn tokens_page(
+    State(state): State<WebState>,
+    AuthedUser(user_id): AuthedUser,
+    session: Session,
+) -> impl IntoResponse {
+    render_page(&state, &session, user_id, None, Ok(())).await
+}
+
+/// Shows the new token's secret on the page it returns, the only time it
+/// can be seen.
+async fn create_token(
+    State(state): State<WebState>,
+    AuthedUser(user_id): AuthedUser,
+    session: Session,
+    Form(input): Form<ApiTokenInput>,
+) -> impl IntoResponse {
+    match services::api_token::create_token(&state, input, user_id).await {
+        Ok(created) => {
+            tracing::info!(user_id, id = created.token.id, "api token created");
+            render_page(&state, &session, user_id, Some(created), Ok(())).await
+        }
+        Err(e) => render_page(&state, &session, user_id, None, Err(e)).await,
+    }
+}
+
+async fn revoke_token(
+    State(state): State<WebState>,
+    AuthedUser(user_id): AuthedUser,
+    session: Session,
+    Path(id): Path<i64>,
+) -> impl IntoResponse {
+    let result = services::api_token::revoke_token(&state, id, user_id).await;
+    if result.is_ok() {
+        tracing::info!(user_id, id, "api token revoked");
+    }
+    render_page(&state, &session, user_id, None, result).await
+}
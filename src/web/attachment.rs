@@ -0,0 +1,79 @@
+use crate::middleware::AuthedUser;
+use crate::models::share::SampleRole;
+use crate::models::state::WebState;
+use crate::services;
+use crate::services::attachment::MAX_ATTACHMENT_BYTES;
+use crate::services::error::status_code;
+use crate::templates::SampleAttachmentsTmpl;
+use askama::Template;
+use axum::response::IntoResponse;
+use axum::{
+    extract::{DefaultBodyLimit, Multipart, Path, State},
+    response::Html,
+    routing::{delete, post},
+    Router,
+};
+use http::StatusCode;
+
+pub fn router() -> Router<WebState> {
+    Router::new()
+        .route(
+            "/samples/{id}/attachments",
+            post(upload_attachments).layer(DefaultBodyLimit::max(MAX_ATTACHMENT_BYTES + 64 * 1024)),
+        )
+        .route(
+            "/samples/{id}/attachments/{attachment_id}",
+            delete(delete_attachment),
+        )
+}
+
+/// The attachments panel on the edit page, re-rendered after every change.
+pub async fn attachments_panel(
+    state: &WebState,
+    sample_id: i64,
+    user_id: i64,
+    error: Option<String>,
+) -> SampleAttachmentsTmpl {
+    let attachments = services::attachment::get_attachments(state, sample_id)
+        .await
+        .unwrap_or_default();
+    let can_edit = services::sample::role_for(state, sample_id, user_id)
+        .await
+        .is_ok_and(|role| role >= SampleRole::Editor);
+    SampleAttachmentsTmpl {
+        sample_id,
+        attachments,
+        max_mb: MAX_ATTACHMENT_BYTES / (1024 * 1024),
+        can_edit,
+        error,
+    }
+}
+
+async fn upload_attachments(
+    State(state): State<WebState>,
+    AuthedUser(user_id): AuthedUser,
+    Path(id): Path<i64>,
+    multipart: Multipart,
+) -> impl IntoResponse {
+    let (status, error) =
+        match services::attachment::add_uploads(&state, id, multipart, user_id).await {
+            Ok(_) => (StatusCode::OK, None),
+            Err(e) => (status_code(&e), Some(e.to_string())),
+        };
+    let panel = attachments_panel(&state, id, user_id, error).await;
+    (status, Html(panel.render().unwrap()))
+}
+
+async fn delete_attachment(
+    State(state): State<WebState>,
+    AuthedUser(user_id): AuthedUser,
+    Path((id, attachment_id)): Path<(i64, i64)>,
+) -> impl IntoResponse {
+    let (status, error) =
+        match services::attachment::remove_attachment(&state, id, attachment_id, user_id).await {
+            Ok(_) => (StatusCode::OK, None),
+            Err(e) => (status_code(&e), Some(e.to_string())),
+        };
+    let panel = attachments_panel(&state, id, user_id, error).await;
+    (status, Html(panel.render().unwrap()))
+}
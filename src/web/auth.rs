@@ -18,11 +18,22 @@ use tower_sessions::Session;
 
 use crate::middleware::is_htmx;
 use crate::models::state::WebState;
+use crate::models::user::{
+    ForgotPasswordInput, MagicLinkInput, RegisterInput, ResetPasswordInput, VerifyEmailInput,
+};
+use crate::services;
+use crate::services::error::status_code;
 use crate::templates::base_ctx;
 use crate::templates::BaseCtx;
-use crate::templates::LoginTmpl;
+use crate::templates::{
+    ForgotPasswordTmpl, LoginTmpl, MagicLinkTmpl, MagicLinkVerifyTmpl, RegisterTmpl,
+    ResetPasswordTmpl, VerifyEmailTmpl,
+};
 
 pub const SESSION_USER_ID: &str = "uid";
+/// The user's `session_epoch` at sign-in. `AuthedUser` rejects the session once
+/// the user's epoch moves on.
+pub const SESSION_EPOCH: &str = "epoch";
 
 #[derive(Deserialize)]
 pub struct LoginForm {
@@ -41,12 +52,54 @@ async fn verify_password(hash: &str, password: &str) -> bool {
         .unwrap_or(false)
 }
 
-pub async fn login_get(Query(q): Query<HashMap<String, String>>, session: Session) -> Html<String> {
+/// What the login page says for each `?error=` code.
+fn login_error(code: &str) -> &'static str {
+    match code {
+        "unverified" => "Confirm your email first. Sign up again with this email to get a new link; your password stays the same.",
+        _ => "Invalid credentials",
+    }
+}
+
+/// Signs the session in as `user_id`, under a fresh session id.
+pub(crate) async fn start_session(
+    session: &Session,
+    user_id: i64,
+    epoch: i64,
+) -> Result<(), tower_sessions::session::Error> {
+    session.cycle_id().await?;
+    session.insert(SESSION_USER_ID, user_id).await?;
+    session.insert(SESSION_EPOCH, epoch).await
+}
+
+/// Sends a freshly signed-in browser on to the samples list.
+fn signed_in(headers: &HeaderMap) -> axum::response::Response {
+    if is_htmx(headers) {
+        let mut hm = HeaderMap::new();
+        hm.insert("HX-Redirect", HeaderValue::from_static("/samples"));
+        hm.insert("HX-Replace-Url", HeaderValue::from_static("/samples"));
+        (StatusCode::NO_CONTENT, hm).into_response()
+    } else {
+        Redirect::to("/samples").into_response()
+    }
+}
+
+async fn magic_links_enabled(state: &WebState) -> bool {
+    services::setting::magic_links_enabled(state)
+        .await
+        .unwrap_or(false)
+}
+
+pub async fn login_get(
+    State(state): State<WebState>,
+    Query(q): Query<HashMap<String, String>>,
+    session: Session,
+) -> Html<String> {
     let ctx = base_ctx(&session).await;
 
     let html = LoginTmpl {
         ctx,
-        error: q.contains_key("error"),
+        error: q.get("error").map(|code| login_error(code)),
+        magic_links: magic_links_enabled(&state).await,
     }
     .render()
     .unwrap();
@@ -59,9 +112,11 @@ pub async fn login_post(
     headers: HeaderMap,
     Form(form): Form<LoginForm>,
 ) -> axum::response::Response {
+    let email = form.email.trim();
     let user = query!(
-        r#"SELECT id, email, password_hash, created_at FROM users WHERE email = ?"#,
-        form.email
+        r#"SELECT id, password_hash, session_epoch, email_verified_at IS NOT NULL AS "verified!: bool"
+FROM users WHERE email = ? COLLATE NOCASE"#,
+        email
     )
     .fetch_optional(&state.db)
     .await
@@ -69,35 +124,36 @@ pub async fn login_post(
 
     let password_match =
         matches!(&user, Some(u) if verify_password(&u.password_hash, &form.password).await);
+    let verified = matches!(&user, Some(u) if u.verified);
 
-    if password_match {
+    if password_match && verified {
         if let Some(u) = user {
-            session.insert(SESSION_USER_ID, u.id).await.unwrap();
-        }
-
-        if is_htmx(&headers) {
-            let mut hm = HeaderMap::new();
-            hm.insert("HX-Redirect", HeaderValue::from_static("/samples"));
-            hm.insert("HX-Replace-Url", HeaderValue::from_static("/samples"));
-            return (StatusCode::NO_CONTENT, hm).into_response();
-        } else {
-            return Redirect::to("/samples").into_response();
+            start_session(&session, u.id, u.session_epoch)
+                .await
+                .unwrap();
         }
+        return signed_in(&headers);
     }
 
-    // invalid login
+    // invalid login, or the right password for an account not yet confirmed
+    let error = if password_match {
+        "unverified"
+    } else {
+        "invalid"
+    };
     if is_htmx(&headers) {
         let html = LoginTmpl {
-            error: true,
+            error: Some(login_error(error)),
             ctx: BaseCtx {
                 is_authenticated: false,
             },
+            magic_links: magic_links_enabled(&state).await,
         }
         .render()
         .unwrap();
         (StatusCode::UNAUTHORIZED, Html(html)).into_response()
     } else {
-        Redirect::to("/login?error=invalid").into_response()
+        Redirect::to(&format!("/login?error={error}")).into_response()
     }
 }
 
@@ -121,9 +177,217 @@ pub async fn auth_check(session: Session) -> StatusCode {
     }
 }
 
+pub async fn register_get(session: Session) -> Html<String> {
+    let html = RegisterTmpl {
+        ctx: base_ctx(&session).await,
+        email: String::new(),
+        sent: false,
+        error: None,
+    }
+    .render()
+    .unwrap();
+    Html(html)
+}
+
+pub async fn register_post(
+    State(state): State<WebState>,
+    session: Session,
+    Form(input): Form<RegisterInput>,
+) -> impl IntoResponse {
+    let ctx = base_ctx(&session).await;
+    let email = input.email.trim().to_string();
+    let (status, sent, error) = match services::account::register(&state, input).await {
+        Ok(()) => (StatusCode::OK, true, None),
+        Err(e) => (status_code(&e), false, Some(e.to_string())),
+    };
+    let html = RegisterTmpl {
+        ctx,
+        email,
+        sent,
+        error,
+    }
+    .render()
+    .unwrap();
+    (status, Html(html))
+}
+
+/// Where the link in the verification email lands.
+pub async fn verify_email_get(
+    State(state): State<WebState>,
+    session: Session,
+    Query(input): Query<VerifyEmailInput>,
+) -> impl IntoResponse {
+    let ctx = base_ctx(&session).await;
+    let (status, error) = match services::account::verify_email(&state, &input.token).await {
+        Ok(()) => (StatusCode::OK, None),
+        Err(e) => (status_code(&e), Some(e.to_string())),
+    };
+    let html = VerifyEmailTmpl { ctx, error }.render().unwrap();
+    (status, Html(html))
+}
+
+pub async fn forgot_password_get(session: Session) -> Html<String> {
+    let html = ForgotPasswordTmpl {
+        ctx: base_ctx(&session).await,
+        sent: false,
+        error: None,
+    }
+    .render()
+    .unwrap();
+    Html(html)
+}
+
+pub async fn forgot_password_post(
+    State(state): State<WebState>,
+    session: Session,
+    Form(input): Form<ForgotPasswordInput>,
+) -> impl IntoResponse {
+    let ctx = base_ctx(&session).await;
+    let (status, sent, error) = match services::account::request_password_reset(&state, input).await
+    {
+        Ok(()) => (StatusCode::OK, true, None),
+        Err(e) => (status_code(&e), false, Some(e.to_string())),
+    };
+    let html = ForgotPasswordTmpl { ctx, sent, error }.render().unwrap();
+    (status, Html(html))
+}
+
+#[derive(Deserialize)]
+pub struct ResetQuery {
+    #[serde(default)]
+    pub token: String,
+}
+
+/// Where the link in the reset email lands. The token is only checked when
+/// the form is sent, so opening the page doesn't use it up.
+pub async fn reset_password_get(session: Session, Query(q): Query<ResetQuery>) -> Html<String> {
+    let html = ResetPasswordTmpl {
+        ctx: base_ctx(&session).await,
+        token: q.token,
+        done: false,
+        error: None,
+    }
+    .render()
+    .unwrap();
+    Html(html)
+}
+
+pub async fn reset_password_post(
+    State(state): State<WebState>,
+    session: Session,
+    Form(input): Form<ResetPasswordInput>,
+) -> impl IntoResponse {
+    let token = input.token.clone();
+    let result = services::account::reset_password(&state, input).await;
+    if result.is_ok() {
+        // this browser may be signed in with a session the reset just ended
+        let _ = session.flush().await;
+    }
+    let ctx = base_ctx(&session).await;
+    let (status, done, error) = match result {
+        Ok(()) => (StatusCode::OK, true, None),
+        Err(e) => (status_code(&e), false, Some(e.to_string())),
+    };
+    let html = ResetPasswordTmpl {
+        ctx,
+        token,
+        done,
+        error,
+    }
+    .render()
+    .unwrap();
+    (status, Html(html))
+}
+
+pub async fn magic_link_get(State(state): State<WebState>, session: Session) -> impl IntoResponse {
+    let ctx = base_ctx(&session).await;
+    let (status, error) = if magic_links_enabled(&state).await {
+        (StatusCode::OK, None)
+    } else {
+        (
+            StatusCode::FORBIDDEN,
+            Some("Login links are turned off. Log in with your password.".to_string()),
+        )
+    };
+    let html = MagicLinkTmpl {
+        ctx,
+        sent: false,
+        error,
+    }
+    .render()
+    .unwrap();
+    (status, Html(html))
+}
+
+pub async fn magic_link_post(
+    State(state): State<WebState>,
+    session: Session,
+    Form(input): Form<MagicLinkInput>,
+) -> impl IntoResponse {
+    let ctx = base_ctx(&session).await;
+    let (status, sent, error) = match services::account::request_magic_link(&state, input).await {
+        Ok(()) => (StatusCode::OK, true, None),
+        Err(e) => (status_code(&e), false, Some(e.to_string())),
+    };
+    let html = MagicLinkTmpl { ctx, sent, error }.render().unwrap();
+    (status, Html(html))
+}
+
+/// Where the link in the login email lands. Like the reset page it only
+/// shows a form, so a mail scanner opening the link doesn't use it up.
+pub async fn magic_link_verify_get(session: Session, Query(q): Query<ResetQuery>) -> Html<String> {
+    let html = MagicLinkVerifyTmpl {
+        ctx: base_ctx(&session).await,
+        token: q.token,
+        error: None,
+    }
+    .render()
+    .unwrap();
+    Html(html)
+}
+
+pub async fn magic_link_verify_post(
+    State(state): State<WebState>,
+    session: Session,
+    headers: HeaderMap,
+    Form(input): Form<VerifyEmailInput>,
+) -> axum::response::Response {
+    match services::account::consume_magic_link(&state, &input.token).await {
+        Ok((user_id, epoch)) => {
+            start_session(&session, user_id, epoch).await.unwrap();
+            signed_in(&headers)
+        }
+        Err(e) => {
+            let html = MagicLinkVerifyTmpl {
+                ctx: base_ctx(&session).await,
+                token: input.token,
+                error: Some(e.to_string()),
+            }
+            .render()
+            .unwrap();
+            (status_code(&e), Html(html)).into_response()
+        }
+    }
+}
+
 pub fn router() -> Router<WebState> {
     Router::new()
         .route("/login", get(login_get).post(login_post))
+        .route("/register", get(register_get).post(register_post))
+        .route("/verify-email", get(verify_email_get))
+        .route(
+            "/forgot-password",
+            get(forgot_password_get).post(forgot_password_post),
+        )
+        .route(
+            "/reset-password",
+            get(reset_password_get).post(reset_password_post),
+        )
+        .route("/login/magic", get(magic_link_get).post(magic_link_post))
+        .route(
+            "/login/magic/verify",
+            get(magic_link_verify_get).post(magic_link_verify_post),
+        )
         .route("/logout", post(logout))
         .route("/auth/check", get(auth_check))
 }
@@ -0,0 +1,89 @@
+use crate::middleware::AuthedUser;
+use crate::models::comment::{CommentInput, CommentUpdate};
+use crate::models::state::WebState;
+use crate::services;
+use crate::services::error::status_code;
+use crate::templates::SampleCommentsTmpl;
+use askama::Template;
+use axum::response::IntoResponse;
+use axum::{
+    extract::{Path, State},
+    response::Html,
+    routing::post,
+    Form, Router,
+};
+use http::StatusCode;
+
+pub fn router() -> Router<WebState> {
+    Router::new()
+        .route("/samples/{id}/comments", post(add_comment))
+        .route(
+            "/samples/{id}/comments/{comment_id}",
+            post(update_comment).delete(delete_comment),
+        )
+}
+
+/// The comments section on the sample page, re-rendered after every change.
+pub async fn comments_panel(
+    state: &WebState,
+    sample_id: i64,
+    user_id: i64,
+    error: Option<String>,
+) -> SampleCommentsTmpl {
+    let comments = services::comment::get_comments(state, sample_id)
+        .await
+        .unwrap_or_default();
+    SampleCommentsTmpl {
+        sample_id,
+        comments,
+        user_id,
+        error,
+    }
+}
+
+async fn render_panel(
+    state: &WebState,
+    sample_id: i64,
+    user_id: i64,
+    result: anyhow::Result<()>,
+) -> impl IntoResponse {
+    let (status, error) = match result {
+        Ok(()) => (StatusCode::OK, None),
+        Err(e) => (status_code(&e), Some(e.to_string())),
+    };
+    let panel = comments_panel(state, sample_id, user_id, error).await;
+    (status, Html(panel.render().unwrap()))
+}
+
+async fn add_comment(
+    State(state): State<WebState>,
+    AuthedUser(user_id): AuthedUser,
+    Path(id): Path<i64>,
+    Form(input): Form<CommentInput>,
+) -> impl IntoResponse {
+    let result = services::comment::add_comment(&state, id, input, user_id)
+        .await
+        .map(|_| ());
+    render_panel(&state, id, user_id, result).await
+}
+
+async fn update_comment(
+    State(state): State<WebState>,
+    AuthedUser(user_id): AuthedUser,
+    Path((id, comment_id)): Path<(i64, i64)>,
+    Form(input): Form<CommentUpdate>,
+) -> impl IntoResponse {
+    let result = services::comment::update_comment(&state, id, comment_id, input, user_id)
+        .await
+        .map(|_| ());
+    render_panel(&state, id, user_id, result).await
+}
+
+async fn delete_comment(
+    State(state): State<WebState>,
+    AuthedUser(user_id): AuthedUser,
+    Path((id, comment_id)): Path<(i64, i64)>,
+) -> impl IntoResponse {
+    let result = services::comment::delete_comment(&state, id, comment_id, user_id).await;
+    render_panel(&state, id, user_id, result).await
+}
@@ -0,0 +1,113 @@
+use crate::middleware::AuthedUser;
+use crate::models::import::ImportFormat;
+use crate::models::state::WebState;
+use crate::services;
+use crate::services::error::status_code;
+use crate::services::import::MAX_IMPORT_BYTES;
+use crate::templates::{base_ctx, SamplesImportTmpl};
+use askama::Template;
+use axum::response::IntoResponse;
+use axum::{
+    extract::{DefaultBodyLimit, Multipart, State},
+    response::Html,
+    routing::get,
+    Router,
+};
+use http::StatusCode;
+use tower_sessions::Session;
+
+pub fn router() -> Router<WebState> {
+    Router::new().route(
+        "/samples/import",
+        get(import_page)
+            .post(import_upload)
+            .layer(DefaultBodyLimit::max(MAX_IMPORT_BYTES)),
+    )
+}
+
+async fn import_page(AuthedUser(_): AuthedUser, session: Session) -> Html<String> {
+    let ctx = base_ctx(&session).await;
+    let html = SamplesImportTmpl {
+        ctx,
+        report: None,
+        error: None,
+    }
+    .render()
+    .unwrap();
+    Html(html)
+}
+
+/// Fields sent by the upload form.
+#[derive(Default)]
+struct ImportUpload {
+    file_name: String,
+    data: Vec<u8>,
+    format: Option<ImportFormat>,
+    dry_run: bool,
+}
+
+async fn read_upload(mut multipart: Multipart) -> Result<ImportUpload, String> {
+    let mut upload = ImportUpload::default();
+    while let Some(field) = multipart.next_field().await.map_err(|e| e.to_string())? {
+        match field.name() {
+            Some("file") => {
+                upload.file_name = field.file_name().unwrap_or_default().to_string();
+                upload.data = field.bytes().await.map_err(|e| e.to_string())?.to_vec();
+            }
+            Some("format") => {
+                let value = field.text().await.map_err(|e| e.to_string())?;
+                upload.format = ImportFormat::detect(&value);
+            }
+            Some("dry_run") => upload.dry_run = true,
+            _ => {}
+        }
+    }
+    Ok(upload)
+}
+
+async fn import_upload(
+    State(state): State<WebState>,
+    AuthedUser(uid): AuthedUser,
+    session: Session,
+    multipart: Multipart,
+) -> impl IntoResponse {
+    let ctx = base_ctx(&session).await;
+
+    let result = match read_upload(multipart).await {
+        Ok(upload) => match upload
+            .format
+            .or_else(|| ImportFormat::detect(&upload.file_name))
+        {
+            Some(format) => {
+                services::import::run_import(&state, format, &upload.data, upload.dry_run, uid)
+                    .await
+                    .map_err(|e| (status_code(&e), e.to_string()))
+            }
+            None => Err((
+                StatusCode::UNPROCESSABLE_ENTITY,
+                "choose a format or upload a .csv or .ndjson file".to_string(),
+            )),
+        },
+        Err(e) => Err((StatusCode::BAD_REQUEST, e)),
+    };
+
+    let (status, tmpl) = match result {
+        Ok(report) => (
+            StatusCode::OK,
+            SamplesImportTmpl {
+                ctx,
+                report: Some(report),
+                error: None,
+            },
+        ),
+        Err((status, error)) => (
+            status,
+            SamplesImportTmpl {
+                ctx,
+                report: None,
+                error: Some(error),
+            },
+        ),
+    };
+    (status, Html(tmpl.render().unwrap()))
+}
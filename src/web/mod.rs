@@ -1,12 +1,145 @@
-use axum::Router;
+use axum::{
+    middleware::from_fn,
+    response::{Html, IntoResponse, Redirect, Response},
+    Router,
+};
+use http::{HeaderMap, HeaderValue, StatusCode};
+use serde_json::json;
+use tower_sessions::Session;
 
-use crate::models::state::WebState;
+use crate::middleware::{audit_context, is_htmx};
+use crate::models::{audit::Channel, state::WebState};
+use crate::services::error::status_code;
+use crate::templates::{base_ctx, error_page};
+use admin::router as admin_router;
+use api_token::router as api_token_router;
+use attachment::router as attachment_router;
 use auth::router as auth_router;
+use comment::router as comment_router;
+use import::router as import_router;
+use notification::router as notification_router;
+use revision::router as revision_router;
 use sample::router as sample_router;
+use sample_template::router as sample_template_router;
+use saved_view::router as saved_view_router;
+use schedule::router as schedule_router;
+use share::router as share_router;
+use stats::router as stats_router;
 
+pub mod admin;
+pub mod api_token;
+pub mod attachment;
 pub mod auth;
+pub mod comment;
+pub mod import;
+pub mod notification;
+pub mod revision;
 pub mod sample;
+pub mod sample_template;
+pub mod saved_view;
+pub mod schedule;
+pub mod share;
+pub mod stats;
 
 pub fn router() -> Router<WebState> {
-    Router::new().merge(auth_router()).merge(sample_router())
+    Router::new()
+        .merge(auth_router())
+        .merge(sample_router())
+        .merge(revision_router())
+        .merge(import_router())
+        .merge(attachment_router())
+        .merge(comment_router())
+        .merge(share_router())
+        .merge(sample_template_router())
+        .merge(saved_view_router())
+        .merge(schedule_router())
+        .merge(stats_router())
+        .merge(notification_router())
+        .merge(api_token_router())
+        .merge(admin_router())
+        .layer(from_fn(|req, next| audit_context(Channel::Web, req, next)))
+}
+
+/// Sends the browser to `path`, swapping the whole shell.
+pub(crate) fn go_to(headers: &HeaderMap, path: &str) -> Response {
+    if is_htmx(headers) {
+        let payload = json!({
+            "path": path,
+            "target": "#shell",
+            "select": "#shell",
+            "swap": "outerHTML swap:200ms",
+            "pushUrl": true
+        })
+        .to_string();
+
+        let mut hm = HeaderMap::new();
+        hm.insert("HX-Location", HeaderValue::from_str(&payload).unwrap());
+        (StatusCode::NO_CONTENT, hm).into_response()
+    } else {
+        Redirect::to(path).into_response()
+    }
+}
+
+/// The page for a failed service call. Refusals other than 403 and 404, such
+/// as a bad input or a conflict, get their message as plain text rather than
+/// an error page.
+pub(crate) async fn error_response(session: &Session, e: anyhow::Error) -> Response {
+    let status = status_code(&e);
+    if status.is_client_error()
+        && status != StatusCode::FORBIDDEN
+        && status != StatusCode::NOT_FOUND
+    {
+        return (status, e.to_string()).into_response();
+    }
+    (status, Html(error_page(base_ctx(session).await, &e))).into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::error::ServiceError;
+    use std::sync::Arc;
+    use tower_sessions::MemoryStore;
+
+    async fn body(resp: Response) -> String {
+        let bytes = axum::body::to_bytes(resp.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        String::from_utf8(bytes.to_vec()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn only_server_errors_get_the_error_page() {
+        let session = Session::new(None, Arc::new(MemoryStore::default()), None);
+
+        let e = ServiceError::Invalid("name is required".into()).into();
+        let resp = error_response(&session, e).await;
+        assert_eq!(resp.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(body(resp).await, "name is required");
+
+        let e = ServiceError::NotFound("sample", 7).into();
+        let resp = error_response(&session, e).await;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+        assert!(!body(resp).await.contains("there was an error"));
+
+        let resp = error_response(&session, anyhow::anyhow!("disk full")).await;
+        assert_eq!(resp.status(), StatusCode::INTERNAL_SERVER_ERROR);
+        assert!(body(resp).await.contains("there was an error"));
+    }
+
+    #[test]
+    fn htmx_requests_are_sent_on_with_hx_location() {
+        let resp = go_to(&HeaderMap::new(), "/samples");
+        assert_eq!(resp.status(), StatusCode::SEE_OTHER);
+        assert_eq!(resp.headers()["location"], "/samples");
+
+        let mut headers = HeaderMap::new();
+        headers.insert("HX-Request", HeaderValue::from_static("true"));
+        let resp = go_to(&headers, "/samples");
+        assert_eq!(resp.status(), StatusCode::NO_CONTENT);
+        assert!(resp.headers()["HX-Location"]
+            .to_str()
+            .unwrap()
+            .contains(r#""path":"/samples""#));
+    }
 }
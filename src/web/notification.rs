@@ -0,0 +1,184 @@
+use askama::Template;
+use axum::{
+    extract::{Path, Query, State},
+    response::{Html, IntoResponse},
+    routing::{get, post},
+    Form, Router,
+};
+use http::{HeaderMap, StatusCode};
+use serde::Deserialize;
+use tower_sessions::Session;
+
+use crate::{
+    middleware::AuthedUser,
+    models::{notification::NotificationQuery, state::WebState},
+    services::{self, error::status_code},
+    templates::{base_ctx, error_page, NotificationsBadgeTmpl, NotificationsTmpl, SampleWatchTmpl},
+    web::{error_response, go_to},
+};
+
+pub fn router() -> Router<WebState> {
+    Router::new()
+        .route(
+            "/samples/{id}/watch",
+            post(watch_sample).delete(unwatch_sample),
+        )
+        .route("/notifications", get(notifications_page))
+        .route("/notifications/badge", get(notifications_badge))
+        .route("/notifications/read-all", post(mark_all_read))
+        .route("/notifications/{id}/read", post(mark_read))
+        .route("/notifications/{id}/open", get(open_notification))
+}
+
+/// The watch toggle on the sample page.
+pub async fn watch_panel(
+    state: &WebState,
+    sample_id: i64,
+    user_id: i64,
+    error: Option<String>,
+) -> SampleWatchTmpl {
+    let watching = services::notification::is_watching(state, sample_id, user_id)
+        .await
+        .unwrap_or(false);
+    SampleWatchTmpl {
+        sample_id,
+        watching,
+        error,
+    }
+}
+
+async fn render_panel(
+    state: &WebState,
+    sample_id: i64,
+    user_id: i64,
+    result: anyhow::Result<()>,
+) -> impl IntoResponse {
+    let (status, error) = match result {
+        Ok(()) => (StatusCode::OK, None),
+        Err(e) => (status_code(&e), Some(e.to_string())),
+    };
+    let panel = watch_panel(state, sample_id, user_id, error).await;
+    (status, Html(panel.render().unwrap()))
+}
+
+async fn watch_sample(
+    State(state): State<WebState>,
+    AuthedUser(user_id): AuthedUser,
+    Path(id): Path<i64>,
+) -> impl IntoResponse {
+    let result = services::notification::watch_sample(&state, id, user_id)
+        .await
+        .map(|_| ());
+    render_panel(&state, id, user_id, result).await
+}
+
+async fn unwatch_sample(
+    State(state): State<WebState>,
+    AuthedUser(user_id): AuthedUser,
+    Path(id): Path<i64>,
+) -> impl IntoResponse {
+    let result = services::notification::unwatch_sample(&state, id, user_id)
+        .await
+        .map(|_| ());
+    render_panel(&state, id, user_id, result).await
+}
+
+async fn notifications_page(
+    State(state): State<WebState>,
+    AuthedUser(user_id): AuthedUser,
+    session: Session,
+    Query(query): Query<NotificationQuery>,
+) -> impl IntoResponse {
+    let ctx = base_ctx(&session).await;
+    match services::notification::get_notifications(&state, &query, user_id).await {
+        Ok(page) => {
+            let next_page = page
+                .next_before_id
+                .map(|id| format!("/notifications?{}", query.next_page_query(id)));
+            let html = NotificationsTmpl {
+                ctx,
+                notifications: page.items,
+                unread: page.unread,
+                query,
+                next_page,
+            }
+            .render()
+            .unwrap();
+            (StatusCode::OK, Html(html))
+        }
+        Err(e) => (status_code(&e), Html(error_page(ctx, &e))),
+    }
+}
+
+/// The unread count next to the header link, polled by the layout.
+async fn notifications_badge(
+    State(state): State<WebState>,
+    AuthedUser(user_id): AuthedUser,
+) -> impl IntoResponse {
+    let unread = services::notification::unread_count(&state, user_id)
+        .await
+        .unwrap_or(0);
+    Html(NotificationsBadgeTmpl { unread }.render().unwrap())
+}
+
+/// The page's query string, so the list comes back as it was.
+#[derive(Deserialize)]
+struct BackForm {
+    #[serde(default)]
+    query: String,
+}
+
+impl BackForm {
+    fn path(&self) -> String {
+        if self.query.is_empty() {
+            "/notifications".to_string()
+        } else {
+            format!("/notifications?{}", self.query)
+        }
+    }
+}
+
+async fn mark_read(
+    State(state): State<WebState>,
+    AuthedUser(user_id): AuthedUser,
+    session: Session,
+    headers: HeaderMap,
+    Path(id): Path<i64>,
+    Form(back): Form<BackForm>,
+) -> impl IntoResponse {
+    match services::notification::mark_read(&state, id, user_id).await {
+        Ok(_) => go_to(&headers, &back.path()),
+        Err(e) => error_response(&session, e).await,
+    }
+}
+
+async fn mark_all_read(
+    State(state): State<WebState>,
+    AuthedUser(user_id): AuthedUser,
+    session: Session,
+    headers: HeaderMap,
+    Form(back): Form<BackForm>,
+) -> impl IntoResponse {
+    match services::notification::mark_all_read(&state, user_id).await {
+        Ok(_) => go_to(&headers, &back.path()),
+        Err(e) => error_response(&session, e).await,
+    }
+}
+
+/// Marks the notification read and goes to its sample, or back to the list
+/// when the sample is gone.
+async fn open_notification(
+    State(state): State<WebState>,
+    AuthedUser(user_id): AuthedUser,
+    session: Session,
+    headers: HeaderMap,
+    Path(id): Path<i64>,
+) -> impl IntoResponse {
+    match services::notification::mark_read(&state, id, user_id).await {
+        Ok(n) => match n.sample_id {
+            Some(sample_id) => go_to(&headers, &format!("/samples/{sample_id}")),
+            None => go_to(&headers, "/notifications"),
+        },
+        Err(e) => error_response(&session, e).await,
+    }
+}
@@ -0,0 +1,95 @@
+use crate::middleware::AuthedUser;
+use crate::models::revision::RevisionDiffQuery;
+use crate::models::share::SampleRole;
+use crate::models::state::WebState;
+use crate::services;
+use crate::templates::{base_ctx, error_page, Error404Tmpl, RevisionDiffTmpl, SampleRevisionsTmpl};
+use crate::web::{error_response, go_to};
+use askama::Template;
+use axum::response::IntoResponse;
+use axum::{
+    extract::{Path, Query, State},
+    response::Html,
+    routing::{get, post},
+    Router,
+};
+use http::header::CACHE_CONTROL;
+use http::{HeaderMap, HeaderValue};
+use tower_http::set_header::SetResponseHeaderLayer;
+use tower_sessions::Session;
+
+pub fn router() -> Router<WebState> {
+    Router::new()
+        .route("/samples/{id}/revisions", get(revisions_page))
+        .route("/samples/{id}/revisions/diff", get(diff_page))
+        .route(
+            "/samples/{id}/revisions/{rev}/restore",
+            post(restore_revision),
+        )
+        .layer(SetResponseHeaderLayer::if_not_present(
+            CACHE_CONTROL,
+            HeaderValue::from_static("no-store, max-age=0, must-revalidate"),
+        ))
+}
+
+async fn revisions_page(
+    State(state): State<WebState>,
+    session: Session,
+    AuthedUser(user_id): AuthedUser,
+    Path(id): Path<i64>,
+) -> Html<String> {
+    let ctx = base_ctx(&session).await;
+    let sample = services::sample::authorize(&state, id, user_id, SampleRole::Viewer).await;
+
+    let html = match sample {
+        Ok(sample) => {
+            let revisions = services::revision::get_revisions(&state, id)
+                .await
+                .unwrap_or_default();
+            SampleRevisionsTmpl {
+                ctx,
+                sample,
+                revisions,
+            }
+            .render()
+            .unwrap()
+        }
+        Err(e) => error_page(ctx, &e),
+    };
+
+    Html(html)
+}
+
+async fn diff_page(
+    State(state): State<WebState>,
+    session: Session,
+    AuthedUser(user_id): AuthedUser,
+    Path(id): Path<i64>,
+    Query(q): Query<RevisionDiffQuery>,
+) -> Html<String> {
+    let ctx = base_ctx(&session).await;
+    let sample = services::sample::authorize(&state, id, user_id, SampleRole::Viewer).await;
+
+    let html = match sample {
+        Ok(sample) => match services::revision::diff_revisions(&state, id, q.from, q.to).await {
+            Ok(Some(diff)) => RevisionDiffTmpl { ctx, sample, diff }.render().unwrap(),
+            _ => Error404Tmpl { ctx }.render().unwrap(),
+        },
+        Err(e) => error_page(ctx, &e),
+    };
+
+    Html(html)
+}
+
+async fn restore_revision(
+    State(state): State<WebState>,
+    session: Session,
+    headers: HeaderMap,
+    AuthedUser(uid): AuthedUser,
+    Path((id, rev)): Path<(i64, i64)>,
+) -> impl IntoResponse {
+    match services::revision::restore_revision(&state, id, rev, uid).await {
+        Ok(_) => go_to(&headers, &format!("/samples/{}/revisions", id)),
+        Err(e) => error_response(&session, e).await,
+    }
+}
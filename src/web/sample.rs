@@ -1,21 +1,33 @@
-use crate::middleware::{is_htmx, AuthedUser};
-use crate::models::sample::SampleInput;
+use crate::middleware::AuthedUser;
+use crate::models::sample::{SampleInput, SampleQuery, SampleSearchQuery, SampleStatus};
+use crate::models::saved_view::{list_query, SampleColumn};
+use crate::models::share::SampleRole;
 use crate::models::state::WebState;
 use crate::services;
+use crate::services::error::{status_code, ServiceError};
 use crate::templates::{
-    base_ctx, Error403Tmpl, Error404Tmpl, Error500Tmpl, SampleFormTmpl, SamplesListTmpl,
+    base_ctx, error_page, SampleConflictTmpl, SampleFormTmpl, SamplesListTmpl, SamplesRowsTmpl,
+    SamplesTrashTmpl,
 };
+use crate::web::attachment::attachments_panel;
+use crate::web::comment::comments_panel;
+use crate::web::notification::watch_panel;
+use crate::web::schedule::schedule_panel;
+use crate::web::share::shares_panel;
+use crate::web::{error_response, go_to};
 use askama::Template;
 use axum::response::IntoResponse;
 use axum::{
-    extract::{Path, State},
-    response::{Html, Redirect},
+    body::Bytes,
+    extract::{FromRequest, Path, Query, Request, State},
+    response::{Html, Redirect, Response},
     routing::{get, post},
-    Form, Router,
+    Router,
 };
-use http::header::{CACHE_CONTROL, PRAGMA};
+use http::header::{CACHE_CONTROL, ETAG, PRAGMA};
 use http::{HeaderMap, HeaderValue, StatusCode};
-use serde_json::json;
+use serde::Deserialize;
+use serde_json::{Map, Value};
 use tower_http::set_header::SetResponseHeaderLayer;
 use tower_sessions::Session;
 
@@ -24,6 +36,11 @@ pub fn router() -> Router<WebState> {
         .route("/", get(index_page))
         .route("/samples", get(samples_page))
         .route("/samples/new", get(create_page))
+        .route("/samples/search", get(search_rows))
+        .route("/samples/trash", get(trash_page))
+        .route("/samples/{id}/restore", post(restore_sample))
+        .route("/samples/{id}/reopen", post(reopen_sample))
+        .route("/samples/{id}/clone", post(clone_sample))
         .route("/samples", post(create_sample))
         .route(
             "/samples/{id}",
@@ -39,27 +56,182 @@ pub fn router() -> Router<WebState> {
         ))
 }
 
+/// The sample form. Standard fields map onto `SampleInput`, custom fields
+/// arrive as `cf.<key>` and are collected into `custom_fields`.
+struct SampleForm(SampleInput);
+
+impl<S> FromRequest<S> for SampleForm
+where
+    S: Send + Sync,
+{
+    type Rejection = Response;
+
+    async fn from_request(req: Request, state: &S) -> Result<Self, Self::Rejection> {
+        let body = Bytes::from_request(req, state)
+            .await
+            .map_err(IntoResponse::into_response)?;
+        let invalid = |e: serde_urlencoded::de::Error| {
+            (StatusCode::UNPROCESSABLE_ENTITY, e.to_string()).into_response()
+        };
+        let mut input: SampleInput = serde_urlencoded::from_bytes(&body).map_err(invalid)?;
+        let pairs: Vec<(String, String)> = serde_urlencoded::from_bytes(&body).map_err(invalid)?;
+        let custom: Map<String, Value> = pairs
+            .into_iter()
+            .filter_map(|(name, value)| {
+                name.strip_prefix("cf.")
+                    .map(|key| (key.to_string(), Value::String(value)))
+            })
+            .collect();
+        if !custom.is_empty() {
+            input.custom_fields = Some(custom);
+        }
+        Ok(SampleForm(input))
+    }
+}
+
 async fn index_page(AuthedUser(_uid): AuthedUser) -> Redirect {
     Redirect::to("/samples")
 }
 
+/// Columns picked with repeated `col` parameters.
+fn picked_columns(pairs: &[(String, String)]) -> Vec<SampleColumn> {
+    let picked: Vec<_> = pairs
+        .iter()
+        .filter(|(k, _)| k == "col")
+        .filter_map(|(_, v)| v.parse().ok())
+        .collect();
+    SampleColumn::normalize(&picked)
+}
+
+/// Besides the `SampleQuery` filters the list takes `col` for its columns and
+/// `view` for the saved view being shown. A bare `/samples` opens the user's
+/// default view if they pinned one.
 async fn samples_page(
     State(state): State<WebState>,
-    AuthedUser(_uid): AuthedUser,
+    AuthedUser(uid): AuthedUser,
     session: Session,
-) -> Html<String> {
+    Query(mut query): Query<SampleQuery>,
+    Query(pairs): Query<Vec<(String, String)>>,
+) -> impl IntoResponse {
     let ctx = base_ctx(&session).await;
-    let samples = services::sample::get_samples(&state).await;
-    let html = SamplesListTmpl { ctx, samples }.render().unwrap();
-    Html(html)
+    let mut columns = picked_columns(&pairs);
+    let mut view_id = pairs
+        .iter()
+        .find(|(k, _)| k == "view")
+        .and_then(|(_, v)| v.parse::<i64>().ok());
+    if pairs.is_empty() {
+        if let Ok(Some(view)) = services::saved_view::get_default_view(&state, uid).await {
+            query = view.filters.0;
+            columns = SampleColumn::normalize(&view.columns.0);
+            view_id = Some(view.id);
+        }
+    }
+    let views = services::saved_view::get_views(&state, uid)
+        .await
+        .unwrap_or_default();
+
+    match services::sample::get_samples(&state, &query, uid).await {
+        Ok(page) => {
+            let next_page = page.next_cursor.map(|cursor| {
+                let next = SampleQuery {
+                    cursor: Some(cursor),
+                    ..query.clone()
+                };
+                format!("/samples?{}", list_query(&next, &columns))
+            });
+            let html = SamplesListTmpl {
+                ctx,
+                samples: page.items,
+                next_page,
+                query,
+                columns,
+                all_columns: SampleColumn::ALL.to_vec(),
+                views,
+                view_id,
+            }
+            .render()
+            .unwrap();
+            Html(html).into_response()
+        }
+        Err(e) => error_response(&session, e).await,
+    }
+}
+
+/// Live search from the list page: returns just the table rows for HTMX to swap
+/// into `#rows`, with the matches marked. An empty query shows the list again
+/// with the filters on screen, which the search box sends along.
+async fn search_rows(
+    State(state): State<WebState>,
+    AuthedUser(uid): AuthedUser,
+    Query(query): Query<SampleSearchQuery>,
+    Query(filters): Query<SampleQuery>,
+    Query(pairs): Query<Vec<(String, String)>>,
+) -> impl IntoResponse {
+    let columns = picked_columns(&pairs);
+    let rows = if query.q.trim().is_empty() {
+        services::sample::get_samples(&state, &filters, uid)
+            .await
+            .map(|page| SamplesRowsTmpl::list(page.items, columns))
+    } else {
+        services::sample::search_samples(&state, &query, uid)
+            .await
+            .map(|hits| SamplesRowsTmpl::search(hits, columns))
+    };
+
+    match rows {
+        Ok(rows) => Html(rows.render().unwrap()).into_response(),
+        Err(e) => (status_code(&e), e.to_string()).into_response(),
+    }
 }
 
-async fn create_page(AuthedUser(_): AuthedUser, session: Session) -> Html<String> {
+#[derive(Deserialize)]
+struct NewSampleQuery {
+    #[serde(default, deserialize_with = "empty_as_none")]
+    template: Option<i64>,
+}
+
+/// The template picker sends `template=` for a blank sample.
+fn empty_as_none<'de, D>(deserializer: D) -> Result<Option<i64>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let raw = Option::<String>::deserialize(deserializer)?;
+    match raw.as_deref().map(str::trim) {
+        None | Some("") => Ok(None),
+        Some(id) => id.parse().map(Some).map_err(serde::de::Error::custom),
+    }
+}
+
+async fn create_page(
+    State(state): State<WebState>,
+    AuthedUser(_): AuthedUser,
+    session: Session,
+    Query(q): Query<NewSampleQuery>,
+) -> Html<String> {
     let ctx = base_ctx(&session).await;
+    let fields = services::custom_field::get_fields(&state)
+        .await
+        .unwrap_or_default();
+    let templates = services::sample_template::get_templates(&state)
+        .await
+        .unwrap_or_default();
+    let template = q
+        .template
+        .and_then(|id| templates.iter().find(|t| t.id == id).cloned());
     let html = SampleFormTmpl {
         ctx,
         s: None,
         action: "/samples".to_string(),
+        statuses: SampleStatus::ALL.to_vec(),
+        fields,
+        templates,
+        template,
+        can_edit: true,
+        attachments: None,
+        comments: None,
+        schedule: None,
+        shares: None,
+        watch: None,
     }
     .render()
     .unwrap();
@@ -72,28 +244,47 @@ async fn edit_page(
     session: Session,
     AuthedUser(user_id): AuthedUser,
     Path(id): Path<i64>,
-) -> Html<String> {
+) -> impl IntoResponse {
     let ctx = base_ctx(&session).await;
-    let sample = services::sample::get_sample_by_id(&state, &id).await;
+    let sample = services::sample::authorize(&state, id, user_id, SampleRole::Viewer).await;
 
+    let mut hm = HeaderMap::new();
     let html = match sample {
-        Some(sample) => {
-            if sample.created_by != user_id {
-                Error403Tmpl { ctx }.render().unwrap()
-            } else {
-                SampleFormTmpl {
-                    ctx,
-                    s: Some(sample),
-                    action: format!("/samples/{}", id),
-                }
-                .render()
-                .unwrap()
+        Ok(sample) => {
+            hm.insert(ETAG, HeaderValue::from_str(&sample.etag()).unwrap());
+            let can_edit = services::sample::role_for(&state, id, user_id)
+                .await
+                .is_ok_and(|role| role >= SampleRole::Editor);
+            let attachments = attachments_panel(&state, id, user_id, None).await;
+            let comments = comments_panel(&state, id, user_id, None).await;
+            let schedule = schedule_panel(&state, id, user_id, None).await;
+            let shares = shares_panel(&state, id, user_id, None).await;
+            let watch = watch_panel(&state, id, user_id, None).await;
+            let fields = services::custom_field::get_fields(&state)
+                .await
+                .unwrap_or_default();
+            SampleFormTmpl {
+                ctx,
+                fields,
+                templates: Vec::new(),
+                template: None,
+                can_edit,
+                attachments: Some(attachments),
+                comments: Some(comments),
+                schedule: Some(schedule),
+                shares: Some(shares),
+                watch: Some(watch),
+                statuses: sample.status.allowed_next().to_vec(),
+                s: Some(sample),
+                action: format!("/samples/{}", id),
             }
+            .render()
+            .unwrap()
         }
-        None => Error404Tmpl { ctx }.render().unwrap(),
+        Err(e) => error_page(ctx, &e),
     };
 
-    Html(html)
+    (hm, Html(html))
 }
 
 async fn create_sample(
@@ -101,37 +292,11 @@ async fn create_sample(
     headers: HeaderMap,
     session: Session,
     AuthedUser(uid): AuthedUser,
-    Form(input): Form<SampleInput>,
+    SampleForm(input): SampleForm,
 ) -> impl IntoResponse {
-    let ctx = base_ctx(&session).await;
     match services::sample::create_sample(&state, input, uid).await {
-        Ok(_) => {
-            if is_htmx(&headers) {
-                let payload = json!({
-                    "path": "/samples",
-                    "target": "#shell",
-                    "select": "#shell",
-                    "swap": "outerHTML swap:200ms",
-                    "pushUrl": true
-                })
-                .to_string();
-
-                let mut hm = HeaderMap::new();
-                hm.insert("HX-Location", HeaderValue::from_str(&payload).unwrap());
-                (StatusCode::NO_CONTENT, hm).into_response()
-            } else {
-                Redirect::to("/samples").into_response()
-            }
-        }
-        Err(e) => {
-            let html = Error500Tmpl {
-                ctx,
-                message: e.to_string(),
-            }
-            .render()
-            .unwrap();
-            (StatusCode::INTERNAL_SERVER_ERROR, Html(html)).into_response()
-        }
+        Ok(_) => go_to(&headers, "/samples"),
+        Err(e) => error_response(&session, e).await,
     }
 }
 
@@ -139,77 +304,166 @@ async fn update_sample(
     State(state): State<WebState>,
     headers: HeaderMap,
     session: Session,
-    AuthedUser(_uid): AuthedUser,
+    AuthedUser(uid): AuthedUser,
     Path(resource_id): Path<i64>,
-    Form(input): Form<SampleInput>,
+    SampleForm(input): SampleForm,
 ) -> impl IntoResponse {
     let ctx = base_ctx(&session).await;
-    match services::sample::update_sample_by_id(&state, input, resource_id).await {
-        Ok(_) => {
-            if is_htmx(&headers) {
-                let payload = json!({
-                    "path": "/samples",
-                    "target": "#shell",
-                    "select": "#shell",
-                    "swap": "outerHTML swap:200ms",
-                    "pushUrl": true
-                })
-                .to_string();
-
-                let mut hm = HeaderMap::new();
-                hm.insert("HX-Location", HeaderValue::from_str(&payload).unwrap());
-                (StatusCode::NO_CONTENT, hm).into_response()
-            } else {
-                Redirect::to("/samples").into_response()
+    let mine = input.clone();
+    match services::sample::update_sample_by_id(&state, input, resource_id, uid).await {
+        Ok(_) => go_to(&headers, "/samples"),
+        Err(e)
+            if matches!(
+                e.downcast_ref::<ServiceError>(),
+                Some(ServiceError::VersionConflict { .. })
+            ) =>
+        {
+            // someone saved in the meantime: show both versions and let the
+            // user decide whether to overwrite with their changes
+            match services::sample::authorize(&state, resource_id, uid, SampleRole::Viewer).await {
+                Ok(current) => {
+                    let html = SampleConflictTmpl {
+                        ctx,
+                        action: format!("/samples/{}", resource_id),
+                        mine,
+                        current,
+                    }
+                    .render()
+                    .unwrap();
+                    (StatusCode::CONFLICT, Html(html)).into_response()
+                }
+                Err(e) => (status_code(&e), Html(error_page(ctx, &e))).into_response(),
             }
         }
-        Err(e) => {
-            let html = Error500Tmpl {
-                ctx,
-                message: e.to_string(),
-            }
-            .render()
-            .unwrap();
-            (StatusCode::INTERNAL_SERVER_ERROR, Html(html)).into_response()
-        }
+        Err(e) => error_response(&session, e).await,
     }
 }
 
+/// The version the list row was rendered at. htmx sends `hx-vals` in the
+/// body of a DELETE, so the row puts it in the URL instead.
+#[derive(Deserialize)]
+struct VersionQuery {
+    version: Option<i64>,
+}
+
 async fn delete_sample(
     State(state): State<WebState>,
     session: Session,
     headers: HeaderMap,
-    AuthedUser(_): AuthedUser,
+    AuthedUser(uid): AuthedUser,
     Path(id): Path<i64>,
+    Query(q): Query<VersionQuery>,
 ) -> impl IntoResponse {
+    match services::sample::delete_sample_by_id(&state, id, q.version, uid).await {
+        Ok(_) => go_to(&headers, "/samples"),
+        Err(e) => error_response(&session, e).await,
+    }
+}
+
+async fn trash_page(
+    State(state): State<WebState>,
+    AuthedUser(uid): AuthedUser,
+    session: Session,
+) -> Html<String> {
     let ctx = base_ctx(&session).await;
-    match services::sample::delete_sample_by_id(&state, id).await {
-        Ok(_) => {
-            if is_htmx(&headers) {
-                let payload = json!({
-                    "path": "/samples",
-                    "target": "#shell",
-                    "select": "#shell",
-                    "swap": "outerHTML swap:200ms",
-                    "pushUrl": true
-                })
-                .to_string();
-
-                let mut hm = HeaderMap::new();
-                hm.insert("HX-Location", HeaderValue::from_str(&payload).unwrap());
-                (StatusCode::NO_CONTENT, hm).into_response()
-            } else {
-                Redirect::to("/samples").into_response()
-            }
-        }
-        Err(e) => {
-            let html = Error500Tmpl {
-                ctx,
-                message: e.to_string(),
-            }
-            .render()
+    let samples = services::sample::get_trashed_samples(&state, uid)
+        .await
+        .unwrap_or_default();
+    let html = SamplesTrashTmpl { ctx, samples }.render().unwrap();
+    Html(html)
+}
+
+async fn restore_sample(
+    State(state): State<WebState>,
+    session: Session,
+    headers: HeaderMap,
+    AuthedUser(uid): AuthedUser,
+    Path(id): Path<i64>,
+) -> impl IntoResponse {
+    match services::sample::restore_sample_by_id(&state, id, uid).await {
+        Ok(_) => go_to(&headers, "/samples/trash"),
+        Err(e) => error_response(&session, e).await,
+    }
+}
+
+async fn reopen_sample(
+    State(state): State<WebState>,
+    session: Session,
+    headers: HeaderMap,
+    AuthedUser(uid): AuthedUser,
+    Path(id): Path<i64>,
+) -> impl IntoResponse {
+    match services::sample::reopen_sample_by_id(&state, id, uid).await {
+        Ok(_) => go_to(&headers, &format!("/samples/{}", id)),
+        Err(e) => error_response(&session, e).await,
+    }
+}
+
+/// Duplicates a sample from the list and opens the copy for editing.
+async fn clone_sample(
+    State(state): State<WebState>,
+    session: Session,
+    headers: HeaderMap,
+    AuthedUser(uid): AuthedUser,
+    Path(id): Path<i64>,
+) -> impl IntoResponse {
+    match services::sample::clone_sample(&state, id, uid).await {
+        Ok(copy) => go_to(&headers, &format!("/samples/{}", copy.id)),
+        Err(e) => error_response(&session, e).await,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+
+    async fn rows(state: &WebState, uid: i64, q: &str, filters: SampleQuery) -> String {
+        let search = SampleSearchQuery {
+            q: q.to_string(),
+            limit: None,
+        };
+        let resp = search_rows(
+            State(state.clone()),
+            AuthedUser(uid),
+            Query(search),
+            Query(filters),
+            Query(vec![]),
+        )
+        .await
+        .into_response();
+        assert_eq!(resp.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(resp.into_body(), usize::MAX)
+            .await
             .unwrap();
-            (StatusCode::INTERNAL_SERVER_ERROR, Html(html)).into_response()
-        }
+        String::from_utf8(body.to_vec()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn search_rows_mark_matches_and_keep_filters_when_cleared() {
+        let state = testing::state().await;
+        let uid = testing::user(&state.db, "ann@example.com").await;
+        testing::sample(&state, uid, "Blue dye").await;
+        let paint = SampleInput {
+            description: Some("A deep blue <b>gloss</b>".into()),
+            status: SampleStatus::Active,
+            ..testing::input("Red paint")
+        };
+        services::sample::create_sample(&state, paint, uid)
+            .await
+            .unwrap();
+
+        let html = rows(&state, uid, "blue", SampleQuery::default()).await;
+        assert!(html.contains("<mark>Blue</mark> dye"));
+        assert!(html.contains("A deep <mark>blue</mark> &lt;b&gt;gloss"));
+
+        let active = SampleQuery {
+            status: Some(SampleStatus::Active),
+            ..Default::default()
+        };
+        let html = rows(&state, uid, " ", active).await;
+        assert!(html.contains("Red paint"));
+        assert!(!html.contains("Blue dye"));
+        assert!(!html.contains("<mark>"));
     }
 }
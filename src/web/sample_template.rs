@@ -0,0 +1,82 @@
+use crate::middleware::AuthedUser;
+use crate::models::sample_template::TemplateFromSample;
+use crate::models::state::WebState;
+use crate::services;
+use crate::services::error::status_code;
+use crate::templates::{base_ctx, SampleTemplatesTmpl};
+use crate::web::{error_response, go_to};
+use askama::Template;
+use axum::response::IntoResponse;
+use axum::{
+    extract::{Path, State},
+    response::Html,
+    routing::{delete, get, post},
+    Form, Router,
+};
+use http::{HeaderMap, StatusCode};
+use tower_sessions::Session;
+
+pub fn router() -> Router<WebState> {
+    Router::new()
+        .route("/sample-templates", get(templates_page))
+        .route("/sample-templates/{id}", delete(delete_template))
+        .route("/samples/{id}/template", post(save_as_template))
+}
+
+async fn render_page(
+    state: &WebState,
+    session: &Session,
+    user_id: i64,
+    status: StatusCode,
+    error: Option<String>,
+) -> impl IntoResponse {
+    let ctx = base_ctx(session).await;
+    let templates = services::sample_template::get_templates(state)
+        .await
+        .unwrap_or_default();
+    let html = SampleTemplatesTmpl {
+        ctx,
+        templates,
+        user_id,
+        error,
+    }
+    .render()
+    .unwrap();
+    (status, Html(html))
+}
+
+async fn templates_page(
+    State(state): State<WebState>,
+    session: Session,
+    AuthedUser(user_id): AuthedUser,
+) -> impl IntoResponse {
+    render_page(&state, &session, user_id, StatusCode::OK, None).await
+}
+
+async fn save_as_template(
+    State(state): State<WebState>,
+    session: Session,
+    headers: HeaderMap,
+    AuthedUser(user_id): AuthedUser,
+    Path(id): Path<i64>,
+    Form(form): Form<TemplateFromSample>,
+) -> impl IntoResponse {
+    match services::sample_template::template_from_sample(&state, id, form.name, user_id).await {
+        Ok(_) => go_to(&headers, "/sample-templates"),
+        Err(e) => error_response(&session, e).await,
+    }
+}
+
+async fn delete_template(
+    State(state): State<WebState>,
+    session: Session,
+    AuthedUser(user_id): AuthedUser,
+    Path(id): Path<i64>,
+) -> impl IntoResponse {
+    let (status, error) =
+        match services::sample_template::delete_template(&state, id, user_id).await {
+            Ok(()) => (StatusCode::OK, None),
+            Err(e) => (status_code(&e), Some(e.to_string())),
+        };
+    render_page(&state, &session, user_id, status, error).await
+}
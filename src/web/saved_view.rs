@@ -0,0 +1,106 @@
+use axum::{
+    extract::{Path, State},
+    response::IntoResponse,
+    routing::{delete, post},
+    Form, Router,
+};
+use http::{HeaderMap, StatusCode};
+use serde::Deserialize;
+use tower_sessions::Session;
+
+use crate::{
+    middleware::AuthedUser,
+    models::{
+        sample::SampleQuery,
+        saved_view::{SampleColumn, SavedViewInput},
+        state::WebState,
+    },
+    services,
+    web::{error_response, go_to},
+};
+
+pub fn router() -> Router<WebState> {
+    Router::new()
+        .route("/samples/views", post(save_view))
+        .route("/samples/views/{id}", delete(delete_view))
+        .route("/samples/views/{id}/default", post(toggle_default))
+}
+
+/// The save form sends the list's query string as it is on screen.
+#[derive(Deserialize)]
+struct SaveViewForm {
+    name: String,
+    #[serde(default)]
+    query: String,
+    #[serde(default)]
+    default: Option<String>,
+}
+
+impl TryFrom<SaveViewForm> for SavedViewInput {
+    type Error = serde_urlencoded::de::Error;
+
+    fn try_from(form: SaveViewForm) -> Result<Self, Self::Error> {
+        let filters: SampleQuery = serde_urlencoded::from_str(&form.query)?;
+        let pairs: Vec<(String, String)> = serde_urlencoded::from_str(&form.query)?;
+        let columns = pairs
+            .into_iter()
+            .filter(|(k, _)| k == "col")
+            .filter_map(|(_, v)| v.parse::<SampleColumn>().ok())
+            .collect();
+        Ok(SavedViewInput {
+            name: form.name,
+            filters,
+            columns,
+            is_default: form.default.is_some(),
+        })
+    }
+}
+
+async fn save_view(
+    State(state): State<WebState>,
+    AuthedUser(user_id): AuthedUser,
+    session: Session,
+    headers: HeaderMap,
+    Form(form): Form<SaveViewForm>,
+) -> impl IntoResponse {
+    let input = match SavedViewInput::try_from(form) {
+        Ok(input) => input,
+        Err(e) => return (StatusCode::UNPROCESSABLE_ENTITY, e.to_string()).into_response(),
+    };
+    match services::saved_view::save_view(&state, input, user_id).await {
+        Ok(view) => go_to(&headers, &format!("/samples?{}", view.list_query())),
+        Err(e) => error_response(&session, e).await,
+    }
+}
+
+/// Pins the view as the default, or unpins it if it already is.
+async fn toggle_default(
+    State(state): State<WebState>,
+    AuthedUser(user_id): AuthedUser,
+    session: Session,
+    headers: HeaderMap,
+    Path(id): Path<i64>,
+) -> impl IntoResponse {
+    let result = async {
+        let view = services::saved_view::get_view(&state, id, user_id).await?;
+        services::saved_view::set_default(&state, id, !view.is_default, user_id).await
+    }
+    .await;
+    match result {
+        Ok(view) => go_to(&headers, &format!("/samples?{}", view.list_query())),
+        Err(e) => error_response(&session, e).await,
+    }
+}
+
+async fn delete_view(
+    State(state): State<WebState>,
+    AuthedUser(user_id): AuthedUser,
+    session: Session,
+    headers: HeaderMap,
+    Path(id): Path<i64>,
+) -> impl IntoResponse {
+    match services::saved_view::delete_view(&state, id, user_id).await {
+        Ok(()) => go_to(&headers, "/samples?view="),
+        Err(e) => error_response(&session, e).await,
+    }
+}
@@ -0,0 +1,107 @@
+use crate::middleware::AuthedUser;
+use crate::models::sample::SampleStatus;
+use crate::models::schedule::ScheduleInput;
+use crate::models::share::SampleRole;
+use crate::models::state::WebState;
+use crate::services;
+use crate::services::error::status_code;
+use crate::templates::SampleScheduleTmpl;
+use askama::Template;
+use axum::response::IntoResponse;
+use axum::{
+    extract::{Path, State},
+    response::Html,
+    routing::{delete, post},
+    Form, Router,
+};
+use http::StatusCode;
+use serde::Deserialize;
+
+pub fn router() -> Router<WebState> {
+    Router::new()
+        .route("/samples/{id}/schedule", post(schedule_action))
+        .route("/samples/{id}/schedule/{action_id}", delete(cancel_action))
+}
+
+/// The schedule form. Whichever of the two timing fields is filled in wins.
+#[derive(Deserialize)]
+struct ScheduleForm {
+    status: SampleStatus,
+    #[serde(default)]
+    run_at: String,
+    #[serde(default)]
+    after_days: String,
+}
+
+impl From<ScheduleForm> for ScheduleInput {
+    fn from(form: ScheduleForm) -> Self {
+        let after_days = form.after_days.trim();
+        ScheduleInput {
+            status: form.status,
+            run_at: Some(form.run_at).filter(|r| !r.trim().is_empty()),
+            after_days: if after_days.is_empty() {
+                None
+            } else {
+                // anything unparseable is reported like an out of range value
+                Some(after_days.parse().unwrap_or(-1))
+            },
+        }
+    }
+}
+
+/// Upcoming and past scheduled changes for the sample page.
+pub async fn schedule_panel(
+    state: &WebState,
+    sample_id: i64,
+    user_id: i64,
+    error: Option<String>,
+) -> SampleScheduleTmpl {
+    let actions = services::schedule::get_actions(state, sample_id, user_id)
+        .await
+        .unwrap_or_default();
+    let can_edit = services::sample::role_for(state, sample_id, user_id)
+        .await
+        .is_ok_and(|role| role >= SampleRole::Editor);
+    SampleScheduleTmpl {
+        sample_id,
+        actions,
+        statuses: SampleStatus::ALL.to_vec(),
+        can_edit,
+        error,
+    }
+}
+
+async fn render_panel(
+    state: &WebState,
+    sample_id: i64,
+    user_id: i64,
+    result: anyhow::Result<()>,
+) -> impl IntoResponse {
+    let (status, error) = match result {
+        Ok(()) => (StatusCode::OK, None),
+        Err(e) => (status_code(&e), Some(e.to_string())),
+    };
+    let panel = schedule_panel(state, sample_id, user_id, error).await;
+    (status, Html(panel.render().unwrap()))
+}
+
+async fn schedule_action(
+    State(state): State<WebState>,
+    AuthedUser(user_id): AuthedUser,
+    Path(id): Path<i64>,
+    Form(form): Form<ScheduleForm>,
+) -> impl IntoResponse {
+    let result = services::schedule::schedule_action(&state, id, form.into(), user_id)
+        .await
+        .map(|_| ());
+    render_panel(&state, id, user_id, result).await
+}
+
+async fn cancel_action(
+    State(state): State<WebState>,
+    AuthedUser(user_id): AuthedUser,
+    Path((id, action_id)): Path<(i64, i64)>,
+) -> impl IntoResponse {
+    let result = services::schedule::cancel_action(&state, id, action_id, user_id).await;
+    render_panel(&state, id, user_id, result).await
+}
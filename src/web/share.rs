@@ -0,0 +1,79 @@
+use crate::middleware::AuthedUser;
+use crate::models::share::{SampleRole, ShareInput};
+use crate::models::state::WebState;
+use crate::services;
+use crate::services::error::status_code;
+use crate::templates::SampleSharesTmpl;
+use askama::Template;
+use axum::response::IntoResponse;
+use axum::{
+    extract::{Path, State},
+    response::Html,
+    routing::{delete, post},
+    Form, Router,
+};
+use http::StatusCode;
+
+pub fn router() -> Router<WebState> {
+    Router::new()
+        .route("/samples/{id}/shares", post(share_sample))
+        .route("/samples/{id}/shares/{user_id}", delete(unshare_sample))
+}
+
+/// Who has access to the sample, with owner controls to change it.
+pub async fn shares_panel(
+    state: &WebState,
+    sample_id: i64,
+    user_id: i64,
+    error: Option<String>,
+) -> SampleSharesTmpl {
+    let shares = services::share::get_shares(state, sample_id, user_id)
+        .await
+        .unwrap_or_default();
+    let is_owner = shares
+        .iter()
+        .any(|s| s.user_id == user_id && s.role == SampleRole::Owner);
+    SampleSharesTmpl {
+        sample_id,
+        shares,
+        roles: SampleRole::ALL.to_vec(),
+        user_id,
+        is_owner,
+        error,
+    }
+}
+
+async fn render_panel(
+    state: &WebState,
+    sample_id: i64,
+    user_id: i64,
+    result: anyhow::Result<()>,
+) -> impl IntoResponse {
+    let (status, error) = match result {
+        Ok(()) => (StatusCode::OK, None),
+        Err(e) => (status_code(&e), Some(e.to_string())),
+    };
+    let panel = shares_panel(state, sample_id, user_id, error).await;
+    (status, Html(panel.render().unwrap()))
+}
+
+async fn share_sample(
+    State(state): State<WebState>,
+    AuthedUser(user_id): AuthedUser,
+    Path(id): Path<i64>,
+    Form(input): Form<ShareInput>,
+) -> impl IntoResponse {
+    let result = services::share::share_sample(&state, id, input, user_id)
+        .await
+        .map(|_| ());
+    render_panel(&state, id, user_id, result).await
+}
+
+async fn unshare_sample(
+    State(state): State<WebState>,
+    AuthedUser(user_id): AuthedUser,
+    Path((id, target)): Path<(i64, i64)>,
+) -> impl IntoResponse {
+    let result = services::share::unshare_sample(&state, id, target, user_id).await;
+    render_panel(&state, id, user_id, result).await
+}
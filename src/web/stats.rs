@@ -0,0 +1,46 @@
+use askama::Template;
+use axum::{
+    extract::{Query, State},
+    response::{Html, IntoResponse},
+    routing::get,
+    Router,
+};
+use http::StatusCode;
+use tower_sessions::Session;
+
+use crate::{
+    middleware::AuthedUser,
+    models::{
+        state::WebState,
+        stats::{Bucket, StatsQuery},
+    },
+    services::{self, error::status_code},
+    templates::{base_ctx, DashboardTmpl},
+};
+
+pub fn router() -> Router<WebState> {
+    Router::new().route("/dashboard", get(dashboard_page))
+}
+
+async fn dashboard_page(
+    State(state): State<WebState>,
+    AuthedUser(uid): AuthedUser,
+    session: Session,
+    Query(query): Query<StatsQuery>,
+) -> impl IntoResponse {
+    let ctx = base_ctx(&session).await;
+    let (stats, status, error) = match services::stats::get_stats(&state, &query, uid).await {
+        Ok(stats) => (Some(stats), StatusCode::OK, None),
+        Err(e) => (None, status_code(&e), Some(e.to_string())),
+    };
+    let html = DashboardTmpl {
+        ctx,
+        stats,
+        query,
+        buckets: Bucket::ALL.to_vec(),
+        error,
+    }
+    .render()
+    .unwrap();
+    (status, Html(html))
+}